@@ -0,0 +1,314 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use serde::Serialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use regtidy::error::{http_error, ErrorKind};
+use regtidy::executor::Outcome;
+
+/// Who ran regtidy
+#[derive(Debug, Clone, Serialize)]
+pub struct Operator {
+    pub user: String,
+    pub host: String,
+}
+
+impl Operator {
+    pub fn current() -> Self {
+        let user = std::env::var("USER")
+            .or_else(|_| std::env::var("USERNAME"))
+            .unwrap_or_else(|_| "unknown".to_string());
+        let host = hostname::get()
+            .ok()
+            .and_then(|h| h.into_string().ok())
+            .unwrap_or_else(|| "unknown".to_string());
+        Self { user, host }
+    }
+}
+
+/// Append-only JSON lines log of deletions, optionally hash-chained
+pub struct AuditLog {
+    path: PathBuf,
+    file: File,
+    registry: String,
+    run_id: String,
+    operator: Operator,
+    chain: bool,
+}
+
+impl AuditLog {
+    pub fn open(path: &Path, registry: &str, chain: bool) -> Result<Self> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .read(true)
+            .open(path)
+            .with_context(|| format!("Failed to open audit log {}", path.display()))?;
+
+        let now = Utc::now();
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            registry: registry.to_string(),
+            run_id: format!("{}-{:x}", now.format("%Y%m%dT%H%M%SZ"), std::process::id()),
+            operator: Operator::current(),
+            chain,
+        })
+    }
+
+    pub fn run_id(&self) -> &str {
+        &self.run_id
+    }
+
+    /// Append the record for one digest and the tags that pointed at it
+    pub fn record(
+        &mut self,
+        repository: &str,
+        digest: &str,
+        tags: &[String],
+        rule: &str,
+        dry_run: bool,
+        result: Outcome,
+    ) -> Result<()> {
+        let (result, http_status, error_kind, error) = match result {
+            Outcome::Planned => ("planned", None, None, None),
+            Outcome::Deleted(status) => ("deleted", Some(status), None, None),
+            Outcome::Failed(e) => (
+                "failed",
+                http_error(e).map(|h| h.status),
+                Some(ErrorKind::of(e)),
+                Some(format!("{:#}", e)),
+            ),
+        };
+
+        let mut record = json!({
+            "timestamp": Utc::now().to_rfc3339(),
+            "run_id": self.run_id,
+            "registry": self.registry,
+            "repository": repository,
+            "digest": digest,
+            "tags": tags,
+            "rule": rule,
+            "dry_run": dry_run,
+            "result": result,
+            "http_status": http_status,
+            "error_kind": error_kind,
+            "error": error,
+            "operator": self.operator,
+        });
+
+        // Hold the lock across reading the previous hash and appending,
+        // so concurrent runs cannot fork the chain
+        self.file
+            .lock()
+            .with_context(|| format!("Failed to lock {}", self.path.display()))?;
+        let written = self.append(&mut record);
+        let _ = self.file.unlock();
+        written.with_context(|| format!("Failed to write audit log {}", self.path.display()))
+    }
+
+    fn append(&mut self, record: &mut Value) -> Result<()> {
+        if self.chain {
+            let prev = last_line(&mut self.file)?
+                .and_then(|line| serde_json::from_str::<Value>(&line).ok())
+                .and_then(|v| v.get("hash").and_then(|h| h.as_str()).map(String::from));
+            let hash = chain_hash(prev.as_deref(), record);
+            record["prev_hash"] = json!(prev);
+            record["hash"] = json!(hash);
+        }
+        let mut line = serde_json::to_string(record)?;
+        line.push('\n');
+        self.file.write_all(line.as_bytes())?;
+        self.file.flush()?;
+        Ok(())
+    }
+}
+
+/// sha256 over the previous hash and the record without its chain fields
+fn chain_hash(prev: Option<&str>, record: &Value) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(prev.unwrap_or("").as_bytes());
+    hasher.update(b"\n");
+    hasher.update(serde_json::to_string(record).unwrap_or_default().as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+/// Last non-empty line of the file, read backwards from the end
+fn last_line(file: &mut File) -> Result<Option<String>> {
+    let len = file.seek(SeekFrom::End(0))?;
+    let mut window: u64 = 4096;
+    loop {
+        let start = len.saturating_sub(window);
+        file.seek(SeekFrom::Start(start))?;
+        let mut buf = Vec::new();
+        Read::by_ref(file).take(len - start).read_to_end(&mut buf)?;
+        let text = String::from_utf8_lossy(&buf);
+        let trimmed = text.trim_end_matches('\n');
+        match trimmed.rfind('\n') {
+            Some(pos) => return Ok(Some(trimmed[pos + 1..].to_string())),
+            None if start == 0 => return Ok(Some(trimmed.to_string()).filter(|l| !l.is_empty())),
+            None => window *= 4,
+        }
+    }
+}
+
+/// Summary of a verified audit log
+#[derive(Debug, PartialEq, Eq)]
+pub struct VerifyReport {
+    pub entries: usize,
+    /// Entries before the first hash-chained one
+    pub unchained: usize,
+}
+
+/// Check that every entry after the first chained one links to its predecessor
+pub fn verify(path: &Path) -> Result<VerifyReport> {
+    let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut report = VerifyReport {
+        entries: 0,
+        unchained: 0,
+    };
+    let mut prev: Option<String> = None;
+    let mut chained = false;
+
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let lineno = index + 1;
+        let mut record: Value = serde_json::from_str(&line)
+            .with_context(|| format!("Line {} is not valid JSON", lineno))?;
+        report.entries += 1;
+
+        let Some(object) = record.as_object_mut() else {
+            anyhow::bail!("Line {} is not a JSON object", lineno);
+        };
+        let hash = object.remove("hash");
+        let prev_hash = object.remove("prev_hash");
+        let Some(hash) = hash.as_ref().and_then(|h| h.as_str()) else {
+            if chained {
+                anyhow::bail!("Line {} is missing its hash; the chain is broken", lineno);
+            }
+            report.unchained += 1;
+            prev = None;
+            continue;
+        };
+
+        let claimed_prev = prev_hash.as_ref().and_then(|p| p.as_str());
+        if claimed_prev != prev.as_deref() {
+            anyhow::bail!(
+                "Line {} does not follow line {}: an entry was removed or reordered",
+                lineno,
+                lineno - 1
+            );
+        }
+        if chain_hash(claimed_prev, &record) != hash {
+            anyhow::bail!("Line {} was modified (hash mismatch)", lineno);
+        }
+        chained = true;
+        prev = Some(hash.to_string());
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regtidy::error::AppError;
+    use std::fs;
+
+    fn temp_log(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "regtidy-audit-{}-{}.jsonl",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    fn write_entries(path: &Path, chain: bool) {
+        let mut log = AuditLog::open(path, "http://registry:5000", chain).unwrap();
+        let tags = vec!["v1".to_string(), "latest".to_string()];
+        log.record(
+            "app",
+            "sha256:a",
+            &tags,
+            "keep=5",
+            false,
+            Outcome::Deleted(202),
+        )
+        .unwrap();
+        let error: anyhow::Error = AppError::from_response("DELETE manifest", 405, None, "").into();
+        log.record(
+            "app",
+            "sha256:b",
+            &["v0".to_string()],
+            "keep=5",
+            false,
+            Outcome::Failed(&error),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_record_fields() {
+        let path = temp_log("fields");
+        write_entries(&path, false);
+
+        let text = fs::read_to_string(&path).unwrap();
+        let lines: Vec<Value> = text
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["tags"], json!(["v1", "latest"]));
+        assert_eq!(lines[0]["result"], "deleted");
+        assert_eq!(lines[0]["http_status"], 202);
+        assert_eq!(lines[0]["rule"], "keep=5");
+        assert_eq!(lines[1]["result"], "failed");
+        assert_eq!(lines[1]["http_status"], 405);
+        assert_eq!(lines[1]["error_kind"], "deletion_disabled");
+        assert_eq!(lines[0]["run_id"], lines[1]["run_id"]);
+        assert!(lines[0].get("hash").is_none());
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_chain_verify_and_tamper() {
+        let path = temp_log("chain");
+        write_entries(&path, false);
+        write_entries(&path, true);
+        write_entries(&path, true);
+        assert_eq!(
+            verify(&path).unwrap(),
+            VerifyReport {
+                entries: 6,
+                unchained: 2
+            }
+        );
+
+        let text = fs::read_to_string(&path).unwrap();
+        let mut lines: Vec<&str> = text.lines().collect();
+
+        let tampered = text.replace("\"sha256:b\"", "\"sha256:c\"");
+        fs::write(&path, tampered).unwrap();
+        assert!(verify(&path).is_err());
+
+        lines.remove(3);
+        fs::write(&path, lines.join("\n")).unwrap();
+        let err = verify(&path).unwrap_err().to_string();
+        assert!(err.contains("removed or reordered"), "{}", err);
+        let _ = fs::remove_file(&path);
+    }
+}
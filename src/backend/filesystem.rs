@@ -0,0 +1,467 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+
+use super::RegistryBackend;
+use crate::doctor::Check;
+use crate::error::ErrorKind;
+use crate::models::{ImageConfig, Manifest, ManifestRevision, TagInfo};
+use crate::registry::RegistryClient;
+use crate::report::Reporter;
+
+/// Reads the docker/distribution filesystem storage layout directly, without a running server.
+///
+/// Layout (relative to the `docker/registry/v2` directory):
+///   repositories/<name>/_manifests/tags/<tag>/current/link  → manifest digest
+///   repositories/<name>/_manifests/revisions/sha256/<hex>/link
+///   repositories/<name>/_layers/sha256/<hex>/link
+///   blobs/sha256/<hex[..2]>/<hex>/data                       → blob content
+///
+/// The storage is opened read-only. Deletions are forwarded to a registry
+/// API client when one is configured, so that the server keeps its own
+/// bookkeeping consistent.
+#[derive(Clone)]
+pub struct FilesystemBackend {
+    root: PathBuf,
+    reporter: Arc<dyn Reporter>,
+    delete_client: Option<RegistryClient>,
+}
+
+impl FilesystemBackend {
+    /// `path` may point at the storage root (containing `docker/`) or at the `v2` directory itself
+    pub fn new(path: &Path, reporter: Arc<dyn Reporter>) -> Result<Self> {
+        let nested = path.join("docker").join("registry").join("v2");
+        let root = if nested.is_dir() {
+            nested
+        } else if path.join("repositories").is_dir() || path.join("blobs").is_dir() {
+            path.to_path_buf()
+        } else {
+            anyhow::bail!(
+                "{} does not look like a registry storage directory (no docker/registry/v2)",
+                path.display()
+            );
+        };
+
+        Ok(Self {
+            root,
+            reporter,
+            delete_client: None,
+        })
+    }
+
+    /// Forward deletions to the registry API
+    pub fn with_delete_client(mut self, client: RegistryClient) -> Self {
+        self.delete_client = Some(client);
+        self
+    }
+
+    fn repo_dir(&self, repo: &str) -> PathBuf {
+        self.root.join("repositories").join(repo)
+    }
+
+    fn blob_path(&self, digest: &str) -> Result<PathBuf> {
+        let (algorithm, hex) = digest
+            .split_once(':')
+            .filter(|(_, hex)| hex.len() > 2)
+            .with_context(|| format!("Invalid digest {}", digest))?;
+        Ok(self
+            .root
+            .join("blobs")
+            .join(algorithm)
+            .join(&hex[..2])
+            .join(hex)
+            .join("data"))
+    }
+
+    fn read_blob(&self, digest: &str) -> Result<Vec<u8>> {
+        let path = self.blob_path(digest)?;
+        tracing::trace!(path = %path.display(), "reading blob");
+        fs::read(&path).with_context(|| format!("Failed to read blob {}", digest))
+    }
+
+    fn scan_repositories(&self) -> Result<Vec<String>> {
+        let base = self.root.join("repositories");
+        let mut repos = Vec::new();
+        if base.is_dir() {
+            collect_repositories(&base, &base, &mut repos)?;
+        }
+        repos.sort();
+        Ok(repos)
+    }
+
+    fn scan_tags(&self, repo: &str) -> Result<Vec<(String, String)>> {
+        let tags_dir = self.repo_dir(repo).join("_manifests").join("tags");
+        let entries = match fs::read_dir(&tags_dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                if !self.repo_dir(repo).is_dir() {
+                    anyhow::bail!("Repository {} not found in {}", repo, self.root.display());
+                }
+                return Ok(Vec::new());
+            }
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read {}", tags_dir.display()))
+            }
+        };
+
+        let mut tags = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            let tag = entry.file_name().to_string_lossy().into_owned();
+            // A tag directory without current/link is a deleted tag
+            match read_link(&entry.path().join("current").join("link")) {
+                Ok(digest) => tags.push((tag, digest)),
+                Err(e) => self
+                    .reporter
+                    .debug(format!("Skipping tag {}:{}: {:#}", repo, tag, e)),
+            }
+        }
+        tags.sort();
+        Ok(tags)
+    }
+
+    fn resolve_tag(&self, repo: &str, tag: &str, digest: String) -> Result<TagInfo> {
+        let manifest: Manifest = serde_json::from_slice(&self.read_blob(&digest)?)
+            .with_context(|| format!("Failed to parse manifest for {}:{}", repo, tag))?;
+
+        let created = match &manifest.config {
+            Some(config) => match self
+                .read_blob(&config.digest)
+                .and_then(|bytes| Ok(serde_json::from_slice::<ImageConfig>(&bytes)?))
+            {
+                Ok(img_config) => img_config.created,
+                Err(e) => {
+                    self.reporter.debug(format!(
+                        "Could not read image config for {}:{}: {}",
+                        repo, tag, e
+                    ));
+                    None
+                }
+            },
+            None => None,
+        };
+
+        Ok(TagInfo {
+            repository: repo.to_string(),
+            tag: tag.to_string(),
+            digest,
+            created,
+            size: manifest.total_size(),
+            pushed: None,
+            last_pulled: None,
+            blobs: manifest.blobs(),
+        })
+    }
+
+    fn resolve_tags(&self, repo: &str) -> Result<Vec<TagInfo>> {
+        let mut infos = Vec::new();
+        for (tag, digest) in self.scan_tags(repo)? {
+            match self.resolve_tag(repo, &tag, digest) {
+                Ok(info) => infos.push(info),
+                Err(e) => self
+                    .reporter
+                    .error(format!("Failed to resolve {}:{}: {:#}", repo, tag, e)),
+            }
+        }
+        Ok(infos)
+    }
+
+    fn scan_revisions(&self, repo: &str) -> Result<Vec<ManifestRevision>> {
+        let mut tags_by_digest: HashMap<String, Vec<String>> = HashMap::new();
+        for (tag, digest) in self.scan_tags(repo)? {
+            tags_by_digest.entry(digest).or_default().push(tag);
+        }
+
+        let revisions_dir = self.repo_dir(repo).join("_manifests").join("revisions");
+        let mut revisions = Vec::new();
+        let algorithms = match fs::read_dir(&revisions_dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(revisions),
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("Failed to read {}", revisions_dir.display()))
+            }
+        };
+
+        for algorithm in algorithms {
+            let algorithm = algorithm?;
+            for entry in fs::read_dir(algorithm.path())? {
+                let entry = entry?;
+                // Deleting a manifest removes its revision link but leaves the directory
+                let digest = match read_link(&entry.path().join("link")) {
+                    Ok(digest) => digest,
+                    Err(_) => continue,
+                };
+
+                let manifest: Manifest = match self
+                    .read_blob(&digest)
+                    .and_then(|bytes| Ok(serde_json::from_slice(&bytes)?))
+                {
+                    Ok(manifest) => manifest,
+                    Err(e) => {
+                        self.reporter
+                            .warn(format!("Skipping revision {}@{}: {:#}", repo, digest, e));
+                        continue;
+                    }
+                };
+
+                revisions.push(ManifestRevision {
+                    tags: tags_by_digest.remove(&digest).unwrap_or_default(),
+                    children: manifest
+                        .manifests
+                        .iter()
+                        .map(|m| m.digest.clone())
+                        .collect(),
+                    subject: manifest.subject.as_ref().map(|s| s.digest.clone()),
+                    size: manifest.total_size(),
+                    digest,
+                });
+            }
+        }
+
+        revisions.sort_by(|a, b| a.digest.cmp(&b.digest));
+        Ok(revisions)
+    }
+
+    /// Run a blocking filesystem scan off the async runtime
+    async fn blocking<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&FilesystemBackend) -> Result<T> + Send + 'static,
+    {
+        let backend = self.clone();
+        tokio::task::spawn_blocking(move || f(&backend))
+            .await
+            .context("Task join error")?
+    }
+}
+
+#[async_trait]
+impl RegistryBackend for FilesystemBackend {
+    fn location(&self) -> String {
+        self.root.display().to_string()
+    }
+
+    async fn list_repositories(&self) -> Result<Vec<String>> {
+        self.blocking(|b| b.scan_repositories()).await
+    }
+
+    async fn list_tags(&self, repo: &str) -> Result<Vec<String>> {
+        let repo = repo.to_string();
+        let tags = self.blocking(move |b| b.scan_tags(&repo)).await?;
+        Ok(tags.into_iter().map(|(tag, _)| tag).collect())
+    }
+
+    async fn resolve_all_tags(&self, repo: &str) -> Result<Vec<TagInfo>> {
+        let repo = repo.to_string();
+        self.blocking(move |b| b.resolve_tags(&repo)).await
+    }
+
+    async fn list_revisions(&self, repo: &str) -> Result<Vec<ManifestRevision>> {
+        let repo = repo.to_string();
+        self.blocking(move |b| b.scan_revisions(&repo)).await
+    }
+
+    async fn delete_manifest(&self, repo: &str, digest: &str) -> Result<u16> {
+        match &self.delete_client {
+            Some(client) => client.delete_manifest(repo, digest).await,
+            None => anyhow::bail!(
+                "Filesystem storage is read-only; pass --registry to delete through the registry API"
+            ),
+        }
+    }
+
+    fn can_delete(&self) -> bool {
+        self.delete_client.is_some()
+    }
+
+    /// Storage readability, then the checks of the registry that deletes
+    async fn diagnose(&self, repo: Option<&str>) -> Vec<Check> {
+        let storage = match self.list_repositories().await {
+            Ok(repos) => Check::ok("storage", format!("{} repositories", repos.len())),
+            Err(e) => Check::fail("storage", format!("{:#}", e), ErrorKind::of(&e)),
+        };
+        let mut checks = vec![storage];
+        match &self.delete_client {
+            Some(client) => checks.extend(client.diagnose(repo).await),
+            None => checks.push(Check::fail(
+                "delete",
+                "storage is read-only; pass --registry to delete through the registry API",
+                ErrorKind::General,
+            )),
+        }
+        checks
+    }
+}
+
+/// Recursively find repository directories (those containing `_manifests`)
+fn collect_repositories(base: &Path, dir: &Path, repos: &mut Vec<String>) -> Result<()> {
+    if dir.join("_manifests").is_dir() {
+        let name = dir.strip_prefix(base).unwrap_or(dir);
+        repos.push(name.to_string_lossy().replace('\\', "/"));
+    }
+
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))? {
+        let entry = entry?;
+        let name = entry.file_name();
+        // _manifests, _layers and _uploads hold repository data, not nested repositories
+        if name.to_string_lossy().starts_with('_') || !entry.file_type()?.is_dir() {
+            continue;
+        }
+        collect_repositories(base, &entry.path(), repos)?;
+    }
+
+    Ok(())
+}
+
+/// Read a `link` file containing a digest
+fn read_link(path: &Path) -> Result<String> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(content.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::Silent;
+    use sha2::{Digest, Sha256};
+
+    /// Minimal on-disk registry used by the tests
+    struct Fixture {
+        dir: PathBuf,
+    }
+
+    impl Fixture {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!(
+                "regtidy-fs-test-{}-{}",
+                std::process::id(),
+                name
+            ));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(dir.join("docker/registry/v2/repositories")).unwrap();
+            Self { dir }
+        }
+
+        fn v2(&self) -> PathBuf {
+            self.dir.join("docker/registry/v2")
+        }
+
+        fn put_blob(&self, content: &[u8]) -> String {
+            let hex = format!("{:x}", Sha256::digest(content));
+            let path = self.v2().join("blobs/sha256").join(&hex[..2]).join(&hex);
+            fs::create_dir_all(&path).unwrap();
+            fs::write(path.join("data"), content).unwrap();
+            format!("sha256:{}", hex)
+        }
+
+        fn put_revision(&self, repo: &str, manifest: &[u8]) -> String {
+            let digest = self.put_blob(manifest);
+            let link_dir = self
+                .v2()
+                .join("repositories")
+                .join(repo)
+                .join("_manifests/revisions/sha256")
+                .join(digest.trim_start_matches("sha256:"));
+            fs::create_dir_all(&link_dir).unwrap();
+            fs::write(link_dir.join("link"), &digest).unwrap();
+            digest
+        }
+
+        fn put_image(&self, repo: &str, tag: &str, created: &str) -> String {
+            let config = format!(r#"{{"created":"{}"}}"#, created);
+            let config_digest = self.put_blob(config.as_bytes());
+            let manifest = format!(
+                r#"{{"schemaVersion":2,"config":{{"mediaType":"application/vnd.docker.container.image.v1+json","size":{},"digest":"{}"}},"layers":[{{"mediaType":"application/vnd.docker.image.rootfs.diff.tar.gzip","size":100,"digest":"sha256:00"}}]}}"#,
+                config.len(),
+                config_digest
+            );
+            let digest = self.put_revision(repo, manifest.as_bytes());
+
+            let tag_dir = self
+                .v2()
+                .join("repositories")
+                .join(repo)
+                .join("_manifests/tags")
+                .join(tag)
+                .join("current");
+            fs::create_dir_all(&tag_dir).unwrap();
+            fs::write(tag_dir.join("link"), &digest).unwrap();
+            digest
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_list_and_resolve() {
+        let fx = Fixture::new("resolve");
+        let digest = fx.put_image("team/app", "v1", "2024-01-02T03:04:05Z");
+        fx.put_image("team/app", "v2", "2024-02-02T03:04:05Z");
+        fx.put_image("base", "latest", "2023-01-01T00:00:00Z");
+
+        let backend = FilesystemBackend::new(&fx.dir, Arc::new(Silent)).unwrap();
+
+        let repos = backend.list_repositories().await.unwrap();
+        assert_eq!(repos, vec!["base", "team/app"]);
+
+        let tags = backend.resolve_all_tags("team/app").await.unwrap();
+        assert_eq!(tags.len(), 2);
+        let v1 = tags.iter().find(|t| t.tag == "v1").unwrap();
+        assert_eq!(v1.digest, digest);
+        assert_eq!(
+            v1.created.unwrap().to_rfc3339(),
+            "2024-01-02T03:04:05+00:00"
+        );
+        assert!(v1.size.unwrap() > 100);
+    }
+
+    #[tokio::test]
+    async fn test_dangling_repository_has_no_tags() {
+        let fx = Fixture::new("dangling");
+        fs::create_dir_all(fx.v2().join("repositories/empty/_manifests/revisions")).unwrap();
+
+        let backend = FilesystemBackend::new(&fx.v2(), Arc::new(Silent)).unwrap();
+        assert_eq!(backend.list_repositories().await.unwrap(), vec!["empty"]);
+        assert!(backend.list_tags("empty").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_list_revisions_includes_untagged() {
+        let fx = Fixture::new("revisions");
+        let tagged = fx.put_image("app", "latest", "2024-01-01T00:00:00Z");
+        let index = format!(
+            r#"{{"schemaVersion":2,"manifests":[{{"mediaType":"application/vnd.oci.image.manifest.v1+json","size":1,"digest":"{}"}}]}}"#,
+            tagged
+        );
+        let untagged = fx.put_revision("app", index.as_bytes());
+
+        let backend = FilesystemBackend::new(&fx.dir, Arc::new(Silent)).unwrap();
+        let revisions = backend.list_revisions("app").await.unwrap();
+        assert_eq!(revisions.len(), 2);
+
+        let t = revisions.iter().find(|r| r.digest == tagged).unwrap();
+        assert_eq!(t.tags, vec!["latest"]);
+        let u = revisions.iter().find(|r| r.digest == untagged).unwrap();
+        assert!(u.tags.is_empty());
+        assert_eq!(u.children, vec![tagged]);
+    }
+
+    #[tokio::test]
+    async fn test_delete_requires_registry() {
+        let fx = Fixture::new("readonly");
+        let backend = FilesystemBackend::new(&fx.dir, Arc::new(Silent)).unwrap();
+        assert!(!backend.can_delete());
+        assert!(backend.delete_manifest("app", "sha256:abc").await.is_err());
+    }
+}
@@ -0,0 +1,345 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use reqwest::Client;
+use serde::Deserialize;
+use tokio::sync::Mutex;
+
+use super::{
+    check_status, encode_segment, http_client, send, Auth, Paging, RegistryBackend, Tls,
+    DEFAULT_RETRIES,
+};
+use crate::models::TagInfo;
+use crate::report::Reporter;
+
+/// Default `limit` of list requests; Gitea caps it at its MAX_RESPONSE_ITEMS
+const PAGE_LIMIT: usize = 50;
+
+/// Gitea / Forgejo package API (`/api/v1/packages/<owner>`).
+///
+/// Each tag of a container image is a package version. Untagged manifests
+/// (e.g. the per-platform children of a multi-arch image) show up as
+/// versions named after their digest and are never treated as tags.
+pub struct GiteaBackend {
+    client: Client,
+    base_url: String,
+    owner: String,
+    reporter: Arc<dyn Reporter>,
+    page_limit: usize,
+    retries: u32,
+    /// The owner's package versions, fetched once per run
+    versions: Mutex<Option<Vec<PackageVersion>>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct PackageVersion {
+    name: String,
+    version: String,
+    created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PackageFile {
+    name: String,
+    size: Option<u64>,
+    sha256: String,
+}
+
+impl GiteaBackend {
+    pub fn new(
+        base_url: &str,
+        owner: &str,
+        auth: &Auth,
+        tls: &Tls,
+        reporter: Arc<dyn Reporter>,
+    ) -> Result<Self> {
+        Ok(Self {
+            client: http_client(auth, tls)?,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            owner: owner.to_string(),
+            reporter,
+            page_limit: PAGE_LIMIT,
+            retries: DEFAULT_RETRIES,
+            versions: Mutex::new(None),
+        })
+    }
+
+    /// Items per page of the package list
+    pub fn with_page_size(mut self, n: u32) -> Self {
+        self.page_limit = n as usize;
+        self
+    }
+
+    /// Retry a GET or HEAD up to `n` times after a transient failure
+    pub fn with_retries(mut self, n: u32) -> Self {
+        self.retries = n;
+        self
+    }
+
+    async fn get(&self, url: &str) -> Result<reqwest::Response> {
+        let resp = send(self.client.get(url), self.retries)
+            .await
+            .with_context(|| format!("Failed to GET {}", url))?;
+        check_status(resp, &format!("GET {}", url)).await
+    }
+
+    /// All container package versions of the owner
+    async fn list_versions(&self) -> Result<Vec<PackageVersion>> {
+        let mut versions = Vec::new();
+        for page in 1.. {
+            let url = format!(
+                "{}/api/v1/packages/{}?type=container&page={}&limit={}",
+                self.base_url,
+                encode_segment(&self.owner),
+                page,
+                self.page_limit
+            );
+            let resp = self.get(&url).await?;
+            let paging = Paging::of(&resp);
+            let batch: Vec<PackageVersion> =
+                resp.json().await.context("Failed to parse package list")?;
+            let count = batch.len();
+            versions.extend(batch);
+            if paging.last(count, versions.len()) {
+                break;
+            }
+        }
+        Ok(versions)
+    }
+
+    /// The versions of one package, from the list cached for this run
+    async fn package_versions(&self, repo: &str) -> Result<Vec<PackageVersion>> {
+        let mut cached = self.versions.lock().await;
+        if cached.is_none() {
+            *cached = Some(self.list_versions().await?);
+        }
+        let name = self.package_name(repo);
+        Ok(cached
+            .iter()
+            .flatten()
+            .filter(|v| v.name == name)
+            .cloned()
+            .collect())
+    }
+
+    /// Strip the `<owner>/` prefix that repository names carry
+    fn package_name<'a>(&self, repo: &'a str) -> &'a str {
+        repo.strip_prefix(&self.owner)
+            .and_then(|r| r.strip_prefix('/'))
+            .unwrap_or(repo)
+    }
+
+    fn version_url(&self, repo: &str, version: &str) -> String {
+        format!(
+            "{}/api/v1/packages/{}/container/{}/{}",
+            self.base_url,
+            encode_segment(&self.owner),
+            encode_segment(self.package_name(repo)),
+            encode_segment(version)
+        )
+    }
+
+    /// The manifest digest is the sha256 of the version's `manifest.json` file
+    async fn resolve_version(&self, repo: &str, version: &PackageVersion) -> Result<TagInfo> {
+        let url = format!("{}/files", self.version_url(repo, &version.version));
+        let files: Vec<PackageFile> =
+            self.get(&url).await?.json().await.with_context(|| {
+                format!("Failed to parse files of {}:{}", repo, version.version)
+            })?;
+
+        let manifest = files
+            .iter()
+            .find(|f| f.name == "manifest.json")
+            .with_context(|| format!("No manifest.json in {}:{}", repo, version.version))?;
+
+        Ok(TagInfo {
+            repository: repo.to_string(),
+            tag: version.version.clone(),
+            digest: format!("sha256:{}", manifest.sha256),
+            // Gitea does not expose the image config, so the push time stands in for it
+            created: version.created_at,
+            size: Some(files.iter().filter_map(|f| f.size).sum()),
+            pushed: version.created_at,
+            last_pulled: None,
+            blobs: Vec::new(),
+        })
+    }
+
+    async fn delete_version(&self, repo: &str, version: &str) -> Result<u16> {
+        let url = self.version_url(repo, version);
+        let resp = send(self.client.delete(&url), self.retries)
+            .await
+            .with_context(|| format!("Failed to DELETE {}:{}", repo, version))?;
+        let resp = check_status(resp, &format!("DELETE {}:{}", repo, version)).await?;
+        let name = self.package_name(repo);
+        if let Some(versions) = self.versions.lock().await.as_mut() {
+            versions.retain(|v| v.name != name || v.version != version);
+        }
+        Ok(resp.status().as_u16())
+    }
+}
+
+fn is_digest_version(version: &str) -> bool {
+    version.starts_with("sha256:")
+}
+
+#[async_trait]
+impl RegistryBackend for GiteaBackend {
+    fn location(&self) -> String {
+        format!("{} (owner {})", self.base_url, self.owner)
+    }
+
+    /// Lists the owner's packages afresh; the tag lookups that follow reuse it
+    async fn list_repositories(&self) -> Result<Vec<String>> {
+        let mut cached = self.versions.lock().await;
+        let versions = cached.insert(self.list_versions().await?);
+        let mut repos: Vec<String> = versions
+            .iter()
+            .map(|v| format!("{}/{}", self.owner, v.name))
+            .collect();
+        repos.sort();
+        repos.dedup();
+        Ok(repos)
+    }
+
+    async fn list_tags(&self, repo: &str) -> Result<Vec<String>> {
+        Ok(self
+            .package_versions(repo)
+            .await?
+            .into_iter()
+            .filter(|v| !is_digest_version(&v.version))
+            .map(|v| v.version)
+            .collect())
+    }
+
+    async fn resolve_all_tags(&self, repo: &str) -> Result<Vec<TagInfo>> {
+        let versions: Vec<PackageVersion> = self
+            .package_versions(repo)
+            .await?
+            .into_iter()
+            .filter(|v| !is_digest_version(&v.version))
+            .collect();
+        self.reporter.tags_listed(repo, versions.len());
+
+        let mut infos = Vec::new();
+        for version in versions {
+            match self.resolve_version(repo, &version).await {
+                Ok(info) => {
+                    self.reporter.tag_resolved(&info);
+                    infos.push(info)
+                }
+                Err(e) => self.reporter.error(format!(
+                    "Failed to resolve {}:{}: {}",
+                    repo, version.version, e
+                )),
+            }
+        }
+        Ok(infos)
+    }
+
+    async fn delete_manifest(&self, repo: &str, digest: &str) -> Result<u16> {
+        self.delete_version(repo, digest).await
+    }
+
+    async fn delete_tags(&self, repo: &str, digest: &str, tags: &[String]) -> Result<u16> {
+        let mut status = None;
+        for tag in tags {
+            status = Some(self.delete_version(repo, tag).await?);
+        }
+        match status {
+            Some(status) => Ok(status),
+            None => self.delete_manifest(repo, digest).await,
+        }
+    }
+
+    async fn clear_listings(&self) {
+        *self.versions.lock().await = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::testing::serve;
+    use crate::report::testing::Recorder;
+    use axum::extract::Path;
+    use axum::http::StatusCode;
+    use axum::routing::{delete, get};
+    use axum::{Json, Router};
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    fn router(deleted: Arc<Mutex<Vec<String>>>, listed: Arc<AtomicUsize>) -> Router {
+        Router::new()
+            .route(
+                "/api/v1/packages/{owner}",
+                get(move || async move {
+                    listed.fetch_add(1, Ordering::SeqCst);
+                    let total = [("X-Total-Count", "3")];
+                    (total, Json(json!([
+                        {"name": "app", "version": "v1", "created_at": "2024-06-01T00:00:00Z"},
+                        {"name": "app", "version": "sha256:child", "created_at": "2024-06-01T00:00:00Z"},
+                        {"name": "other", "version": "latest", "created_at": "2024-06-02T00:00:00Z"}
+                    ])))
+                }),
+            )
+            .route(
+                "/api/v1/packages/{owner}/container/{name}/{version}/files",
+                get(|Path((_, _, version)): Path<(String, String, String)>| async move {
+                    Json(json!([
+                        {"name": "manifest.json", "size": 500, "sha256": format!("{}digest", version)},
+                        {"name": "sha256:layer", "size": 1500, "sha256": "layer"}
+                    ]))
+                }),
+            )
+            .route(
+                "/api/v1/packages/{owner}/container/{name}/{version}",
+                delete(
+                    move |Path((_, name, version)): Path<(String, String, String)>| async move {
+                        deleted.lock().unwrap().push(format!("{}:{}", name, version));
+                        StatusCode::NO_CONTENT
+                    },
+                ),
+            )
+    }
+
+    #[tokio::test]
+    async fn test_gitea_backend() {
+        let deleted = Arc::new(Mutex::new(Vec::new()));
+        let listed = Arc::new(AtomicUsize::new(0));
+        let url = serve(router(deleted.clone(), listed.clone())).await;
+        let recorder = Arc::new(Recorder::default());
+        let backend =
+            GiteaBackend::new(&url, "acme", &Auth::None, &Tls::default(), recorder.clone())
+                .unwrap();
+
+        assert_eq!(
+            backend.list_repositories().await.unwrap(),
+            vec!["acme/app", "acme/other"]
+        );
+        assert_eq!(backend.list_tags("acme/app").await.unwrap(), vec!["v1"]);
+
+        let tags = backend.resolve_all_tags("acme/app").await.unwrap();
+        assert_eq!(tags.len(), 1);
+        assert_eq!(tags[0].digest, "sha256:v1digest");
+        assert_eq!(tags[0].size, Some(2000));
+        assert_eq!(tags[0].pushed, tags[0].created);
+        assert_eq!(recorder.events(), ["tags_listed", "tag_resolved"]);
+        // The package list is fetched once for the whole run
+        assert_eq!(listed.load(Ordering::SeqCst), 1);
+
+        backend
+            .delete_tags("acme/app", "sha256:v1digest", &["v1".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(*deleted.lock().unwrap(), vec!["app:v1"]);
+        assert!(backend.list_tags("acme/app").await.unwrap().is_empty());
+
+        backend.clear_listings().await;
+        assert_eq!(backend.list_tags("acme/app").await.unwrap(), vec!["v1"]);
+        assert_eq!(listed.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,414 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use reqwest::Client;
+use serde::Deserialize;
+use tokio::sync::Semaphore;
+
+use super::{
+    check_status, encode_segment, http_client, send, Auth, RegistryBackend, Tls, DEFAULT_RETRIES,
+};
+use crate::error::AppError;
+use crate::models::TagInfo;
+use crate::report::Reporter;
+
+/// Default `per_page`; GitLab caps it at 100 and pages follow `X-Next-Page` either way
+const PER_PAGE: usize = 100;
+
+/// GitLab Container Registry API (`/api/v4/projects/:id/registry`).
+///
+/// GitLab deletes images per tag rather than per manifest digest, so this
+/// backend overrides `delete_tags` and refuses digest-only deletion.
+#[derive(Clone)]
+pub struct GitLabBackend {
+    client: Client,
+    base_url: String,
+    project: String,
+    reporter: Arc<dyn Reporter>,
+    per_page: usize,
+    retries: u32,
+    /// Repository path → registry repository id
+    repo_ids: Arc<Mutex<HashMap<String, u64>>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Repository {
+    id: u64,
+    path: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Tag {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TagDetails {
+    name: String,
+    digest: Option<String>,
+    total_size: Option<u64>,
+    created_at: Option<DateTime<Utc>>,
+}
+
+impl GitLabBackend {
+    /// `project` is a numeric project id or a `group/project` path
+    pub fn new(
+        base_url: &str,
+        project: &str,
+        auth: &Auth,
+        tls: &Tls,
+        reporter: Arc<dyn Reporter>,
+    ) -> Result<Self> {
+        Ok(Self {
+            client: http_client(auth, tls)?,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            project: project.to_string(),
+            reporter,
+            per_page: PER_PAGE,
+            retries: DEFAULT_RETRIES,
+            repo_ids: Arc::new(Mutex::new(HashMap::new())),
+        })
+    }
+
+    /// Items per page of list requests
+    pub fn with_page_size(mut self, n: u32) -> Self {
+        self.per_page = n as usize;
+        self
+    }
+
+    /// Retry a GET or HEAD up to `n` times after a transient failure
+    pub fn with_retries(mut self, n: u32) -> Self {
+        self.retries = n;
+        self
+    }
+
+    fn registry_url(&self) -> String {
+        format!(
+            "{}/api/v4/projects/{}/registry/repositories",
+            self.base_url,
+            encode_segment(&self.project)
+        )
+    }
+
+    async fn get(&self, url: &str) -> Result<reqwest::Response> {
+        let resp = send(self.client.get(url), self.retries)
+            .await
+            .with_context(|| format!("Failed to GET {}", url))?;
+        check_status(resp, &format!("GET {}", url)).await
+    }
+
+    /// Fetch all pages of a list endpoint, following `X-Next-Page`
+    async fn get_paged<T: for<'de> Deserialize<'de>>(&self, url: &str) -> Result<Vec<T>> {
+        let mut items = Vec::new();
+        let mut page = "1".to_string();
+
+        loop {
+            let page_url = format!("{}?per_page={}&page={}", url, self.per_page, page);
+            let resp = self.get(&page_url).await?;
+            let next = resp
+                .headers()
+                .get("X-Next-Page")
+                .and_then(|v| v.to_str().ok())
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty());
+
+            let batch: Vec<T> = resp
+                .json()
+                .await
+                .with_context(|| format!("Failed to parse response from {}", page_url))?;
+            items.extend(batch);
+
+            match next {
+                Some(next) => page = next,
+                None => break,
+            }
+        }
+
+        Ok(items)
+    }
+
+    async fn repo_id(&self, repo: &str) -> Result<u64> {
+        if let Some(id) = self.repo_ids.lock().unwrap().get(repo) {
+            return Ok(*id);
+        }
+        self.list_repositories().await?;
+        self.repo_ids
+            .lock()
+            .unwrap()
+            .get(repo)
+            .copied()
+            .with_context(|| format!("Repository {} not found in project {}", repo, self.project))
+    }
+
+    async fn tag_details(&self, repo: &str, repo_id: u64, tag: &str) -> Result<TagInfo> {
+        let url = format!(
+            "{}/{}/tags/{}",
+            self.registry_url(),
+            repo_id,
+            encode_segment(tag)
+        );
+        let details: TagDetails = self
+            .get(&url)
+            .await?
+            .json()
+            .await
+            .with_context(|| format!("Failed to parse tag details for {}:{}", repo, tag))?;
+
+        Ok(TagInfo {
+            repository: repo.to_string(),
+            tag: details.name,
+            digest: details
+                .digest
+                .with_context(|| format!("GitLab returned no digest for {}:{}", repo, tag))?,
+            created: details.created_at,
+            size: details.total_size,
+            // GitLab's created_at is when the tag was pushed
+            pushed: details.created_at,
+            last_pulled: None,
+            blobs: Vec::new(),
+        })
+    }
+
+    async fn delete_tag(&self, repo: &str, repo_id: u64, tag: &str) -> Result<u16> {
+        let url = format!(
+            "{}/{}/tags/{}",
+            self.registry_url(),
+            repo_id,
+            encode_segment(tag)
+        );
+        let resp = send(self.client.delete(&url), self.retries)
+            .await
+            .with_context(|| format!("Failed to DELETE tag {}:{}", repo, tag))?;
+        let resp = check_status(resp, &format!("DELETE tag {}:{}", repo, tag)).await?;
+        Ok(resp.status().as_u16())
+    }
+}
+
+#[async_trait]
+impl RegistryBackend for GitLabBackend {
+    fn location(&self) -> String {
+        format!("{} (project {})", self.base_url, self.project)
+    }
+
+    async fn list_repositories(&self) -> Result<Vec<String>> {
+        let repos: Vec<Repository> = self.get_paged(&self.registry_url()).await?;
+        let mut ids = self.repo_ids.lock().unwrap();
+        Ok(repos
+            .into_iter()
+            .map(|r| {
+                ids.insert(r.path.clone(), r.id);
+                r.path
+            })
+            .collect())
+    }
+
+    async fn list_tags(&self, repo: &str) -> Result<Vec<String>> {
+        let id = self.repo_id(repo).await?;
+        let url = format!("{}/{}/tags", self.registry_url(), id);
+        let tags: Vec<Tag> = self.get_paged(&url).await?;
+        Ok(tags.into_iter().map(|t| t.name).collect())
+    }
+
+    async fn resolve_all_tags(&self, repo: &str) -> Result<Vec<TagInfo>> {
+        let id = self.repo_id(repo).await?;
+        let tags = self.list_tags(repo).await?;
+        self.reporter.tags_listed(repo, tags.len());
+
+        let semaphore = Arc::new(Semaphore::new(10));
+        let mut handles = Vec::with_capacity(tags.len());
+        for tag in tags {
+            let permit = semaphore.clone().acquire_owned().await.unwrap();
+            let backend = self.clone();
+            let repo = repo.to_string();
+            handles.push(tokio::spawn(async move {
+                let result = backend.tag_details(&repo, id, &tag).await;
+                drop(permit);
+                if let Ok(info) = &result {
+                    backend.reporter.tag_resolved(info);
+                }
+                (tag, result)
+            }));
+        }
+
+        let mut infos = Vec::new();
+        for handle in handles {
+            let (tag, result) = handle.await.context("Task join error")?;
+            match result {
+                Ok(info) => infos.push(info),
+                Err(e) => self
+                    .reporter
+                    .error(format!("Failed to resolve {}:{}: {}", repo, tag, e)),
+            }
+        }
+        Ok(infos)
+    }
+
+    async fn delete_manifest(&self, repo: &str, digest: &str) -> Result<u16> {
+        anyhow::bail!(
+            "GitLab deletes images by tag, not by digest ({}@{})",
+            repo,
+            digest
+        )
+    }
+
+    /// Deletes every tag even after a failure; if only some fail, the error
+    /// is an [`AppError::PartialDelete`] naming the tags that were deleted
+    async fn delete_tags(&self, repo: &str, _digest: &str, tags: &[String]) -> Result<u16> {
+        let id = self.repo_id(repo).await?;
+        let mut deleted = Vec::new();
+        let mut failed = Vec::new();
+        let mut status = None;
+        let mut first_error = None;
+        for tag in tags {
+            match self.delete_tag(repo, id, tag).await {
+                Ok(code) => {
+                    deleted.push(tag.clone());
+                    status = Some(code);
+                }
+                Err(e) => {
+                    failed.push(tag.clone());
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+
+        match (first_error, status) {
+            (None, Some(status)) => Ok(status),
+            (None, None) => anyhow::bail!("No tags to delete in {}", repo),
+            (Some(e), None) => Err(e),
+            (Some(e), Some(status)) => Err(AppError::PartialDelete {
+                deleted,
+                failed,
+                status,
+                source: e,
+            }
+            .into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::testing::serve;
+    use crate::error::ErrorKind;
+    use crate::report::Silent;
+    use axum::extract::{Path, Query};
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::response::IntoResponse;
+    use axum::routing::get;
+    use axum::{Json, Router};
+    use serde_json::json;
+
+    fn router(deleted: Arc<Mutex<Vec<String>>>) -> Router {
+        Router::new()
+            .route(
+                "/api/v4/projects/{project}/registry/repositories",
+                get(|Path(project): Path<String>| async move {
+                    assert_eq!(project, "group/app");
+                    Json(json!([{"id": 7, "path": "group/app"}, {"id": 8, "path": "group/app/worker"}]))
+                }),
+            )
+            .route(
+                "/api/v4/projects/{project}/registry/repositories/{id}/tags",
+                get(|Query(q): Query<HashMap<String, String>>| async move {
+                    // Two pages to exercise X-Next-Page
+                    let mut headers = HeaderMap::new();
+                    if q["page"] == "1" {
+                        headers.insert("X-Next-Page", "2".parse().unwrap());
+                        (headers, Json(json!([{"name": "v1"}]))).into_response()
+                    } else {
+                        headers.insert("X-Next-Page", "".parse().unwrap());
+                        (headers, Json(json!([{"name": "v2"}]))).into_response()
+                    }
+                }),
+            )
+            .route(
+                "/api/v4/projects/{project}/registry/repositories/{id}/tags/{tag}",
+                get(|Path((_, id, tag)): Path<(String, u64, String)>| async move {
+                    assert_eq!(id, 7);
+                    Json(json!({
+                        "name": tag,
+                        "digest": format!("sha256:{}", tag),
+                        "total_size": 2048,
+                        "created_at": "2024-05-01T12:00:00.000+00:00"
+                    }))
+                })
+                .delete(move |Path((_, _, tag)): Path<(String, u64, String)>| async move {
+                    if tag == "locked" {
+                        return StatusCode::FORBIDDEN;
+                    }
+                    deleted.lock().unwrap().push(tag);
+                    StatusCode::OK
+                }),
+            )
+    }
+
+    #[tokio::test]
+    async fn test_gitlab_backend() {
+        let deleted = Arc::new(Mutex::new(Vec::new()));
+        let url = serve(router(deleted.clone())).await;
+        let auth = Auth::Bearer("t".to_string());
+        let backend =
+            GitLabBackend::new(&url, "group/app", &auth, &Tls::default(), Arc::new(Silent))
+                .unwrap();
+
+        assert_eq!(
+            backend.list_repositories().await.unwrap(),
+            vec!["group/app", "group/app/worker"]
+        );
+
+        let mut tags = backend.resolve_all_tags("group/app").await.unwrap();
+        tags.sort_by(|a, b| a.tag.cmp(&b.tag));
+        assert_eq!(tags.len(), 2);
+        assert_eq!(tags[0].tag, "v1");
+        assert_eq!(tags[0].digest, "sha256:v1");
+        assert_eq!(tags[0].size, Some(2048));
+        assert!(tags[0].created.is_some());
+        assert_eq!(tags[0].pushed, tags[0].created);
+
+        assert!(backend
+            .delete_manifest("group/app", "sha256:v1")
+            .await
+            .is_err());
+        backend
+            .delete_tags("group/app", "sha256:v1", &["v1".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(*deleted.lock().unwrap(), vec!["v1"]);
+
+        // The other tags are still deleted, and the error tells which were
+        let err = backend
+            .delete_tags(
+                "group/app",
+                "sha256:v2",
+                &["locked".to_string(), "v2".to_string()],
+            )
+            .await
+            .unwrap_err();
+        match err.downcast_ref::<AppError>() {
+            Some(AppError::PartialDelete {
+                deleted, failed, ..
+            }) => {
+                assert_eq!(deleted, &["v2"]);
+                assert_eq!(failed, &["locked"]);
+            }
+            other => panic!("unexpected error {:?}", other),
+        }
+        assert_eq!(ErrorKind::of(&err), ErrorKind::Auth);
+        assert_eq!(*deleted.lock().unwrap(), vec!["v1", "v2"]);
+
+        // Failing on every tag is a plain error
+        let err = backend
+            .delete_tags("group/app", "sha256:x", &["locked".to_string()])
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<AppError>().map(AppError::kind),
+            Some(ErrorKind::Auth)
+        );
+    }
+}
@@ -0,0 +1,429 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use reqwest::Client;
+use serde::Deserialize;
+
+use super::{
+    check_status, encode_segment, http_client, send, Auth, Paging, RegistryBackend, Tls,
+    DEFAULT_RETRIES,
+};
+use crate::models::{ManifestRevision, TagInfo};
+use crate::report::Reporter;
+
+/// Default and maximum `page_size` of the Harbor API
+const PAGE_SIZE: usize = 100;
+
+/// Harbor v2.0 REST API (`/api/v2.0`).
+///
+/// Harbor restricts the V2 catalog and manifest DELETE, but its artifact API
+/// lists every artifact (tagged or not) with push/pull times and deletes by digest.
+pub struct HarborBackend {
+    client: Client,
+    base_url: String,
+    page_size: usize,
+    retries: u32,
+    reporter: Arc<dyn Reporter>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Repository {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Artifact {
+    digest: String,
+    size: Option<u64>,
+    push_time: Option<DateTime<Utc>>,
+    pull_time: Option<DateTime<Utc>>,
+    tags: Option<Vec<Tag>>,
+    extra_attrs: Option<ExtraAttrs>,
+    references: Option<Vec<Reference>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Tag {
+    name: String,
+    push_time: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExtraAttrs {
+    created: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Reference {
+    child_digest: String,
+}
+
+impl HarborBackend {
+    pub fn new(
+        base_url: &str,
+        auth: &Auth,
+        tls: &Tls,
+        reporter: Arc<dyn Reporter>,
+    ) -> Result<Self> {
+        Ok(Self {
+            client: http_client(auth, tls)?,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            page_size: PAGE_SIZE,
+            retries: DEFAULT_RETRIES,
+            reporter,
+        })
+    }
+
+    /// Items per page of list requests, capped at Harbor's maximum
+    pub fn with_page_size(mut self, n: u32) -> Self {
+        self.page_size = (n as usize).min(PAGE_SIZE);
+        self
+    }
+
+    /// Retry a GET or HEAD up to `n` times after a transient failure
+    pub fn with_retries(mut self, n: u32) -> Self {
+        self.retries = n;
+        self
+    }
+
+    /// `/api/v2.0/projects/<project>/repositories/<name>`; Harbor requires the
+    /// repository name (which may contain slashes) to be URL-encoded twice
+    fn repo_url(&self, repo: &str) -> Result<String> {
+        let (project, name) = repo
+            .split_once('/')
+            .with_context(|| format!("Harbor repository {} must be <project>/<name>", repo))?;
+        Ok(format!(
+            "{}/api/v2.0/projects/{}/repositories/{}",
+            self.base_url,
+            encode_segment(project),
+            encode_segment(&encode_segment(name))
+        ))
+    }
+
+    /// Fetch all pages of a paginated list endpoint
+    async fn get_paged<T: for<'de> Deserialize<'de>>(&self, url: &str) -> Result<Vec<T>> {
+        let mut items = Vec::new();
+        let separator = if url.contains('?') { '&' } else { '?' };
+
+        for page in 1.. {
+            let page_url = format!(
+                "{}{}page={}&page_size={}",
+                url, separator, page, self.page_size
+            );
+            let resp = send(self.client.get(&page_url), self.retries)
+                .await
+                .with_context(|| format!("Failed to GET {}", page_url))?;
+            let resp = check_status(resp, &format!("GET {}", page_url)).await?;
+            let paging = Paging::of(&resp);
+
+            let batch: Vec<T> = resp
+                .json()
+                .await
+                .with_context(|| format!("Failed to parse response from {}", page_url))?;
+            let count = batch.len();
+            items.extend(batch);
+            if paging.last(count, items.len()) {
+                break;
+            }
+        }
+
+        Ok(items)
+    }
+
+    async fn list_artifacts(&self, repo: &str) -> Result<Vec<Artifact>> {
+        let url = format!("{}/artifacts?with_tag=true", self.repo_url(repo)?);
+        self.get_paged(&url).await
+    }
+}
+
+/// Harbor reports never-pulled artifacts with a zero timestamp
+fn real_time(time: Option<DateTime<Utc>>) -> Option<DateTime<Utc>> {
+    time.filter(|t| t.timestamp() > 0)
+}
+
+#[async_trait]
+impl RegistryBackend for HarborBackend {
+    fn location(&self) -> String {
+        self.base_url.clone()
+    }
+
+    async fn list_repositories(&self) -> Result<Vec<String>> {
+        let url = format!("{}/api/v2.0/repositories", self.base_url);
+        let repos: Vec<Repository> = self.get_paged(&url).await?;
+        Ok(repos.into_iter().map(|r| r.name).collect())
+    }
+
+    async fn list_tags(&self, repo: &str) -> Result<Vec<String>> {
+        let artifacts = self.list_artifacts(repo).await?;
+        Ok(artifacts
+            .into_iter()
+            .flat_map(|a| a.tags.unwrap_or_default())
+            .map(|t| t.name)
+            .collect())
+    }
+
+    async fn resolve_all_tags(&self, repo: &str) -> Result<Vec<TagInfo>> {
+        let artifacts = self.list_artifacts(repo).await?;
+        let count = artifacts
+            .iter()
+            .map(|a| a.tags.as_ref().map_or(0, Vec::len))
+            .sum();
+        self.reporter.tags_listed(repo, count);
+
+        let mut infos = Vec::new();
+        for artifact in artifacts {
+            let created = artifact.extra_attrs.as_ref().and_then(|e| e.created);
+            for tag in artifact.tags.as_deref().unwrap_or_default() {
+                let info = TagInfo {
+                    repository: repo.to_string(),
+                    tag: tag.name.clone(),
+                    digest: artifact.digest.clone(),
+                    created,
+                    size: artifact.size,
+                    pushed: real_time(tag.push_time.or(artifact.push_time)),
+                    last_pulled: real_time(artifact.pull_time),
+                    blobs: Vec::new(),
+                };
+                self.reporter.tag_resolved(&info);
+                infos.push(info);
+            }
+        }
+        Ok(infos)
+    }
+
+    async fn list_revisions(&self, repo: &str) -> Result<Vec<ManifestRevision>> {
+        Ok(self
+            .list_artifacts(repo)
+            .await?
+            .into_iter()
+            .map(|a| ManifestRevision {
+                tags: a
+                    .tags
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|t| t.name)
+                    .collect(),
+                children: a
+                    .references
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|r| r.child_digest)
+                    .collect(),
+                subject: None,
+                size: a.size,
+                digest: a.digest,
+            })
+            .collect())
+    }
+
+    async fn delete_manifest(&self, repo: &str, digest: &str) -> Result<u16> {
+        let url = format!("{}/artifacts/{}", self.repo_url(repo)?, digest);
+        let resp = send(self.client.delete(&url), self.retries)
+            .await
+            .with_context(|| format!("Failed to DELETE artifact {} for {}", digest, repo))?;
+        let resp = check_status(resp, &format!("DELETE artifact {} for {}", digest, repo)).await?;
+        Ok(resp.status().as_u16())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::testing::serve;
+    use crate::report::testing::Recorder;
+    use crate::report::Silent;
+    use axum::extract::{Path, Query};
+    use axum::http::StatusCode;
+    use axum::routing::{delete, get};
+    use axum::{Json, Router};
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    fn router(deleted: Arc<Mutex<Vec<String>>>) -> Router {
+        Router::new()
+            .route(
+                "/api/v2.0/repositories",
+                // No paging headers: only the empty second page ends the list
+                get(|Query(q): Query<HashMap<String, String>>| async move {
+                    match q["page"].as_str() {
+                        "1" => Json(json!([{"name": "library/app"}])),
+                        _ => Json(json!([])),
+                    }
+                }),
+            )
+            .route(
+                "/api/v2.0/projects/{project}/repositories/{repo}/artifacts",
+                get(
+                    |Path((project, repo)): Path<(String, String)>,
+                     Query(q): Query<HashMap<String, String>>| async move {
+                        assert_eq!(project, "library");
+                        // Axum decodes once; Harbor expects the name encoded twice
+                        assert_eq!(repo, "app");
+                        if q.get("page").map(String::as_str) != Some("1") {
+                            return Json(Value::Array(vec![]));
+                        }
+                        Json(json!([
+                            {
+                                "digest": "sha256:aaa",
+                                "size": 1000,
+                                "push_time": "2024-03-01T00:00:00.000Z",
+                                "pull_time": "2024-04-01T00:00:00.000Z",
+                                "tags": [{"name": "v1", "push_time": "2024-03-02T00:00:00.000Z"},
+                                         {"name": "stable"}],
+                                "extra_attrs": {"created": "2024-02-01T00:00:00Z"}
+                            },
+                            {
+                                "digest": "sha256:bbb",
+                                "size": 500,
+                                "push_time": "2024-01-01T00:00:00.000Z",
+                                "pull_time": "0001-01-01T00:00:00.000Z",
+                                "tags": null,
+                                "references": [{"child_digest": "sha256:ccc"}]
+                            }
+                        ]))
+                    },
+                ),
+            )
+            .route(
+                "/api/v2.0/projects/{project}/repositories/{repo}/artifacts/{digest}",
+                delete(
+                    move |Path((_, _, digest)): Path<(String, String, String)>| async move {
+                        deleted.lock().unwrap().push(digest);
+                        StatusCode::OK
+                    },
+                ),
+            )
+    }
+
+    #[tokio::test]
+    async fn test_harbor_backend() {
+        let deleted = Arc::new(Mutex::new(Vec::new()));
+        let url = serve(router(deleted.clone())).await;
+        let recorder = Arc::new(Recorder::default());
+        let backend =
+            HarborBackend::new(&url, &Auth::None, &Tls::default(), recorder.clone()).unwrap();
+
+        assert_eq!(
+            backend.list_repositories().await.unwrap(),
+            vec!["library/app"]
+        );
+
+        let tags = backend.resolve_all_tags("library/app").await.unwrap();
+        assert_eq!(tags.len(), 2);
+        assert_eq!(
+            recorder.events(),
+            ["tags_listed", "tag_resolved", "tag_resolved"]
+        );
+        let v1 = tags.iter().find(|t| t.tag == "v1").unwrap();
+        assert_eq!(v1.digest, "sha256:aaa");
+        assert_eq!(v1.size, Some(1000));
+        assert_eq!(
+            v1.created.unwrap().to_rfc3339(),
+            "2024-02-01T00:00:00+00:00"
+        );
+        assert_eq!(v1.pushed.unwrap().to_rfc3339(), "2024-03-02T00:00:00+00:00");
+        assert!(v1.last_pulled.is_some());
+        let stable = tags.iter().find(|t| t.tag == "stable").unwrap();
+        assert_eq!(
+            stable.pushed.unwrap().to_rfc3339(),
+            "2024-03-01T00:00:00+00:00"
+        );
+
+        let revisions = backend.list_revisions("library/app").await.unwrap();
+        let untagged = revisions.iter().find(|r| r.digest == "sha256:bbb").unwrap();
+        assert!(untagged.tags.is_empty());
+        assert_eq!(untagged.children, vec!["sha256:ccc"]);
+
+        backend
+            .delete_manifest("library/app", "sha256:bbb")
+            .await
+            .unwrap();
+        assert_eq!(*deleted.lock().unwrap(), vec!["sha256:bbb"]);
+    }
+
+    #[tokio::test]
+    async fn test_page_size_capped_by_server() {
+        // The server returns at most 2 items per page, whatever page_size asks for
+        let router = Router::new().route(
+            "/api/v2.0/repositories",
+            get(|Query(q): Query<HashMap<String, String>>| async move {
+                let names = ["p/a", "p/b", "p/c", "p/d", "p/e"];
+                let page: usize = q["page"].parse().unwrap();
+                let batch: Vec<Value> = names
+                    .iter()
+                    .skip((page - 1) * 2)
+                    .take(2)
+                    .map(|name| json!({ "name": name }))
+                    .collect();
+                ([("X-Total-Count", names.len().to_string())], Json(batch))
+            }),
+        );
+        let url = serve(router).await;
+        let backend = HarborBackend::new(&url, &Auth::None, &Tls::default(), Arc::new(Silent))
+            .unwrap()
+            .with_page_size(4);
+
+        assert_eq!(
+            backend.list_repositories().await.unwrap(),
+            vec!["p/a", "p/b", "p/c", "p/d", "p/e"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pages_follow_link_header() {
+        // No X-Total-Count, short pages, and a rel="next" link on all but the last
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        let router = Router::new().route(
+            "/api/v2.0/repositories",
+            get(move |Query(q): Query<HashMap<String, String>>| async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let names = ["p/a", "p/b", "p/c"];
+                let page: usize = q["page"].parse().unwrap();
+                let batch: Vec<Value> = names
+                    .iter()
+                    .skip((page - 1) * 2)
+                    .take(2)
+                    .map(|name| json!({ "name": name }))
+                    .collect();
+                let mut links = vec![r#"</api/v2.0/repositories?page=1>; rel="first""#.to_string()];
+                if page * 2 < names.len() {
+                    links.push(format!(
+                        r#"</api/v2.0/repositories?page={}>; rel="next""#,
+                        page + 1
+                    ));
+                }
+                ([("Link", links.join(", "))], Json(batch))
+            }),
+        );
+        let url = serve(router).await;
+        let backend =
+            HarborBackend::new(&url, &Auth::None, &Tls::default(), Arc::new(Silent)).unwrap();
+
+        assert_eq!(
+            backend.list_repositories().await.unwrap(),
+            vec!["p/a", "p/b", "p/c"]
+        );
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_repo_url_double_encodes() {
+        let backend = HarborBackend::new(
+            "https://harbor",
+            &Auth::None,
+            &Tls::default(),
+            Arc::new(Silent),
+        )
+        .unwrap();
+        assert_eq!(
+            backend.repo_url("proj/team/app").unwrap(),
+            "https://harbor/api/v2.0/projects/proj/repositories/team%252Fapp"
+        );
+        assert!(backend.repo_url("noproject").is_err());
+    }
+}
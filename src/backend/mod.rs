@@ -0,0 +1,372 @@
+mod filesystem;
+mod gitea;
+mod gitlab;
+mod harbor;
+
+pub use filesystem::FilesystemBackend;
+pub use gitea::GiteaBackend;
+pub use gitlab::GitLabBackend;
+pub use harbor::HarborBackend;
+
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, LINK, RETRY_AFTER};
+use reqwest::{Client, Method, RequestBuilder, Response, StatusCode};
+use tracing::field::Empty;
+use tracing::Instrument;
+
+use crate::doctor::Check;
+use crate::error::{AppError, ErrorKind};
+use crate::models::{ManifestRevision, TagInfo};
+
+/// A source of repositories and tags that cleanup can be planned and executed against
+#[async_trait]
+pub trait RegistryBackend: Send + Sync {
+    /// Human-readable location of the registry (URL or path), used in messages
+    fn location(&self) -> String;
+
+    /// List all repository names
+    async fn list_repositories(&self) -> Result<Vec<String>>;
+
+    /// List tag names in a repository
+    async fn list_tags(&self, repo: &str) -> Result<Vec<String>>;
+
+    /// Resolve every tag in a repository into TagInfo
+    async fn resolve_all_tags(&self, repo: &str) -> Result<Vec<TagInfo>>;
+
+    /// Enumerate every manifest in a repository, including untagged ones
+    async fn list_revisions(&self, repo: &str) -> Result<Vec<ManifestRevision>> {
+        let _ = repo;
+        anyhow::bail!(
+            "{} cannot enumerate untagged manifests; use --storage-root",
+            self.location()
+        )
+    }
+
+    /// Delete a manifest (and thereby all tags pointing at it) by digest;
+    /// returns the HTTP status of the successful response
+    async fn delete_manifest(&self, repo: &str, digest: &str) -> Result<u16>;
+
+    /// Delete the given tags, which all point at `digest`. Backends that
+    /// delete by digest ignore the tag names; tag-oriented APIs override this.
+    async fn delete_tags(&self, repo: &str, digest: &str, tags: &[String]) -> Result<u16> {
+        let _ = tags;
+        self.delete_manifest(repo, digest).await
+    }
+
+    /// Whether `delete_manifest` can succeed at all on this backend
+    fn can_delete(&self) -> bool {
+        true
+    }
+
+    /// Preflight checks of connectivity, credentials and deletion support.
+    /// `repo` is used for probes that need a repository.
+    async fn diagnose(&self, repo: Option<&str>) -> Vec<Check> {
+        let _ = repo;
+        let catalog = match self.list_repositories().await {
+            Ok(repos) => Check::ok("catalog", format!("{} repositories", repos.len())),
+            Err(e) => Check::fail("catalog", format!("{:#}", e), ErrorKind::of(&e)),
+        };
+        let delete = if self.can_delete() {
+            Check::ok("delete", "supported by this backend (not probed)")
+        } else {
+            Check::fail("delete", "this backend is read-only", ErrorKind::General)
+        };
+        vec![catalog, delete]
+    }
+
+    /// Persist any local state (e.g. the metadata cache) gathered during the run
+    fn save_cache(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Forget listings cached during a run, so the next run sees new pushes
+    async fn clear_listings(&self) {}
+}
+
+/// Credentials sent with every API request
+#[derive(Debug, Clone, Default)]
+pub enum Auth {
+    #[default]
+    None,
+    Basic {
+        username: String,
+        password: String,
+    },
+    Bearer(String),
+}
+
+impl Auth {
+    pub fn from_args(
+        username: Option<&str>,
+        password: Option<&str>,
+        token: Option<&str>,
+    ) -> Result<Self> {
+        match (username, password, token) {
+            (_, _, Some(token)) => Ok(Auth::Bearer(token.to_string())),
+            (Some(username), password, None) => Ok(Auth::Basic {
+                username: username.to_string(),
+                password: password.unwrap_or_default().to_string(),
+            }),
+            (None, Some(_), None) => anyhow::bail!("--password requires --username"),
+            (None, None, None) => Ok(Auth::None),
+        }
+    }
+}
+
+/// TLS settings for registry connections
+#[derive(Debug, Clone, Default)]
+pub struct Tls {
+    /// Additional PEM CA certificate to trust
+    pub ca_cert: Option<PathBuf>,
+    /// Accept invalid or self-signed certificates
+    pub insecure: bool,
+}
+
+/// Build an HTTP client that attaches `auth` to every request
+pub fn http_client(auth: &Auth, tls: &Tls) -> Result<Client> {
+    let mut headers = HeaderMap::new();
+    let value = match auth {
+        Auth::None => None,
+        Auth::Basic { username, password } => {
+            use base64::Engine;
+            let encoded = base64::engine::general_purpose::STANDARD
+                .encode(format!("{}:{}", username, password));
+            Some(format!("Basic {}", encoded))
+        }
+        Auth::Bearer(token) => Some(format!("Bearer {}", token)),
+    };
+    if let Some(value) = value {
+        let mut value = HeaderValue::from_str(&value).context("Invalid credentials")?;
+        value.set_sensitive(true);
+        headers.insert(AUTHORIZATION, value);
+    }
+
+    let mut builder = Client::builder()
+        .default_headers(headers)
+        .danger_accept_invalid_certs(tls.insecure);
+    if let Some(path) = &tls.ca_cert {
+        let pem = std::fs::read(path)
+            .with_context(|| format!("Failed to read CA certificate {}", path.display()))?;
+        let cert = reqwest::Certificate::from_pem(&pem)
+            .with_context(|| format!("Invalid CA certificate {}", path.display()))?;
+        builder = builder.add_root_certificate(cert);
+    }
+    builder.build().context("Failed to build HTTP client")
+}
+
+/// Percent-encode a single URL path segment
+fn encode_segment(segment: &str) -> String {
+    let mut out = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                out.push(byte as char)
+            }
+            _ => out.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    out
+}
+
+/// What a page of a Harbor or Gitea list response says about the pages after it
+struct Paging {
+    /// The `X-Total-Count` header
+    total: Option<usize>,
+    /// Whether a `Link` header has a `rel="next"` link, if there is one
+    next: Option<bool>,
+}
+
+impl Paging {
+    fn of(resp: &Response) -> Self {
+        let total = resp
+            .headers()
+            .get("X-Total-Count")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse().ok());
+        let links: Vec<&str> = resp
+            .headers()
+            .get_all(LINK)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .collect();
+        let next = (!links.is_empty()).then(|| crate::registry::next_link(links).is_some());
+        Self { total, next }
+    }
+
+    /// Whether the page of `batch` items that brought the list to `seen` is
+    /// the last. A short page is not: the server may cap the page size.
+    /// Without either header only an empty page ends the list.
+    fn last(&self, batch: usize, seen: usize) -> bool {
+        batch == 0
+            || match (self.total, self.next) {
+                (Some(total), _) => seen >= total,
+                (None, Some(next)) => !next,
+                (None, None) => false,
+            }
+    }
+}
+
+/// How often a GET or HEAD is retried after a transient failure, unless a
+/// backend's `with_retries` says otherwise
+pub const DEFAULT_RETRIES: u32 = 2;
+
+/// Send a request inside a `request` span that records its method, URL,
+/// status, latency and retry count.
+///
+/// GET and HEAD are retried on connection errors, timeouts and 502/503/504,
+/// which a registry behind a load balancer returns briefly while it restarts,
+/// up to `max_retries` times. They are safe to repeat, deletions are never retried.
+/// Rate limiting (429) is left to the caller, as it has its own error kind.
+pub async fn send(request: RequestBuilder, max_retries: u32) -> reqwest::Result<Response> {
+    let (client, request) = request.build_split();
+    let request = request?;
+    let span = tracing::debug_span!(
+        "request",
+        method = %request.method(),
+        url = %request.url(),
+        status = Empty,
+        latency_ms = Empty,
+        retries = Empty,
+    );
+
+    async move {
+        let idempotent = matches!(*request.method(), Method::GET | Method::HEAD);
+        let started = Instant::now();
+        let mut retries = 0;
+        let result = loop {
+            let retry = request
+                .try_clone()
+                .filter(|_| idempotent && retries < max_retries);
+            let Some(attempt) = retry else {
+                break client.execute(request).await;
+            };
+            match client.execute(attempt).await {
+                Ok(resp) if !is_transient(resp.status()) => break Ok(resp),
+                Ok(resp) => tracing::debug!(status = resp.status().as_u16(), "retrying"),
+                Err(e) if e.is_connect() || e.is_timeout() => {
+                    tracing::debug!(error = %e, "retrying")
+                }
+                Err(e) => break Err(e),
+            }
+            tokio::time::sleep(Duration::from_millis(200 << retries)).await;
+            retries += 1;
+        };
+
+        let span = tracing::Span::current();
+        span.record("latency_ms", started.elapsed().as_millis() as u64);
+        span.record("retries", retries);
+        match &result {
+            Ok(resp) => {
+                span.record("status", resp.status().as_u16());
+                tracing::debug!("request finished");
+            }
+            Err(e) => tracing::debug!(error = %e, "request failed"),
+        }
+        result
+    }
+    .instrument(span)
+    .await
+}
+
+fn is_transient(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+/// Fail with a typed error, classified by the registry's error body and status
+pub async fn check_status(resp: reqwest::Response, what: &str) -> Result<reqwest::Response> {
+    let status = resp.status();
+    if status.is_success() {
+        return Ok(resp);
+    }
+    let retry_after = resp
+        .headers()
+        .get(RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse().ok());
+    let body = resp.text().await.unwrap_or_default();
+    Err(AppError::from_response(what, status.as_u16(), retry_after, &body).into())
+}
+
+#[cfg(test)]
+pub(crate) mod testing {
+    use axum::Router;
+
+    /// Serve `router` on an ephemeral local port and return its base URL
+    pub async fn serve(router: Router) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, router).await.unwrap();
+        });
+        format!("http://{}", addr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_segment() {
+        assert_eq!(encode_segment("team/app"), "team%2Fapp");
+        assert_eq!(encode_segment(&encode_segment("team/app")), "team%252Fapp");
+        assert_eq!(encode_segment("v1.0_rc-1"), "v1.0_rc-1");
+    }
+
+    #[test]
+    fn test_auth_from_args() {
+        assert!(matches!(
+            Auth::from_args(None, None, None).unwrap(),
+            Auth::None
+        ));
+        assert!(matches!(
+            Auth::from_args(Some("u"), Some("p"), None).unwrap(),
+            Auth::Basic { .. }
+        ));
+        assert!(matches!(
+            Auth::from_args(Some("u"), None, Some("t")).unwrap(),
+            Auth::Bearer(_)
+        ));
+        assert!(Auth::from_args(None, Some("p"), None).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_send_retries_transient_errors() {
+        use axum::routing::get;
+        use std::sync::atomic::{AtomicU32, Ordering};
+        use std::sync::Arc;
+
+        let calls = Arc::new(AtomicU32::new(0));
+        let counter = calls.clone();
+        let router = axum::Router::new().route(
+            "/flaky",
+            get(move || async move {
+                match counter.fetch_add(1, Ordering::SeqCst) {
+                    0 | 1 => StatusCode::SERVICE_UNAVAILABLE,
+                    _ => StatusCode::OK,
+                }
+            })
+            .delete(|| async { StatusCode::BAD_GATEWAY }),
+        );
+        let url = format!("{}/flaky", testing::serve(router).await);
+        let client = Client::new();
+
+        let resp = send(client.get(&url), 0).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let resp = send(client.get(&url), DEFAULT_RETRIES).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        // Deletes are never repeated
+        let resp = send(client.delete(&url), DEFAULT_RETRIES).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_GATEWAY);
+    }
+}
@@ -0,0 +1,212 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::models::Descriptor;
+
+const CACHE_VERSION: u32 = 1;
+
+/// Metadata derived from a manifest and its config blob.
+///
+/// Content at a digest is immutable, so entries never need revalidation;
+/// `last_used` only exists so that `cache prune` can drop stale entries.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedMetadata {
+    pub config_digest: Option<String>,
+    pub created: Option<DateTime<Utc>>,
+    pub size: Option<u64>,
+    /// Config and layer blobs, so reclaimable bytes can be counted per blob
+    #[serde(default)]
+    pub blobs: Vec<Descriptor>,
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>,
+    pub last_used: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheFile {
+    version: u32,
+    entries: HashMap<String, CachedMetadata>,
+}
+
+/// On-disk cache from manifest digest to parsed image metadata
+pub struct MetadataCache {
+    path: PathBuf,
+    entries: Mutex<HashMap<String, CachedMetadata>>,
+    dirty: AtomicBool,
+}
+
+impl MetadataCache {
+    /// Default cache location: `$XDG_CACHE_HOME/regtidy/metadata.json`
+    pub fn default_path() -> Result<PathBuf> {
+        let dir = dirs::cache_dir().context("Could not determine cache directory")?;
+        Ok(dir.join("regtidy").join("metadata.json"))
+    }
+
+    /// Load the cache from `path`; a missing or incompatible file yields an empty cache
+    pub fn open(path: &Path) -> Result<Self> {
+        let entries = match fs::read(path) {
+            Ok(bytes) => match serde_json::from_slice::<CacheFile>(&bytes) {
+                Ok(file) if file.version == CACHE_VERSION => file.entries,
+                _ => HashMap::new(),
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("Failed to read cache file {}", path.display()))
+            }
+        };
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            entries: Mutex::new(entries),
+            dirty: AtomicBool::new(false),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.lock().unwrap().is_empty()
+    }
+
+    /// Look up a digest, refreshing its last-used timestamp on a hit
+    pub fn get(&self, digest: &str) -> Option<CachedMetadata> {
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries.get_mut(digest)?;
+        entry.last_used = Utc::now();
+        self.dirty.store(true, Ordering::Relaxed);
+        Some(entry.clone())
+    }
+
+    pub fn insert(&self, digest: &str, metadata: CachedMetadata) {
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(digest.to_string(), metadata);
+        self.dirty.store(true, Ordering::Relaxed);
+    }
+
+    /// Remove entries not used within `max_age` (all entries if `None`).
+    /// Returns the number of entries removed.
+    pub fn prune(&self, max_age: Option<Duration>) -> usize {
+        let mut entries = self.entries.lock().unwrap();
+        let before = entries.len();
+        match max_age {
+            Some(age) => {
+                let cutoff = Utc::now() - age;
+                entries.retain(|_, meta| meta.last_used >= cutoff);
+            }
+            None => entries.clear(),
+        }
+        let removed = before - entries.len();
+        if removed > 0 {
+            self.dirty.store(true, Ordering::Relaxed);
+        }
+        removed
+    }
+
+    /// Write the cache back to disk if it changed since it was loaded
+    pub fn save(&self) -> Result<()> {
+        if !self.dirty.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+
+        let file = CacheFile {
+            version: CACHE_VERSION,
+            entries: self.entries.lock().unwrap().clone(),
+        };
+        let json = serde_json::to_vec(&file).context("Failed to serialize cache")?;
+
+        // Write to a temporary file first so an interrupted run never leaves a truncated cache
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, json).with_context(|| format!("Failed to write {}", tmp.display()))?;
+        fs::rename(&tmp, &self.path)
+            .with_context(|| format!("Failed to write {}", self.path.display()))?;
+
+        self.dirty.store(false, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(last_used: DateTime<Utc>) -> CachedMetadata {
+        CachedMetadata {
+            config_digest: Some("sha256:cfg".to_string()),
+            created: Some(Utc::now()),
+            size: Some(42),
+            blobs: Vec::new(),
+            labels: BTreeMap::new(),
+            last_used,
+        }
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("regtidy-cache-test-{}-{}", std::process::id(), name))
+            .join("metadata.json")
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let path = temp_path("roundtrip");
+        let cache = MetadataCache::open(&path).unwrap();
+        assert!(cache.get("sha256:abc").is_none());
+
+        cache.insert("sha256:abc", metadata(Utc::now()));
+        cache.save().unwrap();
+
+        let reloaded = MetadataCache::open(&path).unwrap();
+        let entry = reloaded.get("sha256:abc").unwrap();
+        assert_eq!(entry.size, Some(42));
+        assert_eq!(entry.config_digest.as_deref(), Some("sha256:cfg"));
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_prune_by_age() {
+        let cache = MetadataCache::open(&temp_path("prune")).unwrap();
+        cache.insert("sha256:old", metadata(Utc::now() - Duration::days(40)));
+        cache.insert("sha256:new", metadata(Utc::now()));
+
+        assert_eq!(cache.prune(Some(Duration::days(30))), 1);
+        assert!(cache.get("sha256:old").is_none());
+        assert!(cache.get("sha256:new").is_some());
+
+        assert_eq!(cache.prune(None), 1);
+        assert_eq!(cache.len(), 0);
+    }
+
+    #[test]
+    fn test_incompatible_file_ignored() {
+        let path = temp_path("incompatible");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, r#"{"version":999,"entries":{}}"#).unwrap();
+
+        let cache = MetadataCache::open(&path).unwrap();
+        assert_eq!(cache.len(), 0);
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -1,20 +1,121 @@
-use clap::{Parser, Subcommand};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+use clap::{ArgAction, Args, Parser, Subcommand, ValueEnum};
+use regex::Regex;
+
+use regtidy::error::AppError;
+use regtidy::limits::Limits;
+use regtidy::pulls::PullStore;
+use regtidy::strategy::Strategy;
 
 /// regtidy — Docker Private Registry Image Cleaner
 #[derive(Parser, Debug)]
 #[command(name = "regtidy", version, about)]
 pub struct Cli {
-    /// Registry URL (e.g., http://localhost:5000)
-    #[arg(long, env = "REGTIDY_REGISTRY")]
-    pub registry: String,
+    /// Config file (default: ~/.config/regtidy/config.toml)
+    #[arg(long, env = "REGTIDY_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    /// Profiles of the config file to use (default: its `default_profile`).
+    /// Repeat to operate on several registries at once.
+    #[arg(long, env = "REGTIDY_PROFILE", value_delimiter = ',')]
+    pub profile: Vec<String>,
+
+    /// Registry URL (e.g., http://localhost:5000); repeat for several registries
+    #[arg(long, env = "REGTIDY_REGISTRY", value_delimiter = ',')]
+    pub registry: Vec<String>,
+
+    /// Registry API to talk to
+    #[arg(long, value_enum, default_value_t = BackendKind::Distribution, env = "REGTIDY_BACKEND")]
+    pub backend: BackendKind,
+
+    /// Username for basic authentication
+    #[arg(long, env = "REGTIDY_USERNAME")]
+    pub username: Option<String>,
+
+    /// Password for basic authentication
+    #[arg(long, env = "REGTIDY_PASSWORD", hide_env_values = true)]
+    pub password: Option<String>,
+
+    /// Bearer / access token (takes precedence over username and password)
+    #[arg(long, env = "REGTIDY_TOKEN", hide_env_values = true)]
+    pub token: Option<String>,
+
+    /// GitLab project id or path (required with --backend gitlab)
+    #[arg(long, env = "REGTIDY_GITLAB_PROJECT")]
+    pub gitlab_project: Option<String>,
+
+    /// Gitea/Forgejo package owner (required with --backend gitea)
+    #[arg(long, env = "REGTIDY_GITEA_OWNER")]
+    pub gitea_owner: Option<String>,
+
+    /// Read a registry's storage directory directly instead of using the HTTP API.
+    /// Deletions still go through --registry, if given.
+    #[arg(long, env = "REGTIDY_STORAGE_ROOT")]
+    pub storage_root: Option<PathBuf>,
+
+    /// Additional CA certificate (PEM) to trust for the registry
+    #[arg(long, env = "REGTIDY_CA_CERT")]
+    pub ca_cert: Option<PathBuf>,
+
+    /// Accept invalid or self-signed TLS certificates
+    #[arg(long, default_value_t = false, overrides_with = "no_insecure")]
+    pub insecure: bool,
+
+    /// Verify TLS certificates even if the profile sets `insecure`
+    #[arg(long, default_value_t = false, overrides_with = "insecure")]
+    pub no_insecure: bool,
+
+    /// Repositories or tags per catalog and tag list page (the registry's `n` parameter)
+    #[arg(long, env = "REGTIDY_PAGE_SIZE", value_parser = clap::value_parser!(u32).range(1..))]
+    pub page_size: Option<u32>,
 
     /// Repository name (omit to process all repos from catalog)
-    #[arg(long)]
+    #[arg(long, conflicts_with_all = ["repo_include", "repo_exclude", "repo_file"])]
     pub repo: Option<String>,
 
-    /// Verbose output
-    #[arg(short, long, default_value_t = false)]
-    pub verbose: bool,
+    /// Only process catalog repositories matching this glob (`team-a/**`)
+    /// or regex (`re:^ci/`); repeatable
+    #[arg(long)]
+    pub repo_include: Vec<String>,
+
+    /// Skip catalog repositories matching this glob or regex; repeatable
+    #[arg(long)]
+    pub repo_exclude: Vec<String>,
+
+    /// File of repository patterns, one per line (`!pattern` excludes)
+    #[arg(long)]
+    pub repo_file: Option<PathBuf>,
+
+    /// More log detail: -v for debug messages and requests, -vv for everything.
+    /// RUST_LOG, if set, overrides the log filter.
+    #[arg(short, long, global = true, action = ArgAction::Count)]
+    pub verbose: u8,
+
+    /// Only log errors
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Format of log lines on stderr
+    #[arg(long, value_enum, global = true, default_value_t = LogLineFormat::Text, env = "REGTIDY_LOG_FORMAT")]
+    pub log_format: LogLineFormat,
+
+    /// Do not read or write the local metadata cache
+    #[arg(long, default_value_t = false)]
+    pub no_cache: bool,
+
+    /// Write metrics in node_exporter textfile format after `list` or `clean`
+    #[arg(long, global = true, env = "REGTIDY_METRICS_FILE")]
+    pub metrics_file: Option<PathBuf>,
+
+    /// How to report progress, warnings and errors on stderr
+    #[arg(long, value_enum, global = true, default_value_t = Progress::Auto, env = "REGTIDY_PROGRESS")]
+    pub progress: Progress,
+
+    /// How to print the error that ends a failed run
+    #[arg(long, value_enum, global = true, default_value_t = ErrorFormat::Text, env = "REGTIDY_ERROR_FORMAT")]
+    pub error_format: ErrorFormat,
 
     #[command(subcommand)]
     pub command: Command,
@@ -28,11 +129,87 @@ pub enum Command {
     /// Find repositories with no tags (dangling)
     Dangling,
 
+    /// Check connectivity, credentials and whether the registry allows deletion
+    Doctor,
+
     /// Clean up images by deleting old, excess, or pattern-matched tags
-    Clean(CleanArgs),
+    Clean(Box<CleanArgs>),
+
+    /// Delete manifests that no tag points at (directly or via an index or referrer)
+    Untagged {
+        /// Preview changes without deleting
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+    },
+
+    /// Receive registry notifications and record when images were last pulled
+    Listen(ListenArgs),
+
+    /// Record manifest pulls from registry or reverse-proxy access logs
+    IngestLogs(IngestArgs),
+
+    /// Run cleanup policies on a schedule
+    Daemon(DaemonArgs),
+
+    /// Run registry garbage collection to reclaim storage
+    Gc {
+        #[command(flatten)]
+        gc: GcArgs,
+
+        /// Only report what would be collected
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+    },
+
+    /// Inspect the audit log
+    Audit {
+        #[command(subcommand)]
+        command: AuditCommand,
+    },
+
+    /// Inspect the configuration
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+
+    /// Manage the local metadata cache
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommand,
+    },
 }
 
-#[derive(Parser, Debug)]
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+    /// Print the effective configuration with secrets redacted
+    Show,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum AuditCommand {
+    /// Check the hash chain of an audit log
+    Verify {
+        /// Audit log written with --audit-log --audit-chain
+        path: PathBuf,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CacheCommand {
+    /// Remove cache entries that have not been used recently
+    Prune {
+        /// Remove entries not used in the last N days
+        #[arg(long, default_value_t = 30, conflicts_with = "all")]
+        older_than: u64,
+
+        /// Remove all entries
+        #[arg(long, default_value_t = false)]
+        all: bool,
+    },
+}
+
+#[derive(Parser, Debug, Clone)]
 pub struct CleanArgs {
     /// Keep N most recent tags, delete the rest
     #[arg(long, group = "strategy")]
@@ -46,7 +223,399 @@ pub struct CleanArgs {
     #[arg(long, group = "strategy")]
     pub pattern: Option<String>,
 
+    /// Delete images not pulled or pushed within this duration (e.g. 30d, 12h, 2w)
+    #[arg(long, group = "strategy", value_parser = parse_duration)]
+    pub unused_for: Option<chrono::Duration>,
+
+    /// Pull statistics recorded by `listen` or `ingest-logs` (used with --unused-for)
+    #[arg(long, env = "REGTIDY_PULL_STORE")]
+    pub pull_store: Option<PathBuf>,
+
     /// Preview changes without deleting
-    #[arg(long, default_value_t = false)]
+    #[arg(long, default_value_t = false, overrides_with = "no_dry_run")]
     pub dry_run: bool,
+
+    /// Delete for real even if the profile sets `dry_run`
+    #[arg(long, default_value_t = false, overrides_with = "dry_run")]
+    pub no_dry_run: bool,
+
+    /// Keep a digest in every repository while any repository keeps it.
+    /// Indexes the tags of the whole catalog before planning.
+    #[arg(long, default_value_t = false)]
+    pub protect_across_repos: bool,
+
+    /// Delete without asking for confirmation
+    #[arg(short, long, default_value_t = false)]
+    pub yes: bool,
+
+    /// Abort if more than N tags would be deleted (per registry)
+    #[arg(long)]
+    pub max_tags: Option<usize>,
+
+    /// Abort if more than N digests would be deleted (per registry)
+    #[arg(long)]
+    pub max_digests: Option<usize>,
+
+    /// Abort if a larger share of any repository's tags would be deleted (e.g. 0.5 or 50%)
+    #[arg(long, value_parser = crate::safeguard::parse_fraction)]
+    pub max_repo_fraction: Option<f64>,
+
+    /// Allow deleting every tag of a repository
+    #[arg(long, default_value_t = false)]
+    pub allow_empty_repo: bool,
+
+    /// Review and edit the plan in a terminal UI before anything is deleted
+    #[arg(long, default_value_t = false)]
+    pub interactive: bool,
+
+    /// Do not check that the registry allows deletion before deleting
+    #[arg(long, default_value_t = false)]
+    pub skip_preflight: bool,
+
+    /// Run registry garbage collection after cleaning
+    #[arg(long, default_value_t = false)]
+    pub gc: bool,
+
+    /// Append a JSON line per deleted digest to this file
+    #[arg(long, env = "REGTIDY_AUDIT_LOG")]
+    pub audit_log: Option<PathBuf>,
+
+    /// Hash-chain audit log entries so tampering can be detected
+    #[arg(long, default_value_t = false, requires = "audit_log")]
+    pub audit_chain: bool,
+
+    #[command(flatten)]
+    pub gc_args: GcArgs,
+
+    #[command(flatten)]
+    pub notify: NotifyArgs,
+}
+
+impl CleanArgs {
+    /// The cleanup strategy selected by --keep, --older-than, --pattern or --unused-for
+    pub fn strategy(&self) -> Result<Strategy, AppError> {
+        if let Some(n) = self.keep {
+            Ok(Strategy::KeepRecent(n))
+        } else if let Some(days) = self.older_than {
+            Ok(Strategy::OlderThan(days))
+        } else if let Some(ref pat) = self.pattern {
+            let re = Regex::new(pat)?;
+            Ok(Strategy::Pattern(re))
+        } else if let Some(duration) = self.unused_for {
+            let store = PullStore::open_or_default(self.pull_store.as_deref())
+                .map_err(|e| AppError::PullStore(format!("{:#}", e)))?;
+            Ok(Strategy::UnusedFor(duration, store))
+        } else {
+            Err(AppError::NoStrategy)
+        }
+    }
+
+    /// The safety limits set by --max-tags, --max-digests, --max-repo-fraction
+    /// and --allow-empty-repo
+    pub fn limits(&self) -> Limits {
+        Limits {
+            max_tags: self.max_tags,
+            max_digests: self.max_digests,
+            max_repo_fraction: self.max_repo_fraction,
+            allow_empty_repo: self.allow_empty_repo,
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct ListenArgs {
+    /// Address to listen on for notification webhooks
+    #[arg(long, default_value = "0.0.0.0:5050")]
+    pub bind: SocketAddr,
+
+    /// File to record pull statistics in
+    #[arg(long, env = "REGTIDY_PULL_STORE")]
+    pub pull_store: Option<PathBuf>,
+
+    /// Require `Authorization: Bearer <token>` on incoming notifications
+    #[arg(long, env = "REGTIDY_LISTEN_TOKEN", hide_env_values = true)]
+    pub auth_token: Option<String>,
+
+    /// Seconds between writes of the pull store
+    #[arg(long, default_value_t = 10)]
+    pub flush_interval: u64,
+}
+
+#[derive(Args, Debug)]
+pub struct DaemonArgs {
+    /// Policy file (YAML) defining schedules and cleanup options
+    #[arg(long, env = "REGTIDY_POLICY")]
+    pub policy: PathBuf,
+
+    /// Lock file preventing overlapping runs across processes
+    #[arg(long)]
+    pub lock_file: Option<PathBuf>,
+
+    /// Serve Prometheus metrics on this address (GET /metrics)
+    #[arg(long)]
+    pub metrics_bind: Option<SocketAddr>,
+
+    /// Run every policy once immediately at startup
+    #[arg(long, default_value_t = false)]
+    pub run_now: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct IngestArgs {
+    /// Log files to read (`-` for stdin)
+    #[arg(required = true)]
+    pub files: Vec<String>,
+
+    /// Log format
+    #[arg(long, value_enum, default_value_t = LogFormat::Auto)]
+    pub format: LogFormat,
+
+    /// File to record pull statistics in
+    #[arg(long, env = "REGTIDY_PULL_STORE")]
+    pub pull_store: Option<PathBuf>,
+}
+
+/// Access log formats understood by `ingest-logs`
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Detect the format of each line
+    Auto,
+    /// docker/distribution logrus JSON output
+    DistributionJson,
+    /// docker/distribution logrus text output
+    DistributionText,
+    /// Common/combined log format (nginx, traefik, distribution access log)
+    Clf,
+    /// Traefik JSON access log
+    TraefikJson,
+}
+
+/// Turn a `clean` option from a policy or config file into command-line flags
+/// (`older_than: 30` becomes `--older-than 30`, `true` a bare flag, lists repeat)
+pub fn push_option_flags(
+    argv: &mut Vec<String>,
+    key: &str,
+    value: &serde_json::Value,
+) -> anyhow::Result<()> {
+    use serde_json::Value;
+
+    let flag = format!("--{}", key.replace('_', "-"));
+    match value {
+        Value::Bool(true) => argv.push(flag),
+        Value::Bool(false) | Value::Null => {}
+        Value::Number(n) => argv.extend([flag, n.to_string()]),
+        Value::String(s) => argv.extend([flag, s.clone()]),
+        Value::Array(items) => {
+            for item in items {
+                push_option_flags(argv, key, item)?;
+            }
+        }
+        Value::Object(_) => anyhow::bail!("Unsupported value for clean option {:?}", key),
+    }
+    Ok(())
+}
+
+/// Parse a duration such as `30d`, `12h`, `90m`, `2w` or `45s`
+pub fn parse_duration(s: &str) -> Result<chrono::Duration, String> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| format!("missing unit in '{}' (use s, m, h, d or w)", s))?;
+    let (value, unit) = s.split_at(split);
+    let value: i64 = value
+        .parse()
+        .map_err(|_| format!("invalid duration '{}'", s))?;
+
+    match unit {
+        "s" => Ok(chrono::Duration::seconds(value)),
+        "m" => Ok(chrono::Duration::minutes(value)),
+        "h" => Ok(chrono::Duration::hours(value)),
+        "d" => Ok(chrono::Duration::days(value)),
+        "w" => Ok(chrono::Duration::weeks(value)),
+        _ => Err(format!("unknown unit '{}' in '{}' (use s, m, h, d or w)", unit, s)),
+    }
+}
+
+/// Reporting of progress and log messages
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Progress {
+    /// A progress bar on terminals (unless --verbose or --quiet), log lines otherwise
+    Auto,
+    /// Progress bars for tag resolution and deletion
+    Bar,
+    /// Log lines only, in the --log-format
+    Log,
+    /// One JSON object per event
+    Json,
+}
+
+/// Format of regtidy's own log lines
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogLineFormat {
+    /// `[LEVEL] message key=value` lines
+    Text,
+    /// One JSON object per line, with the enclosing request span
+    Json,
+}
+
+/// Output format of the final error
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorFormat {
+    /// `Error: <message>` line
+    Text,
+    /// One JSON object with the error class, exit code and registry error codes
+    Json,
+}
+
+/// Registry API flavour
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BackendKind {
+    /// Docker Distribution / OCI Registry V2 API
+    Distribution,
+    /// Harbor v2.0 API
+    Harbor,
+    /// GitLab Container Registry API
+    Gitlab,
+    /// Gitea / Forgejo package API
+    Gitea,
+}
+
+/// How to reach the registry's `garbage-collect` command
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GcRunner {
+    /// Run the registry binary on this host
+    Local,
+    /// `docker exec` into the registry container
+    Docker,
+    /// `kubectl exec` into the registry pod
+    Kubectl,
+}
+
+/// When to send notifications
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NotifyOn {
+    /// After every run
+    Always,
+    /// Only when the run failed or had errors
+    Failure,
+}
+
+/// Connection security for SMTP
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SmtpTls {
+    /// Plain connection (e.g. a local relay)
+    None,
+    /// Upgrade with STARTTLS (port 587)
+    Starttls,
+    /// Implicit TLS (port 465)
+    Tls,
+}
+
+#[derive(Args, Debug, Clone, PartialEq)]
+pub struct NotifyArgs {
+    /// POST a JSON summary to this URL after the run (repeatable)
+    #[arg(long, env = "REGTIDY_NOTIFY_WEBHOOK", value_delimiter = ',', hide_env_values = true)]
+    pub notify_webhook: Vec<String>,
+
+    /// Slack or Mattermost incoming webhook URL (repeatable)
+    #[arg(long, env = "REGTIDY_NOTIFY_SLACK", value_delimiter = ',', hide_env_values = true)]
+    pub notify_slack: Vec<String>,
+
+    /// Email the summary to this address (repeatable, requires --smtp-server)
+    #[arg(long, requires = "smtp_server")]
+    pub notify_email: Vec<String>,
+
+    /// Send notifications after every run or only on failure
+    #[arg(long, value_enum, default_value_t = NotifyOn::Always)]
+    pub notify_on: NotifyOn,
+
+    /// Template for the message body (minijinja syntax)
+    #[arg(long)]
+    pub notify_template: Option<PathBuf>,
+
+    /// SMTP server as host or host:port
+    #[arg(long, env = "REGTIDY_SMTP_SERVER")]
+    pub smtp_server: Option<String>,
+
+    /// SMTP connection security
+    #[arg(long, value_enum, default_value_t = SmtpTls::Starttls)]
+    pub smtp_tls: SmtpTls,
+
+    /// SMTP username
+    #[arg(long, env = "REGTIDY_SMTP_USERNAME")]
+    pub smtp_username: Option<String>,
+
+    /// SMTP password
+    #[arg(long, env = "REGTIDY_SMTP_PASSWORD", hide_env_values = true)]
+    pub smtp_password: Option<String>,
+
+    /// Sender address of notification emails
+    #[arg(long, default_value = "regtidy@localhost")]
+    pub smtp_from: String,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct GcArgs {
+    /// Where to run the garbage collector
+    #[arg(long, value_enum, default_value_t = GcRunner::Docker, env = "REGTIDY_GC_RUNNER")]
+    pub gc_runner: GcRunner,
+
+    /// Registry container (docker) or pod (kubectl) name
+    #[arg(long, env = "REGTIDY_GC_CONTAINER")]
+    pub gc_container: Option<String>,
+
+    /// Kubernetes namespace of the registry pod
+    #[arg(long, env = "REGTIDY_GC_NAMESPACE")]
+    pub gc_namespace: Option<String>,
+
+    /// Container of the registry pod to run in (kubectl -c), if not its default one
+    #[arg(long, env = "REGTIDY_GC_POD_CONTAINER")]
+    pub gc_pod_container: Option<String>,
+
+    /// Path of the registry binary
+    #[arg(long, default_value = "bin/registry")]
+    pub gc_binary: String,
+
+    /// Path of the registry configuration file
+    #[arg(long, default_value = "/etc/docker/registry/config.yml")]
+    pub gc_config: String,
+
+    /// Also delete manifests that are not referenced by any tag
+    #[arg(long, default_value_t = false)]
+    pub gc_delete_untagged: bool,
+
+    /// Registry storage root; its size is measured before and after to report reclaimed bytes
+    #[arg(long)]
+    pub gc_storage_root: Option<String>,
+
+    /// Shell command that puts the registry in read-only mode before collecting
+    #[arg(long, requires = "gc_read_write_cmd")]
+    pub gc_read_only_cmd: Option<String>,
+
+    /// Shell command that restores read-write mode after collecting
+    #[arg(long, requires = "gc_read_only_cmd")]
+    pub gc_read_write_cmd: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("30d"), Ok(chrono::Duration::days(30)));
+        assert_eq!(parse_duration("12h"), Ok(chrono::Duration::hours(12)));
+        assert_eq!(parse_duration("2w"), Ok(chrono::Duration::weeks(2)));
+        assert!(parse_duration("30").is_err());
+        assert!(parse_duration("d").is_err());
+        assert!(parse_duration("5y").is_err());
+    }
+
+    #[test]
+    fn test_gc_mode_commands_require_each_other() {
+        let parse = |args: &[&str]| Cli::try_parse_from([&["regtidy", "gc"], args].concat());
+        assert!(parse(&["--gc-read-only-cmd", "ro"]).is_err());
+        assert!(parse(&["--gc-read-write-cmd", "rw"]).is_err());
+        assert!(parse(&["--gc-read-only-cmd", "ro", "--gc-read-write-cmd", "rw"]).is_ok());
+    }
 }
@@ -0,0 +1,584 @@
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, ValueEnum};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::cli::{push_option_flags, BackendKind, CleanArgs, Cli, Command};
+
+/// Strategy options of `clean`; a profile's strategy only applies if none is given
+const STRATEGY_OPTIONS: [&str; 4] = ["keep", "older_than", "pattern", "unused_for"];
+
+/// `clean` options whose values embed credentials, besides any `*password*`/`*token*` key
+const SECRET_CLEAN_OPTIONS: [&str; 2] = ["notify_slack", "notify_webhook"];
+
+/// `config.toml`
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    default_profile: Option<String>,
+    #[serde(default)]
+    profiles: BTreeMap<String, Profile>,
+}
+
+/// Settings of one registry
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    pub registry: Option<String>,
+    pub backend: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub token: Option<String>,
+    pub gitlab_project: Option<String>,
+    pub gitea_owner: Option<String>,
+    pub storage_root: Option<PathBuf>,
+    pub ca_cert: Option<PathBuf>,
+    pub insecure: Option<bool>,
+    pub page_size: Option<u32>,
+    pub no_cache: Option<bool>,
+    /// Default options for `clean`, named like its flags
+    #[serde(default)]
+    pub clean: BTreeMap<String, Value>,
+}
+
+/// Where an effective setting came from, highest precedence first
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    CommandLine,
+    Env,
+    Profile,
+    Default,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::CommandLine => write!(f, "command line"),
+            Source::Env => write!(f, "environment"),
+            Source::Profile => write!(f, "profile"),
+            Source::Default => write!(f, "default"),
+        }
+    }
+}
+
+/// Connection settings of one registry, merged from flags, environment and profile
+#[derive(Debug, Clone)]
+pub struct Target {
+    pub profile: Option<String>,
+    pub registry: Option<String>,
+    pub backend: BackendKind,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub token: Option<String>,
+    pub gitlab_project: Option<String>,
+    pub gitea_owner: Option<String>,
+    pub storage_root: Option<PathBuf>,
+    pub ca_cert: Option<PathBuf>,
+    pub insecure: bool,
+    pub page_size: Option<u32>,
+    pub no_cache: bool,
+    /// `clean` options with this profile's defaults applied (for the clean subcommand)
+    pub clean: Option<CleanArgs>,
+    /// The profile's `clean` defaults, for `config show`
+    pub clean_defaults: BTreeMap<String, Value>,
+    pub sources: BTreeMap<&'static str, Source>,
+}
+
+impl Target {
+    /// Short name used to tag output: the profile, else the registry host
+    pub fn label(&self) -> String {
+        if let Some(profile) = &self.profile {
+            return profile.clone();
+        }
+        if let Some(url) = &self.registry {
+            let host = url.split("://").nth(1).unwrap_or(url);
+            return host.trim_end_matches('/').to_string();
+        }
+        match &self.storage_root {
+            Some(root) => root.display().to_string(),
+            None => "registry".to_string(),
+        }
+    }
+}
+
+/// The config file in use and the registries to operate on
+#[derive(Debug)]
+pub struct Effective {
+    /// Config file that was read, if any
+    pub path: Option<PathBuf>,
+    pub targets: Vec<Target>,
+}
+
+/// Default location: `$XDG_CONFIG_HOME/regtidy/config.toml`
+pub fn default_path() -> Result<PathBuf> {
+    let dir = dirs::config_dir().context("Could not determine config directory")?;
+    Ok(dir.join("regtidy").join("config.toml"))
+}
+
+/// Load the config file; a missing default file is not an error, a missing `--config` is
+fn load(explicit: Option<&Path>) -> Result<(Option<PathBuf>, ConfigFile)> {
+    match explicit {
+        Some(path) => Ok((Some(path.to_path_buf()), read_config(path)?)),
+        None => {
+            let path = default_path()?;
+            if path.exists() {
+                let file = read_config(&path)?;
+                Ok((Some(path), file))
+            } else {
+                Ok((None, ConfigFile::default()))
+            }
+        }
+    }
+}
+
+fn read_config(path: &Path) -> Result<ConfigFile> {
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config file {}", path.display()))?;
+    toml::from_str(&text).with_context(|| format!("Failed to parse config file {}", path.display()))
+}
+
+/// Parse the command line and resolve the registries to operate on.
+///
+/// Precedence: command-line flags, then environment variables, then the
+/// profile, then built-in defaults. Several `--registry` values or several
+/// `--profile` names yield one target each.
+pub fn parse_cli() -> Result<(Cli, Effective)> {
+    parse_cli_from(std::env::args_os().collect())
+}
+
+fn parse_cli_from(argv: Vec<OsString>) -> Result<(Cli, Effective)> {
+    let matches = Cli::command().get_matches_from(&argv);
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let (path, file) = load(cli.config.as_deref())?;
+
+    let names: Vec<Option<String>> = if cli.profile.is_empty() {
+        vec![file.default_profile.clone()]
+    } else {
+        cli.profile.iter().cloned().map(Some).collect()
+    };
+    if names.len() > 1 && cli.registry.len() > 1 {
+        anyhow::bail!("Use either several --registry values or several --profile names, not both");
+    }
+
+    let mut targets = Vec::new();
+    for name in names {
+        let profile = match &name {
+            Some(name) => file.profiles.get(name).cloned().with_context(|| {
+                let known: Vec<&String> = file.profiles.keys().collect();
+                format!("Unknown profile {:?} (available: {:?})", name, known)
+            })?,
+            None => Profile::default(),
+        };
+        let clean = clean_args(&argv, &matches, &profile, name.as_deref())?;
+
+        let mut target = merge_target(&cli, &matches, &profile)?;
+        target.profile = name;
+        target.clean = clean;
+        if cli.registry.len() > 1 {
+            for url in &cli.registry {
+                let mut target = target.clone();
+                target.registry = Some(url.clone());
+                targets.push(target);
+            }
+        } else {
+            targets.push(target);
+        }
+    }
+
+    Ok((cli, Effective { path, targets }))
+}
+
+/// `clean` options with the profile's defaults appended as flags, so clap validates them
+fn clean_args(
+    argv: &[OsString],
+    matches: &ArgMatches,
+    profile: &Profile,
+    name: Option<&str>,
+) -> Result<Option<CleanArgs>> {
+    if matches.subcommand_matches("clean").is_none() {
+        return Ok(None);
+    }
+    let mut argv = argv.to_vec();
+    argv.extend(
+        clean_defaults(profile, matches)?
+            .into_iter()
+            .map(OsString::from),
+    );
+    let matches = Cli::command().try_get_matches_from(&argv).map_err(|e| {
+        anyhow::anyhow!(
+            "Invalid clean options in profile {:?}: {}",
+            name.unwrap_or_default(),
+            e.to_string().trim_end()
+        )
+    })?;
+    match Cli::from_arg_matches(&matches).map_err(|e| anyhow::anyhow!("{}", e))? {
+        Cli {
+            command: Command::Clean(args),
+            ..
+        } => Ok(Some(*args)),
+        _ => Ok(None),
+    }
+}
+
+/// Flags for the profile's `clean` options not already given explicitly
+fn clean_defaults(profile: &Profile, matches: &ArgMatches) -> Result<Vec<String>> {
+    let Some(clean) = matches.subcommand_matches("clean") else {
+        return Ok(Vec::new());
+    };
+    let has_strategy = STRATEGY_OPTIONS
+        .iter()
+        .any(|id| explicit_source(clean, id).is_some());
+
+    let mut argv = Vec::new();
+    for (key, value) in &profile.clean {
+        let id = key.replace('-', "_");
+        if has_strategy && STRATEGY_OPTIONS.contains(&id.as_str()) {
+            continue;
+        }
+        if explicit_source(clean, &id).is_some() {
+            continue;
+        }
+        push_option_flags(&mut argv, &id, value)?;
+    }
+    Ok(argv)
+}
+
+/// The source of a value the user gave, or None if it is unset or a default.
+/// A `--no-…` flag gives a false value to the boolean it negates.
+fn explicit_source(matches: &ArgMatches, id: &str) -> Option<Source> {
+    given_source(matches, id).or_else(|| given_source(matches, &format!("no_{}", id)))
+}
+
+fn given_source(matches: &ArgMatches, id: &str) -> Option<Source> {
+    match matches
+        .try_get_raw(id)
+        .ok()
+        .flatten()
+        .and_then(|_| matches.value_source(id))
+    {
+        Some(ValueSource::CommandLine) => Some(Source::CommandLine),
+        Some(ValueSource::EnvVariable) => Some(Source::Env),
+        _ => None,
+    }
+}
+
+/// Settings the user gave, completed from the profile
+fn merge_target(cli: &Cli, matches: &ArgMatches, profile: &Profile) -> Result<Target> {
+    let mut sources = BTreeMap::new();
+    let mut pick = |id: &'static str, given: Option<Source>, from_profile: bool| {
+        let source = match given {
+            Some(source) => source,
+            None if from_profile => Source::Profile,
+            None => Source::Default,
+        };
+        sources.insert(id, source);
+    };
+
+    macro_rules! merge {
+        ($id:literal, $cli:expr, $profile:expr) => {{
+            let given = explicit_source(matches, $id);
+            let profile_value = $profile.clone();
+            pick(
+                $id,
+                given.clone(),
+                given.is_none() && profile_value.is_some(),
+            );
+            match (given, profile_value) {
+                (None, Some(value)) => Some(value),
+                _ => $cli,
+            }
+        }};
+    }
+
+    let backend = match &profile.backend {
+        Some(name) => Some(
+            BackendKind::from_str(name, true)
+                .map_err(|e| anyhow::anyhow!("Invalid backend {:?} in profile: {}", name, e))?,
+        ),
+        None => None,
+    };
+
+    let registry = merge!("registry", cli.registry.first().cloned(), profile.registry);
+    let backend = merge!("backend", Some(cli.backend), backend).unwrap_or(cli.backend);
+    let username = merge!("username", cli.username.clone(), profile.username);
+    let password = merge!("password", cli.password.clone(), profile.password);
+    let token = merge!("token", cli.token.clone(), profile.token);
+    let gitlab_project = merge!(
+        "gitlab_project",
+        cli.gitlab_project.clone(),
+        profile.gitlab_project
+    );
+    let gitea_owner = merge!("gitea_owner", cli.gitea_owner.clone(), profile.gitea_owner);
+    let storage_root = merge!(
+        "storage_root",
+        cli.storage_root.clone(),
+        profile.storage_root
+    );
+    let ca_cert = merge!("ca_cert", cli.ca_cert.clone(), profile.ca_cert);
+    let insecure = merge!("insecure", Some(cli.insecure), profile.insecure).unwrap_or_default();
+    let page_size = merge!("page_size", cli.page_size, profile.page_size);
+    if page_size == Some(0) {
+        anyhow::bail!("page_size must be at least 1");
+    }
+    let no_cache = merge!("no_cache", Some(cli.no_cache), profile.no_cache).unwrap_or_default();
+
+    Ok(Target {
+        profile: None,
+        registry,
+        backend,
+        username,
+        password,
+        token,
+        gitlab_project,
+        gitea_owner,
+        storage_root,
+        ca_cert,
+        insecure,
+        page_size,
+        no_cache,
+        clean: None,
+        clean_defaults: profile.clean.clone(),
+        sources,
+    })
+}
+
+/// Print the effective configuration of every target; passwords and tokens are redacted
+pub fn show(effective: &Effective) {
+    match &effective.path {
+        Some(path) => println!("# Config file: {}", path.display()),
+        None => println!("# Config file: none"),
+    }
+
+    let quote = |value: Option<String>| match value {
+        Some(value) => format!("{:?}", value),
+        None => "(unset)".to_string(),
+    };
+    let redact = |value: &Option<String>| value.as_ref().map(|_| "********".to_string());
+    let path = |value: &Option<PathBuf>| value.as_ref().map(|p| p.display().to_string());
+
+    for target in &effective.targets {
+        println!();
+        match &target.profile {
+            Some(name) => println!("# Profile: {}", name),
+            None => println!("# Profile: none"),
+        }
+        let backend = target
+            .backend
+            .to_possible_value()
+            .map(|v| v.get_name().to_string());
+
+        let rows = [
+            ("registry", quote(target.registry.clone())),
+            ("backend", quote(backend)),
+            ("username", quote(target.username.clone())),
+            ("password", quote(redact(&target.password))),
+            ("token", quote(redact(&target.token))),
+            ("gitlab_project", quote(target.gitlab_project.clone())),
+            ("gitea_owner", quote(target.gitea_owner.clone())),
+            ("storage_root", quote(path(&target.storage_root))),
+            ("ca_cert", quote(path(&target.ca_cert))),
+            ("insecure", target.insecure.to_string()),
+            ("page_size", quote(target.page_size.map(|n| n.to_string()))),
+            ("no_cache", target.no_cache.to_string()),
+        ];
+        for (key, value) in rows {
+            let source = target.sources.get(key).unwrap_or(&Source::Default);
+            println!("{:<15} = {:<40} # {}", key, value, source);
+        }
+
+        if !target.clean_defaults.is_empty() {
+            println!("[clean]");
+            for (key, value) in clean_rows(&target.clean_defaults) {
+                println!("{:<15} = {}", key, value);
+            }
+        }
+    }
+}
+
+/// The profile's `clean` options for display, with credentials and webhook URLs redacted
+fn clean_rows(defaults: &BTreeMap<String, Value>) -> Vec<(&str, String)> {
+    defaults
+        .iter()
+        .map(|(key, value)| {
+            let id = key.replace('-', "_");
+            let secret = id.contains("password")
+                || id.contains("token")
+                || SECRET_CLEAN_OPTIONS.contains(&id.as_str());
+            let value = if secret {
+                format!("{:?}", "********")
+            } else {
+                value.to_string()
+            };
+            (key.as_str(), value)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const CONFIG: &str = r#"
+default_profile = "staging"
+
+[profiles.staging]
+registry = "https://staging.example.com"
+username = "robot"
+password = "secret"
+insecure = true
+
+[profiles.staging.clean]
+keep = 10
+dry_run = true
+
+[profiles.gitlab]
+registry = "https://gitlab.example.com"
+backend = "gitlab"
+gitlab_project = "group/app"
+"#;
+
+    fn parse(args: &[&str]) -> Result<(Cli, Effective)> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "regtidy-config-{}-{}.toml",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::write(&path, CONFIG).unwrap();
+        let mut argv: Vec<OsString> = vec!["regtidy".into(), "--config".into(), path.into()];
+        argv.extend(args.iter().map(OsString::from));
+        parse_cli_from(argv)
+    }
+
+    #[test]
+    fn test_profile_fills_unset_values() {
+        let (_, effective) = parse(&["list"]).unwrap();
+        let target = &effective.targets[0];
+        assert_eq!(target.profile.as_deref(), Some("staging"));
+        assert_eq!(
+            target.registry.as_deref(),
+            Some("https://staging.example.com")
+        );
+        assert_eq!(target.password.as_deref(), Some("secret"));
+        assert!(target.insecure);
+        assert_eq!(target.sources["registry"], Source::Profile);
+        assert_eq!(target.sources["backend"], Source::Default);
+    }
+
+    #[test]
+    fn test_command_line_wins() {
+        let (_, effective) = parse(&[
+            "--registry",
+            "http://localhost:5000",
+            "--profile",
+            "gitlab",
+            "list",
+        ])
+        .unwrap();
+        let target = &effective.targets[0];
+        assert_eq!(target.registry.as_deref(), Some("http://localhost:5000"));
+        assert_eq!(target.sources["registry"], Source::CommandLine);
+        assert_eq!(target.backend, BackendKind::Gitlab);
+        assert_eq!(target.gitlab_project.as_deref(), Some("group/app"));
+        assert_eq!(target.username, None);
+    }
+
+    #[test]
+    fn test_clean_defaults() {
+        let (_, effective) = parse(&["clean"]).unwrap();
+        let args = effective.targets[0].clean.as_ref().unwrap();
+        assert_eq!(args.keep, Some(10));
+        assert!(args.dry_run);
+
+        // An explicit strategy replaces the profile's
+        let (_, effective) = parse(&["clean", "--older-than", "30"]).unwrap();
+        let args = effective.targets[0].clean.as_ref().unwrap();
+        assert_eq!(args.keep, None);
+        assert_eq!(args.older_than, Some(30));
+        assert!(args.dry_run);
+    }
+
+    #[test]
+    fn test_negated_profile_booleans() {
+        let (_, effective) = parse(&["--no-insecure", "clean", "--no-dry-run"]).unwrap();
+        let target = &effective.targets[0];
+        assert!(!target.insecure);
+        assert_eq!(target.sources["insecure"], Source::CommandLine);
+        let args = target.clean.as_ref().unwrap();
+        assert!(!args.dry_run);
+        assert_eq!(args.keep, Some(10));
+
+        // The last of a flag and its negation wins
+        let (_, effective) = parse(&["--no-insecure", "--insecure", "list"]).unwrap();
+        assert!(effective.targets[0].insecure);
+    }
+
+    #[test]
+    fn test_clean_rows_redact_secrets() {
+        let defaults: BTreeMap<String, Value> = toml::from_str(
+            r#"
+keep = 10
+smtp_password = "hunter2"
+smtp-username = "robot"
+notify_slack = ["https://hooks.slack.com/services/T0/B0/secret"]
+notify_webhook = ["https://example.com/hook?token=secret"]
+"#,
+        )
+        .unwrap();
+
+        let rows = clean_rows(&defaults);
+        assert_eq!(
+            rows,
+            vec![
+                ("keep", "10".to_string()),
+                ("notify_slack", "\"********\"".to_string()),
+                ("notify_webhook", "\"********\"".to_string()),
+                ("smtp-username", "\"robot\"".to_string()),
+                ("smtp_password", "\"********\"".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_multiple_targets() {
+        let (_, effective) =
+            parse(&["--profile", "staging,gitlab", "clean", "--keep", "2"]).unwrap();
+        let labels: Vec<String> = effective.targets.iter().map(|t| t.label()).collect();
+        assert_eq!(labels, vec!["staging", "gitlab"]);
+        assert!(effective.targets[0].clean.as_ref().unwrap().dry_run);
+        assert!(!effective.targets[1].clean.as_ref().unwrap().dry_run);
+
+        let (_, effective) = parse(&[
+            "--profile",
+            "gitlab",
+            "--registry",
+            "https://a.example.com",
+            "--registry",
+            "https://b.example.com/",
+            "list",
+        ])
+        .unwrap();
+        assert_eq!(effective.targets.len(), 2);
+        assert_eq!(
+            effective.targets[1].registry.as_deref(),
+            Some("https://b.example.com/")
+        );
+        assert_eq!(effective.targets[1].backend, BackendKind::Gitlab);
+
+        assert!(parse(&["--profile", "staging,gitlab", "--registry", "a,b", "list"]).is_err());
+    }
+
+    #[test]
+    fn test_unknown_profile() {
+        let err = parse(&["--profile", "prod", "list"]).unwrap_err();
+        assert!(err.to_string().contains("Unknown profile \"prod\""));
+    }
+}
@@ -0,0 +1,449 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use clap::Parser;
+use cron::Schedule;
+use serde::Deserialize;
+use serde_json::Value;
+use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
+
+use regtidy::backend::RegistryBackend;
+use regtidy::filter::RepoFilter;
+use regtidy::report::Reporter;
+
+use crate::cli::{push_option_flags, CleanArgs, Cli, DaemonArgs};
+use crate::listen::shutdown_signal;
+use crate::metrics::Metrics;
+
+/// Top level of the policy file
+#[derive(Debug, Deserialize)]
+struct PolicyFile {
+    policies: Vec<PolicySpec>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PolicySpec {
+    name: String,
+    /// Cron expression; 5-field expressions are accepted and run at second 0
+    schedule: String,
+    /// Repositories to clean; empty means the --repo flag or the whole catalog
+    #[serde(default)]
+    repos: Vec<String>,
+    /// `clean` options, named like the flags (`older_than: 30`, `dry_run: true`)
+    #[serde(default)]
+    clean: BTreeMap<String, Value>,
+}
+
+/// A validated policy ready to be scheduled
+pub struct Policy {
+    pub name: String,
+    schedule: Schedule,
+    repos: Vec<String>,
+    args: CleanArgs,
+}
+
+impl Policy {
+    fn from_spec(spec: PolicySpec) -> Result<Self> {
+        let expr = if spec.schedule.split_whitespace().count() == 5 {
+            format!("0 {}", spec.schedule)
+        } else {
+            spec.schedule.clone()
+        };
+        let schedule = Schedule::from_str(&expr)
+            .with_context(|| format!("Invalid schedule {:?}", spec.schedule))?;
+
+        let mut argv = vec!["clean".to_string()];
+        for (key, value) in &spec.clean {
+            push_option_flags(&mut argv, key, value)?;
+        }
+        let mut args = CleanArgs::try_parse_from(&argv)
+            .map_err(|e| anyhow::anyhow!("Invalid clean options: {}", e.to_string().trim_end()))?;
+        if args.interactive {
+            anyhow::bail!("interactive cannot be used in a policy");
+        }
+        // Scheduled runs are approved by the policy; the safety limits still apply
+        args.yes = true;
+
+        Ok(Self {
+            name: spec.name,
+            schedule,
+            repos: spec.repos,
+            args,
+        })
+    }
+
+    pub fn next_after(&self, after: &DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.schedule.after(after).next()
+    }
+}
+
+/// Load and validate every policy in `path`
+pub fn load_policies(path: &Path) -> Result<Vec<Policy>> {
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read policy file {}", path.display()))?;
+    let file: PolicyFile = serde_yaml::from_str(&text)
+        .with_context(|| format!("Failed to parse policy file {}", path.display()))?;
+    if file.policies.is_empty() {
+        anyhow::bail!("{} defines no policies", path.display());
+    }
+
+    file.policies
+        .into_iter()
+        .map(|spec| {
+            let name = spec.name.clone();
+            Policy::from_spec(spec).with_context(|| format!("Policy {:?}", name))
+        })
+        .collect()
+}
+
+/// Exclusive lock held for the lifetime of the daemon
+struct RunLock {
+    _file: File,
+    path: PathBuf,
+}
+
+impl RunLock {
+    fn default_path() -> Result<PathBuf> {
+        let dir = dirs::cache_dir().context("Could not determine cache directory")?;
+        Ok(dir.join("regtidy").join("daemon.lock"))
+    }
+
+    fn acquire(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(path)
+            .with_context(|| format!("Failed to open lock file {}", path.display()))?;
+        file.try_lock().map_err(|_| {
+            anyhow::anyhow!(
+                "{} is locked; another regtidy daemon is already running",
+                path.display()
+            )
+        })?;
+        Ok(Self {
+            _file: file,
+            path: path.to_path_buf(),
+        })
+    }
+}
+
+/// Run the policies on their schedules until SIGINT/SIGTERM.
+///
+/// Runs never overlap: policies due at the same time run one after another,
+/// and a tick that passes while another run is still going is skipped.
+pub async fn run_daemon(
+    client: &dyn RegistryBackend,
+    cli: &Cli,
+    filter: &RepoFilter,
+    args: &DaemonArgs,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    let policies = load_policies(&args.policy)?;
+    let lock_path = match &args.lock_file {
+        Some(path) => path.clone(),
+        None => RunLock::default_path()?,
+    };
+    let lock = RunLock::acquire(&lock_path)?;
+
+    let cancel = CancellationToken::new();
+    {
+        let cancel = cancel.clone();
+        tokio::spawn(async move {
+            shutdown_signal().await;
+            cancel.cancel();
+        });
+    }
+
+    let metrics = Arc::new(Metrics::default());
+    if let Some(addr) = args.metrics_bind {
+        let listener = tokio::net::TcpListener::bind(addr)
+            .await
+            .with_context(|| format!("Failed to bind {}", addr))?;
+        let app = metrics.clone().router();
+        let shutdown = cancel.clone().cancelled_owned();
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, app)
+                .with_graceful_shutdown(shutdown)
+                .await
+            {
+                tracing::error!("Metrics server failed: {}", e);
+            }
+        });
+        tracing::info!("Serving metrics on http://{}/metrics", addr);
+    }
+
+    let now = Utc::now();
+    let mut next: Vec<Option<DateTime<Utc>>> = policies
+        .iter()
+        .map(|p| {
+            if args.run_now {
+                Some(now)
+            } else {
+                p.next_after(&now)
+            }
+        })
+        .collect();
+    for (policy, at) in policies.iter().zip(&next) {
+        tracing::info!(
+            event = "scheduled",
+            policy = %policy.name,
+            next_run = at.map(|t| t.to_rfc3339()).as_deref().unwrap_or("never"),
+            "Policy scheduled"
+        );
+    }
+    tracing::info!(
+        "Daemon started for {} with {} policies (lock {})",
+        client.location(),
+        policies.len(),
+        lock.path.display()
+    );
+
+    loop {
+        let Some((index, at)) = next
+            .iter()
+            .enumerate()
+            .filter_map(|(i, at)| at.map(|at| (i, at)))
+            .min_by_key(|(_, at)| *at)
+        else {
+            tracing::info!("No policy has an upcoming run");
+            break;
+        };
+
+        let wait = (at - Utc::now()).to_std().unwrap_or_default();
+        tokio::select! {
+            _ = tokio::time::sleep(wait) => {}
+            _ = cancel.cancelled() => break,
+        }
+
+        let policy = &policies[index];
+        run_policy(client, cli, filter, reporter, policy, &metrics, &cancel).await;
+        if cancel.is_cancelled() {
+            break;
+        }
+
+        // Ticks that passed during the run are skipped rather than queued;
+        // policies due at the same time as this one still run next
+        let now = Utc::now();
+        next[index] = policy.next_after(&now);
+        for (p, slot) in policies.iter().zip(next.iter_mut()) {
+            if slot.is_some_and(|t| t > at && t < now) {
+                tracing::info!(
+                    event = "run_skipped",
+                    policy = %p.name,
+                    reason = "overlap",
+                    "Policy run skipped"
+                );
+                *slot = p.next_after(&now);
+            }
+        }
+    }
+
+    tracing::info!("Daemon stopped");
+    Ok(())
+}
+
+async fn run_policy(
+    client: &dyn RegistryBackend,
+    cli: &Cli,
+    filter: &RepoFilter,
+    reporter: &dyn Reporter,
+    policy: &Policy,
+    metrics: &Metrics,
+    cancel: &CancellationToken,
+) {
+    let started = Utc::now();
+    tracing::info!(event = "run_started", policy = %policy.name, "Policy run started");
+    client.clear_listings().await;
+
+    let result = async {
+        let repos = if policy.repos.is_empty() {
+            crate::select_repos(client, cli.repo.as_deref(), filter, reporter).await?
+        } else {
+            policy.repos.clone()
+        };
+        let report = crate::run_clean(client, &repos, &policy.args, None, reporter, cancel).await?;
+        crate::output::print_summary(&report);
+        Ok(report)
+    }
+    .instrument(tracing::info_span!("policy", name = %policy.name))
+    .await;
+
+    let duration = (Utc::now() - started).num_milliseconds() as f64 / 1000.0;
+    crate::notify::notify(
+        &policy.args.notify,
+        &client.location(),
+        policy.args.dry_run,
+        &result,
+    )
+    .await;
+    match result {
+        Ok(report) => {
+            metrics.record_clean(&report, duration);
+            tracing::info!(
+                event = "run_finished",
+                policy = %policy.name,
+                duration_secs = duration,
+                deleted_tags = report.deleted_tags,
+                deleted_digests = report.deleted_digests,
+                kept_tags = report.kept_tags,
+                deleted_bytes = report.deleted_bytes,
+                errors = report.errors,
+                dry_run = report.dry_run,
+                interrupted = report.interrupted,
+                "Policy run finished"
+            )
+        }
+        Err(e) => {
+            metrics.record_failure(duration);
+            tracing::error!(
+                event = "run_failed",
+                policy = %policy.name,
+                duration_secs = duration,
+                error = format!("{:#}", e),
+                "Policy run failed"
+            )
+        }
+    }
+
+    // Keep what this run resolved even if the daemon is killed before the next
+    if let Err(e) = client.save_cache() {
+        tracing::warn!("Failed to save metadata cache: {:#}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const POLICY: &str = r#"
+policies:
+  - name: dev
+    schedule: "30 3 * * *"
+    repos: [dev/app]
+    clean:
+      older_than: 14
+      dry_run: true
+  - name: ci
+    schedule: "0 */15 * * * *"
+    clean:
+      pattern: "^pr-\\d+$"
+"#;
+
+    fn load(text: &str) -> Result<Vec<Policy>> {
+        let file: PolicyFile = serde_yaml::from_str(text)?;
+        file.policies.into_iter().map(Policy::from_spec).collect()
+    }
+
+    #[test]
+    fn test_load_policies() {
+        let policies = load(POLICY).unwrap();
+        assert_eq!(policies.len(), 2);
+
+        let dev = &policies[0];
+        assert_eq!(dev.repos, vec!["dev/app"]);
+        assert_eq!(dev.args.older_than, Some(14));
+        assert!(dev.args.dry_run);
+
+        let at: DateTime<Utc> = "2024-05-01T04:00:00Z".parse().unwrap();
+        assert_eq!(
+            dev.next_after(&at).unwrap().to_rfc3339(),
+            "2024-05-02T03:30:00+00:00"
+        );
+        assert_eq!(
+            policies[1].next_after(&at).unwrap().to_rfc3339(),
+            "2024-05-01T04:15:00+00:00"
+        );
+        assert_eq!(policies[1].args.pattern.as_deref(), Some(r"^pr-\d+$"));
+    }
+
+    #[derive(Default)]
+    struct EmptyRegistry {
+        saved: AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl RegistryBackend for EmptyRegistry {
+        fn location(&self) -> String {
+            "empty".to_string()
+        }
+
+        async fn list_repositories(&self) -> Result<Vec<String>> {
+            Ok(Vec::new())
+        }
+
+        async fn list_tags(&self, _repo: &str) -> Result<Vec<String>> {
+            Ok(Vec::new())
+        }
+
+        async fn resolve_all_tags(&self, _repo: &str) -> Result<Vec<regtidy::TagInfo>> {
+            Ok(Vec::new())
+        }
+
+        async fn delete_manifest(&self, _repo: &str, _digest: &str) -> Result<u16> {
+            anyhow::bail!("nothing to delete")
+        }
+
+        fn save_cache(&self) -> Result<()> {
+            self.saved.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cache_saved_after_each_run() {
+        let policies = load(POLICY).unwrap();
+        let client = EmptyRegistry::default();
+        let cli = Cli::try_parse_from(["regtidy", "list"]).unwrap();
+        let filter = RepoFilter::new(&[], &[], None).unwrap();
+        let metrics = Metrics::default();
+        let cancel = CancellationToken::new();
+
+        for _ in 0..2 {
+            run_policy(
+                &client,
+                &cli,
+                &filter,
+                &regtidy::report::Silent,
+                &policies[0],
+                &metrics,
+                &cancel,
+            )
+            .await;
+        }
+        assert_eq!(client.saved.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_invalid_policies() {
+        let bad_cron = "policies:\n  - name: x\n    schedule: \"every day\"\n";
+        assert!(load(bad_cron).is_err());
+
+        let two_strategies = "policies:\n  - name: x\n    schedule: \"0 3 * * *\"\n    clean:\n      keep: 3\n      older_than: 7\n";
+        assert!(load(two_strategies).is_err());
+
+        let unknown = "policies:\n  - name: x\n    schedule: \"0 3 * * *\"\n    clean:\n      keep_forever: true\n";
+        assert!(load(unknown).is_err());
+    }
+
+    #[test]
+    fn test_lock_is_exclusive() {
+        let path = std::env::temp_dir().join(format!("regtidy-lock-{}", std::process::id()));
+        let lock = RunLock::acquire(&path).unwrap();
+        assert!(RunLock::acquire(&path).is_err());
+        drop(lock);
+        assert!(RunLock::acquire(&path).is_ok());
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,320 @@
+use anyhow::Result;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use crate::backend::{Auth, RegistryBackend};
+use crate::error::{http_error, AppError, ErrorKind};
+use crate::report::Reporter;
+
+/// Clock difference to the registry beyond which ages are reported as unreliable
+const MAX_CLOCK_SKEW_SECS: i64 = 60;
+
+/// Outcome of one preflight check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Ok,
+    Warn,
+    /// Deleting cannot work until this is fixed
+    Fail,
+}
+
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+    /// Class of a failed check, used for the exit code
+    pub kind: Option<ErrorKind>,
+}
+
+impl Check {
+    pub fn ok(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Ok,
+            detail: detail.into(),
+            kind: None,
+        }
+    }
+
+    pub fn warn(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Warn,
+            detail: detail.into(),
+            kind: None,
+        }
+    }
+
+    pub fn fail(name: &'static str, detail: impl Into<String>, kind: ErrorKind) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Fail,
+            detail: detail.into(),
+            kind: Some(kind),
+        }
+    }
+}
+
+/// The first failed check as an error, if any
+pub fn ensure(checks: &[Check]) -> Result<()> {
+    match checks.iter().find(|c| c.status == CheckStatus::Fail) {
+        Some(check) => Err(AppError::Preflight {
+            check: check.name.to_string(),
+            message: check.detail.clone(),
+            kind: check.kind.unwrap_or(ErrorKind::General),
+        }
+        .into()),
+        None => Ok(()),
+    }
+}
+
+/// Check the registry before deleting and abort if deletion cannot work.
+/// Warnings are reported as such, passing checks as debug detail.
+pub async fn preflight(
+    client: &dyn RegistryBackend,
+    repo: Option<&str>,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    let checks = client.diagnose(repo).await;
+    for check in &checks {
+        match check.status {
+            CheckStatus::Warn => {
+                reporter.warn(format!("Preflight {}: {}", check.name, check.detail))
+            }
+            CheckStatus::Ok => {
+                reporter.debug(format!("Preflight {}: {}", check.name, check.detail))
+            }
+            CheckStatus::Fail => {}
+        }
+    }
+    ensure(&checks)
+}
+
+/// Interpret the response to `GET /v2/`
+pub fn api_check(status: u16, api_version: Option<&str>, challenge: Option<&str>) -> Check {
+    match status {
+        200..=299 => match api_version {
+            Some("registry/2.0") => Check::ok("api", "Registry V2 API (registry/2.0)"),
+            Some(other) => Check::warn("api", format!("unexpected API version {}", other)),
+            None => Check::warn(
+                "api",
+                "no Docker-Distribution-API-Version header; the registry may not implement the V2 API fully",
+            ),
+        },
+        401 | 403 => {
+            let detail = match challenge {
+                Some(challenge) => format!(
+                    "authentication required (status {}, challenge: {}); check --username/--password or --token",
+                    status, challenge
+                ),
+                None => format!("credentials rejected (status {})", status),
+            };
+            Check::fail("api", detail, ErrorKind::Auth)
+        }
+        404 => Check::fail("api", "no Registry V2 API at /v2/ (status 404)", ErrorKind::NotFound),
+        _ => Check::fail(
+            "api",
+            format!("GET /v2/ returned status {}", status),
+            ErrorKind::RegistryStatus,
+        ),
+    }
+}
+
+/// Describe the credentials the registry accepted, including the scopes of a JWT bearer token
+pub fn auth_check(auth: &Auth) -> Check {
+    let token = match auth {
+        Auth::None => return Check::ok("auth", "anonymous access"),
+        Auth::Basic { username, .. } => {
+            return Check::ok(
+                "auth",
+                format!("basic credentials for {} accepted", username),
+            )
+        }
+        Auth::Bearer(token) => token,
+    };
+    let Some(claims) = token_claims(token) else {
+        return Check::ok("auth", "bearer token accepted");
+    };
+
+    if let Some(exp) = claims.exp.and_then(|exp| DateTime::from_timestamp(exp, 0)) {
+        if exp < Utc::now() {
+            return Check::warn(
+                "auth",
+                format!("bearer token expired at {}", exp.to_rfc3339()),
+            );
+        }
+    }
+    if claims.access.is_empty() {
+        return Check::ok("auth", "bearer token accepted (no scopes listed)");
+    }
+
+    let scopes: Vec<String> = claims
+        .access
+        .iter()
+        .map(|a| format!("{}:{}:{}", a.kind, a.name, a.actions.join(",")))
+        .collect();
+    let can_delete = claims.access.iter().any(|a| {
+        a.actions
+            .iter()
+            .any(|action| action == "delete" || action == "*")
+    });
+    if can_delete {
+        Check::ok("auth", format!("token scopes: {}", scopes.join(" ")))
+    } else {
+        Check::warn(
+            "auth",
+            format!("token has no delete scope: {}", scopes.join(" ")),
+        )
+    }
+}
+
+/// Interpret the outcome of deleting a manifest digest that cannot exist
+pub fn delete_check(repo: &str, result: &Result<()>) -> Check {
+    let err = match result {
+        Ok(()) => return Check::ok("delete", "deletion is enabled"),
+        Err(e) => e,
+    };
+    match ErrorKind::of(err) {
+        // The registry looked the manifest up, so it would have deleted a real one
+        ErrorKind::NotFound => Check::ok("delete", "deletion is enabled"),
+        ErrorKind::DeletionDisabled => Check::fail(
+            "delete",
+            format!(
+                "deletion is disabled ({}); set storage.delete.enabled: true in the registry config",
+                response_summary(err)
+            ),
+            ErrorKind::DeletionDisabled,
+        ),
+        ErrorKind::Auth => Check::fail(
+            "delete",
+            format!("credentials may not delete in {} ({})", repo, response_summary(err)),
+            ErrorKind::Auth,
+        ),
+        _ => Check::warn("delete", format!("deletion probe inconclusive: {:#}", err)),
+    }
+}
+
+/// Status and registry error codes of a failed request, e.g. `status 405 UNSUPPORTED`
+fn response_summary(err: &anyhow::Error) -> String {
+    let Some(http) = http_error(err) else {
+        return format!("{:#}", err);
+    };
+    let mut summary = format!("status {}", http.status);
+    for e in &http.errors {
+        summary.push(' ');
+        summary.push_str(&e.code);
+    }
+    summary
+}
+
+/// Compare the local clock with the registry's `Date` header
+pub fn clock_check(date: Option<&str>, now: DateTime<Utc>) -> Check {
+    let Some(server) = date.and_then(|d| DateTime::parse_from_rfc2822(d).ok()) else {
+        return Check::warn("clock", "no Date header; clock skew unknown");
+    };
+    let skew = (now - server.with_timezone(&Utc)).num_seconds();
+    if skew.abs() <= MAX_CLOCK_SKEW_SECS {
+        return Check::ok("clock", format!("in sync with the registry ({}s)", skew));
+    }
+    Check::warn(
+        "clock",
+        format!(
+            "local clock is {}s {} the registry; tag ages and token expiry may be off",
+            skew.abs(),
+            if skew > 0 { "ahead of" } else { "behind" }
+        ),
+    )
+}
+
+#[derive(Deserialize)]
+struct TokenClaims {
+    #[serde(default)]
+    access: Vec<TokenAccess>,
+    exp: Option<i64>,
+}
+
+/// One entry of the `access` claim of a registry token
+#[derive(Deserialize)]
+struct TokenAccess {
+    #[serde(rename = "type")]
+    kind: String,
+    name: String,
+    #[serde(default)]
+    actions: Vec<String>,
+}
+
+/// Claims of a JWT, read without verifying its signature
+fn token_claims(token: &str) -> Option<TokenClaims> {
+    let payload = token.split('.').nth(1)?;
+    let json = base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(payload.trim_end_matches('='))
+        .ok()?;
+    serde_json::from_slice(&json).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn jwt(claims: serde_json::Value) -> String {
+        let payload = base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .encode(serde_json::to_vec(&claims).unwrap());
+        format!("eyJhbGciOiJub25lIn0.{}.sig", payload)
+    }
+
+    #[test]
+    fn test_auth_check_scopes() {
+        let token = jwt(serde_json::json!({
+            "access": [{"type": "repository", "name": "app", "actions": ["pull", "delete"]}],
+        }));
+        let check = auth_check(&Auth::Bearer(token));
+        assert_eq!(check.status, CheckStatus::Ok);
+        assert_eq!(check.detail, "token scopes: repository:app:pull,delete");
+
+        let token = jwt(serde_json::json!({
+            "access": [{"type": "repository", "name": "app", "actions": ["pull"]}],
+        }));
+        assert_eq!(auth_check(&Auth::Bearer(token)).status, CheckStatus::Warn);
+
+        let token = jwt(serde_json::json!({ "exp": 1 }));
+        assert!(auth_check(&Auth::Bearer(token))
+            .detail
+            .starts_with("bearer token expired"));
+
+        let check = auth_check(&Auth::Bearer("opaque".to_string()));
+        assert_eq!(check.detail, "bearer token accepted");
+    }
+
+    #[test]
+    fn test_delete_check() {
+        let missing = Err(AppError::from_response("DELETE", 404, None, "").into());
+        assert_eq!(delete_check("app", &missing).status, CheckStatus::Ok);
+
+        let disabled = Err(AppError::from_response("DELETE", 405, None, "").into());
+        let check = delete_check("app", &disabled);
+        assert_eq!(check.kind, Some(ErrorKind::DeletionDisabled));
+        let err = ensure(&[check]).unwrap_err();
+        assert_eq!(ErrorKind::of(&err), ErrorKind::DeletionDisabled);
+
+        let unavailable = Err(AppError::from_response("DELETE", 503, None, "").into());
+        assert_eq!(delete_check("app", &unavailable).status, CheckStatus::Warn);
+    }
+
+    #[test]
+    fn test_clock_check() {
+        let now = DateTime::parse_from_rfc3339("2024-05-02T08:30:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let date = "Thu, 02 May 2024 08:29:30 GMT";
+        assert_eq!(clock_check(Some(date), now).status, CheckStatus::Ok);
+
+        let date = "Thu, 02 May 2024 08:20:00 GMT";
+        let check = clock_check(Some(date), now);
+        assert_eq!(check.status, CheckStatus::Warn);
+        assert!(check.detail.starts_with("local clock is 600s ahead of"));
+
+        assert_eq!(clock_check(None, now).status, CheckStatus::Warn);
+    }
+}
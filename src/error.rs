@@ -1,23 +1,335 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
-#[allow(dead_code)]
 pub enum AppError {
-    #[error("Registry API error: {0}")]
-    RegistryApi(String),
-
-    #[error("No cleanup strategy specified. Use --keep, --older-than, or --pattern")]
+    #[error("No cleanup strategy specified. Use --keep, --older-than, --pattern, or --unused-for")]
     NoStrategy,
 
+    #[error("Pull store error: {0}")]
+    PullStore(String),
+
     #[error("Invalid regex pattern: {0}")]
     InvalidPattern(#[from] regex::Error),
 
-    #[error("HTTP error: {0}")]
-    Http(#[from] reqwest::Error),
+    /// 401/403, or an `UNAUTHORIZED`/`DENIED` error code
+    #[error("Authentication failed: {0}")]
+    Auth(HttpError),
+
+    /// 404, or a `*_UNKNOWN` error code
+    #[error("Not found: {0}")]
+    NotFound(HttpError),
+
+    /// 405, or an `UNSUPPORTED` error code: the registry does not allow deletes
+    #[error("Deletion is disabled on the registry: {0}")]
+    DeletionDisabled(HttpError),
+
+    /// 429, or a `TOOMANYREQUESTS` error code
+    #[error("Rate limited: {0}")]
+    RateLimited(HttpError),
+
+    /// Any other unsuccessful status
+    #[error("{0}")]
+    Status(HttpError),
+
+    #[error("Safety limits exceeded, nothing was deleted:\n  - {0}")]
+    LimitExceeded(String),
+
+    #[error("Cleanup not confirmed, nothing was deleted")]
+    NotConfirmed,
+
+    /// A preflight check found that deleting cannot work
+    #[error("Preflight check '{check}' failed: {message}")]
+    Preflight {
+        check: String,
+        message: String,
+        kind: ErrorKind,
+    },
+
+    /// A backend that deletes tag by tag removed some tags of a digest
+    /// before failing on the others
+    #[error("Failed to delete tags {}, deleted {}", failed.join(", "), deleted.join(", "))]
+    PartialDelete {
+        deleted: Vec<String>,
+        failed: Vec<String>,
+        /// HTTP status of the successful deletions
+        status: u16,
+        #[source]
+        source: anyhow::Error,
+    },
+
+    /// Several failures summarized in one error; `kind` is set when they all
+    /// share the same class
+    #[error("{message}")]
+    Failures {
+        message: String,
+        kind: Option<ErrorKind>,
+    },
+}
+
+impl AppError {
+    /// Classify an unsuccessful registry response by its error codes, then its status
+    pub fn from_response(what: &str, status: u16, retry_after: Option<u64>, body: &str) -> Self {
+        let body = body.trim();
+        let errors = serde_json::from_str::<ErrorBody>(body)
+            .map(|b| b.errors)
+            .unwrap_or_default();
+        let code = errors.first().map(|e| e.code.clone()).unwrap_or_default();
+        let err = HttpError {
+            what: what.to_string(),
+            status,
+            retry_after,
+            body: body.to_string(),
+            errors,
+        };
+        match (code.as_str(), status) {
+            ("UNAUTHORIZED" | "DENIED", _) | (_, 401 | 403) => AppError::Auth(err),
+            ("UNSUPPORTED", _) | (_, 405) => AppError::DeletionDisabled(err),
+            ("TOOMANYREQUESTS", _) | (_, 429) => AppError::RateLimited(err),
+            (code, _) if code.ends_with("_UNKNOWN") => AppError::NotFound(err),
+            (_, 404) => AppError::NotFound(err),
+            _ => AppError::Status(err),
+        }
+    }
+
+    /// The failed HTTP exchange behind this error, if any
+    pub fn http(&self) -> Option<&HttpError> {
+        match self {
+            AppError::Auth(e)
+            | AppError::NotFound(e)
+            | AppError::DeletionDisabled(e)
+            | AppError::RateLimited(e)
+            | AppError::Status(e) => Some(e),
+            _ => None,
+        }
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            AppError::Auth(_) => ErrorKind::Auth,
+            AppError::NotFound(_) => ErrorKind::NotFound,
+            AppError::DeletionDisabled(_) => ErrorKind::DeletionDisabled,
+            AppError::RateLimited(_) => ErrorKind::RateLimited,
+            AppError::Status(_) => ErrorKind::RegistryStatus,
+            AppError::LimitExceeded(_) => ErrorKind::LimitExceeded,
+            AppError::NotConfirmed => ErrorKind::NotConfirmed,
+            AppError::Preflight { kind, .. } => *kind,
+            AppError::Failures { kind, .. } => kind.unwrap_or(ErrorKind::General),
+            AppError::PartialDelete { source, .. } => ErrorKind::of(source),
+            AppError::NoStrategy | AppError::PullStore(_) | AppError::InvalidPattern(_) => {
+                ErrorKind::General
+            }
+        }
+    }
+}
+
+/// A registry response with an unsuccessful status
+#[derive(Debug, Clone)]
+pub struct HttpError {
+    /// The request, e.g. `DELETE manifest sha256:… for app`
+    pub what: String,
+    pub status: u16,
+    /// Seconds from the `Retry-After` header
+    pub retry_after: Option<u64>,
+    pub body: String,
+    /// Entries of the Registry V2 JSON error body
+    pub errors: Vec<RegistryError>,
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} returned status {}: ", self.what, self.status)?;
+        if self.errors.is_empty() {
+            return write!(f, "{}", self.body);
+        }
+        for (i, e) in self.errors.iter().enumerate() {
+            if i > 0 {
+                write!(f, "; ")?;
+            }
+            write!(f, "{}", e.code)?;
+            if !e.message.is_empty() {
+                write!(f, " ({})", e.message)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// One entry of the `{"errors": [...]}` body defined by the Registry V2 API
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RegistryError {
+    pub code: String,
+    #[serde(default)]
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct ErrorBody {
+    errors: Vec<RegistryError>,
+}
+
+/// Failure class of a run, mapped to the process exit code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    General,
+    Auth,
+    NotFound,
+    DeletionDisabled,
+    RateLimited,
+    Network,
+    Parse,
+    RegistryStatus,
+    LimitExceeded,
+    NotConfirmed,
+}
+
+impl ErrorKind {
+    /// Exit code; 2 is left to usage errors reported by clap
+    pub fn exit_code(self) -> i32 {
+        match self {
+            ErrorKind::General => 1,
+            ErrorKind::Auth => 3,
+            ErrorKind::NotFound => 4,
+            ErrorKind::DeletionDisabled => 5,
+            ErrorKind::RateLimited => 6,
+            ErrorKind::Network => 7,
+            ErrorKind::Parse => 8,
+            ErrorKind::RegistryStatus => 9,
+            ErrorKind::LimitExceeded => 10,
+            ErrorKind::NotConfirmed => 11,
+        }
+    }
+
+    /// The class of `err`, from the outermost cause that has one
+    pub fn of(err: &anyhow::Error) -> Self {
+        err.chain()
+            .find_map(|cause| {
+                if let Some(e) = cause.downcast_ref::<AppError>() {
+                    return Some(e.kind());
+                }
+                if let Some(e) = cause.downcast_ref::<reqwest::Error>() {
+                    return Some(if e.is_decode() {
+                        ErrorKind::Parse
+                    } else {
+                        ErrorKind::Network
+                    });
+                }
+                cause
+                    .downcast_ref::<serde_json::Error>()
+                    .map(|_| ErrorKind::Parse)
+            })
+            .unwrap_or(ErrorKind::General)
+    }
+
+    /// The shared class of several failures, if they all have the same one
+    pub fn common(kinds: impl IntoIterator<Item = ErrorKind>) -> Option<Self> {
+        let mut kinds = kinds.into_iter();
+        let first = kinds.next()?;
+        kinds.all(|k| k == first).then_some(first)
+    }
+}
+
+/// Machine-readable form of the error that ended the run (`--error-format json`)
+#[derive(Debug, Serialize)]
+pub struct ErrorRecord {
+    pub kind: ErrorKind,
+    pub exit_code: i32,
+    pub message: String,
+    pub http_status: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_after: Option<u64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub registry_errors: Vec<RegistryError>,
+}
+
+impl ErrorRecord {
+    pub fn new(err: &anyhow::Error) -> Self {
+        let kind = ErrorKind::of(err);
+        let http = http_error(err);
+        Self {
+            kind,
+            exit_code: kind.exit_code(),
+            message: format!("{:#}", err),
+            http_status: http.map(|h| h.status),
+            retry_after: http.and_then(|h| h.retry_after),
+            registry_errors: http.map(|h| h.errors.clone()).unwrap_or_default(),
+        }
+    }
+}
+
+/// The failed HTTP exchange anywhere in the error chain
+pub fn http_error(err: &anyhow::Error) -> Option<&HttpError> {
+    err.chain()
+        .find_map(|cause| cause.downcast_ref::<AppError>().and_then(AppError::http))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_from_response() {
+        let body =
+            r#"{"errors":[{"code":"UNSUPPORTED","message":"The operation is unsupported."}]}"#;
+        let err = AppError::from_response("DELETE manifest sha256:a for app", 405, None, body);
+        assert_eq!(err.kind(), ErrorKind::DeletionDisabled);
+        assert_eq!(
+            err.to_string(),
+            "Deletion is disabled on the registry: DELETE manifest sha256:a for app \
+             returned status 405: UNSUPPORTED (The operation is unsupported.)"
+        );
+
+        let body = r#"{"errors":[{"code":"MANIFEST_UNKNOWN","message":"manifest unknown","detail":{"Tag":"v1"}}]}"#;
+        let err = AppError::from_response("GET manifest", 404, None, body);
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        assert_eq!(
+            err.http().unwrap().errors[0].detail,
+            Some(serde_json::json!({"Tag": "v1"}))
+        );
+
+        let err = AppError::from_response("GET /v2/_catalog", 429, Some(30), "slow down");
+        assert_eq!(err.kind(), ErrorKind::RateLimited);
+        assert!(err.to_string().ends_with("returned status 429: slow down"));
+
+        assert_eq!(
+            AppError::from_response("GET", 403, None, "").kind(),
+            ErrorKind::Auth
+        );
+        assert_eq!(
+            AppError::from_response("GET", 503, None, "").kind(),
+            ErrorKind::RegistryStatus
+        );
+    }
+
+    #[test]
+    fn test_error_kind_of() {
+        let err = Err::<(), _>(AppError::from_response("DELETE", 401, None, ""))
+            .context("Failed to delete app")
+            .unwrap_err();
+        assert_eq!(ErrorKind::of(&err), ErrorKind::Auth);
+        assert_eq!(ErrorRecord::new(&err).exit_code, 3);
+        assert_eq!(ErrorRecord::new(&err).http_status, Some(401));
 
-    #[error("JSON parse error: {0}")]
-    Json(#[from] serde_json::Error),
+        let err = serde_json::from_str::<Vec<String>>("{")
+            .context("Failed to parse catalog")
+            .unwrap_err();
+        assert_eq!(ErrorKind::of(&err), ErrorKind::Parse);
+        assert_eq!(ErrorKind::of(&anyhow::anyhow!("boom")), ErrorKind::General);
 
-    #[error("Date parse error: {0}")]
-    DateParse(#[from] chrono::ParseError),
+        assert_eq!(
+            ErrorKind::common([ErrorKind::Network, ErrorKind::Network]),
+            Some(ErrorKind::Network)
+        );
+        assert_eq!(
+            ErrorKind::common([ErrorKind::Network, ErrorKind::Auth]),
+            None
+        );
+        assert_eq!(ErrorKind::common([]), None);
+    }
 }
@@ -0,0 +1,717 @@
+use std::collections::{BTreeMap, HashSet};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use tokio_util::sync::CancellationToken;
+
+use crate::backend::RegistryBackend;
+use crate::doctor;
+use crate::error::{AppError, ErrorKind};
+use crate::index::DigestIndex;
+use crate::limits::Limits;
+use crate::models::{CleanReport, CleanupFailure, CleanupPlan, TagInfo};
+use crate::report::{Event, Reporter};
+use crate::strategy::{self, Strategy};
+use crate::untagged::{self, UntaggedPlan};
+
+/// Tags of the repositories of a run, resolved before anything is planned
+#[derive(Debug, Default)]
+pub struct Resolved {
+    /// Repositories with at least one tag, in the order they were given
+    pub repositories: Vec<(String, Vec<TagInfo>)>,
+    /// Every digest of the resolved repositories
+    pub index: DigestIndex,
+    /// Repositories whose tags could not be resolved
+    pub failures: Vec<CleanupFailure>,
+}
+
+/// What happened to one digest of a plan
+#[derive(Debug, Clone, Copy)]
+pub enum Outcome<'a> {
+    /// Dry run: the digest would have been deleted
+    Planned,
+    /// Deleted, with the HTTP status of the response
+    Deleted(u16),
+    Failed(&'a anyhow::Error),
+}
+
+/// Settings of [`Executor::clean`] beyond those of the executor itself
+#[derive(Debug, Default)]
+pub struct CleanOptions {
+    /// Check that the registry accepts deletions before deleting anything
+    pub preflight: bool,
+    /// Keep a digest in every repository while any catalog repository keeps it.
+    /// The whole catalog is indexed for the reclaimable bytes either way.
+    pub protect_across_repos: bool,
+    /// Abort a real run that would delete more; a dry run only warns
+    pub limits: Limits,
+}
+
+/// Points at which the caller of [`Executor::clean`] takes part in the run.
+/// Every method has a default that lets the run go ahead unattended.
+#[async_trait]
+pub trait CleanHooks: Send {
+    /// See the final plans before the limits are checked, e.g. to print or
+    /// edit them; `None` cancels the run without deleting anything
+    fn review(
+        &mut self,
+        plans: Vec<CleanupPlan>,
+        _index: &DigestIndex,
+    ) -> Result<Option<Vec<CleanupPlan>>> {
+        Ok(Some(plans))
+    }
+
+    /// Whether to go ahead; only asked by real runs that delete something
+    async fn confirm(&mut self, _plans: &[CleanupPlan]) -> Result<bool> {
+        Ok(true)
+    }
+
+    /// The outcome of each digest, e.g. for an audit log; an error aborts the run
+    fn record(
+        &mut self,
+        _repo: &str,
+        _digest: &str,
+        _tags: &[String],
+        _outcome: Outcome,
+    ) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Hooks that let a run go ahead without asking or recording anything
+pub struct Unattended;
+
+impl CleanHooks for Unattended {}
+
+/// Resolves repositories and carries out cleanup plans against a backend.
+///
+/// ```no_run
+/// # async fn example() -> anyhow::Result<()> {
+/// use std::sync::Arc;
+/// use regtidy::report::Silent;
+/// use regtidy::{Executor, RegistryClient, Strategy};
+///
+/// let client = RegistryClient::new("http://localhost:5000", Arc::new(Silent));
+/// let executor = Executor::new(&client, &Silent).dry_run(true);
+/// let resolved = executor.resolve(&["app".to_string()]).await;
+/// let strategy = Strategy::KeepRecent(5);
+/// let plans = resolved
+///     .repositories
+///     .into_iter()
+///     .map(|(repo, tags)| strategy.apply(&repo, tags, &Silent))
+///     .collect();
+/// let report = executor.execute(plans, &resolved.index, |_, _, _, _| Ok(())).await?;
+/// println!("would delete {} tags", report.deleted_tags);
+/// # Ok(())
+/// # }
+/// ```
+pub struct Executor<'a> {
+    backend: &'a dyn RegistryBackend,
+    reporter: &'a dyn Reporter,
+    dry_run: bool,
+    cancel: CancellationToken,
+}
+
+impl<'a> Executor<'a> {
+    pub fn new(backend: &'a dyn RegistryBackend, reporter: &'a dyn Reporter) -> Self {
+        Self {
+            backend,
+            reporter,
+            dry_run: false,
+            cancel: CancellationToken::new(),
+        }
+    }
+
+    /// Only report what would be deleted
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Start no new work once `cancel` is cancelled; in-flight requests finish
+    pub fn with_cancel(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// Resolve the tags of every repository. A repository that fails is
+    /// recorded in [`Resolved::failures`] and does not stop the others.
+    pub async fn resolve(&self, repos: &[String]) -> Resolved {
+        let mut resolved = Resolved::default();
+        for repo in repos {
+            if self.cancel.is_cancelled() {
+                break;
+            }
+            self.reporter.repository_started(repo);
+
+            let result = self.backend.resolve_all_tags(repo).await;
+            self.reporter
+                .repository_resolved(repo, result.as_ref().map_or(0, Vec::len));
+            let tags = match result {
+                Ok(tags) => tags,
+                Err(e) => {
+                    self.reporter
+                        .error(format!("Failed to resolve tags for {}: {}", repo, e));
+                    resolved.failures.push(CleanupFailure {
+                        repository: Some(repo.clone()),
+                        digest: None,
+                        tags: Vec::new(),
+                        kind: ErrorKind::of(&e),
+                        error: format!("{:#}", e),
+                    });
+                    continue;
+                }
+            };
+            if tags.is_empty() {
+                self.reporter.debug(format!("No tags found for {}", repo));
+                continue;
+            }
+
+            resolved.index.add(&tags);
+            resolved.repositories.push((repo.clone(), tags));
+        }
+        resolved
+    }
+
+    /// Add the tags of every catalog repository not in `selected` to `index`,
+    /// so digests they still reference count as in use
+    pub async fn index_others(&self, selected: &[String], index: &mut DigestIndex) -> Result<()> {
+        let selected: HashSet<&String> = selected.iter().collect();
+        for repo in self.backend.list_repositories().await? {
+            if selected.contains(&repo) || self.cancel.is_cancelled() {
+                continue;
+            }
+            self.reporter
+                .debug(format!("Indexing repository: {}", repo));
+            let tags = self
+                .backend
+                .resolve_all_tags(&repo)
+                .await
+                .with_context(|| format!("Failed to index tags of {}", repo))?;
+            index.add(&tags);
+        }
+        Ok(())
+    }
+
+    /// Run a whole cleanup: resolve the repositories, plan with `strategy`,
+    /// protect shared digests, check the limits and delete what is left.
+    /// Repositories that cannot be resolved are recorded in the report's
+    /// failures ahead of failed deletions.
+    pub async fn clean(
+        &self,
+        repos: &[String],
+        strategy: &Strategy,
+        options: &CleanOptions,
+        hooks: &mut dyn CleanHooks,
+    ) -> Result<CleanReport> {
+        if !self.dry_run && !self.backend.can_delete() {
+            anyhow::bail!(
+                "{} does not support deletion; use --dry-run or pass --registry",
+                self.backend.location()
+            );
+        }
+
+        if !self.dry_run && options.preflight {
+            doctor::preflight(
+                self.backend,
+                repos.first().map(String::as_str),
+                self.reporter,
+            )
+            .await?;
+        }
+
+        self.reporter.debug(format!("Strategy: {:?}", strategy));
+        self.reporter.debug(format!("Dry run: {}", self.dry_run));
+
+        // Resolve every repository first, so the digest index covers them all before planning
+        let Resolved {
+            repositories,
+            mut index,
+            mut failures,
+        } = self.resolve(repos).await;
+
+        // Repositories outside this run still reference their digests and
+        // blobs: they always count for the reclaimable bytes, but only
+        // protect digests with `protect_across_repos`
+        match self.index_others(repos, &mut index).await {
+            Ok(()) => {}
+            Err(e) if options.protect_across_repos => return Err(e),
+            Err(e) => self.reporter.warn(format!(
+                "Could not index other repositories, deleted bytes may include shared blobs: {:#}",
+                e
+            )),
+        }
+
+        let mut plans: Vec<CleanupPlan> = repositories
+            .into_iter()
+            .map(|(repo, tags)| strategy.apply(&repo, tags, self.reporter))
+            .collect();
+        if options.protect_across_repos {
+            strategy::protect_across_repos(&mut plans, &index, self.reporter);
+        }
+        let plans = hooks.review(plans, &index)?.unwrap_or_default();
+        // Only now are the plans final: protection may have moved tags to `to_keep`
+        for plan in &plans {
+            self.reporter.plan_ready(plan);
+        }
+
+        let violations = options.limits.check(&plans);
+        if !violations.is_empty() {
+            if !self.dry_run {
+                return Err(AppError::LimitExceeded(violations.join("\n  - ")).into());
+            }
+            for violation in &violations {
+                self.reporter
+                    .warn(format!("Safety limit exceeded: {}", violation));
+            }
+        }
+
+        let deletes = plans.iter().any(|p| !p.to_delete.is_empty());
+        if !self.dry_run && deletes && !hooks.confirm(&plans).await? {
+            return Err(AppError::NotConfirmed.into());
+        }
+
+        let mut report = self
+            .execute(plans, &index, |repo, digest, tags, outcome| {
+                hooks.record(repo, digest, tags, outcome)
+            })
+            .await?;
+
+        failures.append(&mut report.failures);
+        report.errors = failures.len();
+        report.failures = failures;
+        Ok(report)
+    }
+
+    /// Delete the tags of every plan, one request per digest, and total the run.
+    ///
+    /// `on_digest` sees the outcome of each digest with the tags that pointed
+    /// at it, e.g. to keep an audit log; an error from it aborts the run.
+    /// Failed deletions are recorded in the report and do not stop the others.
+    pub async fn execute<F>(
+        &self,
+        plans: Vec<CleanupPlan>,
+        index: &DigestIndex,
+        mut on_digest: F,
+    ) -> Result<CleanReport>
+    where
+        F: FnMut(&str, &str, &[String], Outcome) -> Result<()>,
+    {
+        let mut total_deleted: usize = 0;
+        let mut total_kept: usize = 0;
+        let mut all_deleted_digests: HashSet<String> = HashSet::new();
+        let mut repository_tags: BTreeMap<String, usize> = BTreeMap::new();
+        let registry = self.backend.location();
+        let mut failures: Vec<CleanupFailure> = Vec::new();
+        let mut done: Vec<CleanupPlan> = Vec::new();
+        let mut deleted: Vec<TagInfo> = Vec::new();
+
+        if !self.dry_run {
+            let digests: HashSet<&str> = plans
+                .iter()
+                .flat_map(|p| p.to_delete.iter().map(|t| t.digest.as_str()))
+                .collect();
+            self.reporter.report(Event::DeletionStarted {
+                digests: digests.len(),
+            });
+        }
+
+        for plan in plans {
+            if self.cancel.is_cancelled() {
+                break;
+            }
+            let repo = &plan.repository;
+            let tag_count = plan.to_delete.len() + plan.to_keep.len();
+
+            total_kept += plan.to_keep.len();
+
+            // Group tags by digest so each digest is deleted once
+            let mut digests_to_delete: Vec<(String, Vec<String>)> = Vec::new();
+
+            for tag in &plan.to_delete {
+                match digests_to_delete.iter_mut().find(|(d, _)| *d == tag.digest) {
+                    Some((_, tags)) => tags.push(tag.tag.clone()),
+                    None => digests_to_delete.push((tag.digest.clone(), vec![tag.tag.clone()])),
+                }
+            }
+
+            if self.dry_run {
+                for (digest, tags) in &digests_to_delete {
+                    on_digest(repo, digest, tags, Outcome::Planned)?;
+                }
+                total_deleted += plan.to_delete.len();
+                for tag in &plan.to_delete {
+                    all_deleted_digests.insert(tag.digest.clone());
+                }
+                repository_tags.insert(repo.clone(), tag_count);
+                deleted.extend(plan.to_delete.iter().cloned());
+            } else {
+                let mut deleted_digests_this_repo: HashSet<String> = HashSet::new();
+                let mut deleted_tags_this_repo: HashSet<String> = HashSet::new();
+
+                for (digest, tags) in &digests_to_delete {
+                    // Let an in-flight DELETE finish, but start no new ones after shutdown
+                    if self.cancel.is_cancelled() {
+                        self.reporter
+                            .warn("Shutdown requested, skipping remaining deletions");
+                        break;
+                    }
+                    let result = self.backend.delete_tags(repo, digest, tags).await;
+                    // The digest stays while any of its tags does
+                    let (tags, partial) = match &result {
+                        Err(e) => match e.downcast_ref::<AppError>() {
+                            Some(AppError::PartialDelete {
+                                deleted,
+                                failed,
+                                status,
+                                ..
+                            }) => (failed, Some((deleted, *status))),
+                            _ => (tags, None),
+                        },
+                        Ok(_) => (tags, None),
+                    };
+                    if let Some((partial, status)) = partial {
+                        on_digest(repo, digest, partial, Outcome::Deleted(status))?;
+                        deleted_tags_this_repo.extend(partial.iter().cloned());
+                    }
+                    let outcome = match &result {
+                        Ok(status) => Outcome::Deleted(*status),
+                        Err(e) => Outcome::Failed(e),
+                    };
+                    on_digest(repo, digest, tags, outcome)?;
+                    match &result {
+                        Ok(_) => {
+                            self.reporter.digest_deleted(repo, digest, tags);
+                            all_deleted_digests.insert(digest.clone());
+                            deleted_digests_this_repo.insert(digest.clone());
+                        }
+                        Err(e) => {
+                            self.reporter
+                                .error(format!("Failed to delete digest {}: {}", digest, e));
+                            failures.push(CleanupFailure {
+                                repository: Some(repo.clone()),
+                                digest: Some(digest.clone()),
+                                tags: tags.clone(),
+                                kind: ErrorKind::of(e),
+                                error: format!("{:#}", e),
+                            });
+                        }
+                    }
+                }
+
+                // Only count tags whose digests (or themselves) were actually deleted
+                let mut deleted_this_repo = 0;
+                for tag in &plan.to_delete {
+                    if deleted_digests_this_repo.contains(&tag.digest)
+                        || deleted_tags_this_repo.contains(&tag.tag)
+                    {
+                        deleted_this_repo += 1;
+                        deleted.push(tag.clone());
+                    }
+                }
+                total_deleted += deleted_this_repo;
+                repository_tags.insert(repo.clone(), tag_count - deleted_this_repo);
+            }
+
+            done.push(plan);
+        }
+
+        if !self.dry_run {
+            self.reporter.report(Event::DeletionFinished {
+                deleted: all_deleted_digests.len(),
+                failed: failures.len(),
+            });
+        }
+
+        Ok(CleanReport {
+            deleted_tags: total_deleted,
+            deleted_digests: all_deleted_digests.len(),
+            kept_tags: total_kept,
+            errors: failures.len(),
+            // Digests still tagged in another repository free nothing
+            deleted_bytes: index.reclaimable_bytes(&deleted),
+            gc_reclaimed_bytes: None,
+            repository_tags: BTreeMap::from([(registry, repository_tags)]),
+            dry_run: self.dry_run,
+            interrupted: self.cancel.is_cancelled(),
+            failures,
+            plans: done,
+            deleted,
+            gc: None,
+        })
+    }
+
+    /// Find the untagged manifests of every repository. A repository whose
+    /// manifests cannot be listed is recorded in the returned failures.
+    pub async fn plan_untagged(
+        &self,
+        repos: &[String],
+    ) -> (Vec<UntaggedPlan>, Vec<CleanupFailure>) {
+        let mut plans = Vec::new();
+        let mut failures = Vec::new();
+        for repo in repos {
+            if self.cancel.is_cancelled() {
+                break;
+            }
+            self.reporter
+                .debug(format!("Scanning manifests in repository: {}", repo));
+            match self.backend.list_revisions(repo).await {
+                Ok(revisions) => plans.push(untagged::plan_untagged(repo, revisions)),
+                Err(e) => {
+                    self.reporter
+                        .error(format!("Failed to list manifests for {}: {:#}", repo, e));
+                    failures.push(CleanupFailure {
+                        repository: Some(repo.clone()),
+                        digest: None,
+                        tags: Vec::new(),
+                        kind: ErrorKind::of(&e),
+                        error: format!("{:#}", e),
+                    });
+                }
+            }
+        }
+        (plans, failures)
+    }
+
+    /// Delete the untagged manifests of every plan, in the plan's order.
+    /// Failed deletions are recorded in the report and do not stop the others.
+    pub async fn execute_untagged(&self, plans: &[UntaggedPlan]) -> CleanReport {
+        let mut report = CleanReport {
+            dry_run: self.dry_run,
+            ..CleanReport::default()
+        };
+        if self.dry_run {
+            report.deleted_digests = plans.iter().map(|p| p.to_delete.len()).sum();
+            return report;
+        }
+
+        self.reporter.report(Event::DeletionStarted {
+            digests: plans.iter().map(|p| p.to_delete.len()).sum(),
+        });
+        'plans: for plan in plans {
+            let repo = &plan.repository;
+            for rev in &plan.to_delete {
+                if self.cancel.is_cancelled() {
+                    self.reporter
+                        .warn("Shutdown requested, skipping remaining deletions");
+                    break 'plans;
+                }
+                match self.backend.delete_manifest(repo, &rev.digest).await {
+                    Ok(_) => {
+                        self.reporter.digest_deleted(repo, &rev.digest, &[]);
+                        report.deleted_digests += 1;
+                    }
+                    Err(e) => {
+                        self.reporter
+                            .error(format!("Failed to delete digest {}: {}", rev.digest, e));
+                        report.failures.push(CleanupFailure {
+                            repository: Some(repo.clone()),
+                            digest: Some(rev.digest.clone()),
+                            tags: Vec::new(),
+                            kind: ErrorKind::of(&e),
+                            error: format!("{:#}", e),
+                        });
+                    }
+                }
+            }
+        }
+        self.reporter.report(Event::DeletionFinished {
+            deleted: report.deleted_digests,
+            failed: report.failures.len(),
+        });
+
+        report.errors = report.failures.len();
+        report.interrupted = self.cancel.is_cancelled();
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::testing::serve;
+    use crate::registry::RegistryClient;
+    use crate::report::testing::Recorder;
+    use crate::report::Silent;
+    use axum::extract::Path;
+    use axum::http::StatusCode;
+    use axum::routing::delete;
+    use axum::Router;
+    use std::sync::{Arc, Mutex};
+
+    fn tag(name: &str, digest: &str) -> TagInfo {
+        TagInfo {
+            repository: "app".to_string(),
+            tag: name.to_string(),
+            digest: digest.to_string(),
+            created: None,
+            size: Some(100),
+            pushed: None,
+            last_pulled: None,
+            blobs: Vec::new(),
+        }
+    }
+
+    fn plan() -> CleanupPlan {
+        CleanupPlan {
+            repository: "app".to_string(),
+            to_delete: vec![
+                tag("v1", "sha256:a"),
+                tag("v1-alias", "sha256:a"),
+                tag("v2", "sha256:b"),
+            ],
+            to_keep: vec![tag("v3", "sha256:c")],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_execute() {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let seen = requests.clone();
+        let router = Router::new().route(
+            "/v2/app/manifests/{digest}",
+            delete(move |Path(digest): Path<String>| async move {
+                seen.lock().unwrap().push(digest.clone());
+                match digest.as_str() {
+                    "sha256:a" => StatusCode::ACCEPTED,
+                    _ => StatusCode::METHOD_NOT_ALLOWED,
+                }
+            }),
+        );
+        let client = RegistryClient::new(&serve(router).await, Arc::new(Silent));
+
+        let mut index = DigestIndex::default();
+        let plan = plan();
+        index.add(&plan.to_delete);
+        index.add(&plan.to_keep);
+
+        let mut outcomes = Vec::new();
+        let recorder = Recorder::default();
+        let report = Executor::new(&client, &recorder)
+            .execute(vec![plan], &index, |_, digest, tags, outcome| {
+                outcomes.push((
+                    digest.to_string(),
+                    tags.len(),
+                    matches!(outcome, Outcome::Deleted(202)),
+                ));
+                Ok(())
+            })
+            .await
+            .unwrap();
+
+        // Each digest is deleted once, however many tags point at it
+        assert_eq!(*requests.lock().unwrap(), ["sha256:a", "sha256:b"]);
+        assert_eq!(
+            outcomes,
+            [
+                ("sha256:a".to_string(), 2, true),
+                ("sha256:b".to_string(), 1, false)
+            ]
+        );
+        assert_eq!(report.deleted_tags, 2);
+        assert_eq!(report.deleted_digests, 1);
+        assert_eq!(report.kept_tags, 1);
+        assert_eq!(report.errors, 1);
+        assert_eq!(report.failures[0].kind, ErrorKind::DeletionDisabled);
+        assert_eq!(report.repository_tags[&client.location()]["app"], 2);
+        assert_eq!(
+            recorder.events(),
+            [
+                "deletion_started",
+                "digest_deleted",
+                "error",
+                "deletion_finished"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_execute_partial_tag_delete() {
+        use crate::backend::{Auth, GitLabBackend, Tls};
+        use axum::routing::get;
+        use axum::Json;
+
+        let router = Router::new()
+            .route(
+                "/api/v4/projects/{project}/registry/repositories",
+                get(|| async { Json(serde_json::json!([{"id": 7, "path": "app"}])) }),
+            )
+            .route(
+                "/api/v4/projects/{project}/registry/repositories/{id}/tags/{tag}",
+                delete(
+                    |Path((_, _, tag)): Path<(String, u64, String)>| async move {
+                        match tag.as_str() {
+                            "v1-alias" => StatusCode::INTERNAL_SERVER_ERROR,
+                            _ => StatusCode::OK,
+                        }
+                    },
+                ),
+            );
+        let url = serve(router).await;
+        let backend = GitLabBackend::new(
+            &url,
+            "group",
+            &Auth::None,
+            &Tls::default(),
+            Arc::new(Silent),
+        )
+        .unwrap();
+
+        let mut outcomes = Vec::new();
+        let report = Executor::new(&backend, &Silent)
+            .execute(
+                vec![plan()],
+                &DigestIndex::default(),
+                |_, digest, tags, outcome| {
+                    outcomes.push((
+                        digest.to_string(),
+                        tags.to_vec(),
+                        matches!(outcome, Outcome::Deleted(200)),
+                    ));
+                    Ok(())
+                },
+            )
+            .await
+            .unwrap();
+
+        // v1 is gone even though sha256:a is still tagged v1-alias
+        assert_eq!(
+            outcomes,
+            [
+                ("sha256:a".to_string(), vec!["v1".to_string()], true),
+                ("sha256:a".to_string(), vec!["v1-alias".to_string()], false),
+                ("sha256:b".to_string(), vec!["v2".to_string()], true),
+            ]
+        );
+        let deleted: Vec<&str> = report.deleted.iter().map(|t| t.tag.as_str()).collect();
+        assert_eq!(deleted, ["v1", "v2"]);
+        assert_eq!(report.deleted_tags, 2);
+        assert_eq!(report.deleted_digests, 1);
+        assert_eq!(report.failures[0].tags, ["v1-alias"]);
+        assert_eq!(report.failures[0].kind, ErrorKind::RegistryStatus);
+    }
+
+    #[tokio::test]
+    async fn test_execute_dry_run() {
+        // Nothing listens here; a dry run must not send any request
+        let client = RegistryClient::new("http://127.0.0.1:9", Arc::new(Silent));
+        let index = DigestIndex::default();
+
+        let mut planned = 0;
+        let report = Executor::new(&client, &Silent)
+            .dry_run(true)
+            .execute(vec![plan()], &index, |_, _, _, outcome| {
+                assert!(matches!(outcome, Outcome::Planned));
+                planned += 1;
+                Ok(())
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(planned, 2);
+        assert_eq!(report.deleted_tags, 3);
+        assert_eq!(report.deleted_digests, 2);
+        assert_eq!(report.repository_tags["http://127.0.0.1:9"]["app"], 4);
+        assert!(report.dry_run);
+    }
+}
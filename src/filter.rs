@@ -0,0 +1,194 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use regex::Regex;
+
+use crate::report::Reporter;
+
+/// A repository name pattern: a glob, or a regex when prefixed with `re:`
+#[derive(Debug)]
+struct Pattern {
+    source: String,
+    regex: Regex,
+}
+
+impl Pattern {
+    fn parse(source: &str) -> Result<Self> {
+        let regex = match source.strip_prefix("re:") {
+            Some(re) => Regex::new(re),
+            None => Regex::new(&glob_to_regex(source)),
+        }
+        .with_context(|| format!("Invalid repository pattern {:?}", source))?;
+        Ok(Self {
+            source: source.to_string(),
+            regex,
+        })
+    }
+
+    fn matches(&self, repo: &str) -> bool {
+        self.regex.is_match(repo)
+    }
+}
+
+/// Translate a glob into an anchored regex. `*` and `?` stay within one path
+/// segment, `**` spans segments, and `**/` also matches no segment at all.
+fn glob_to_regex(glob: &str) -> String {
+    let mut re = String::from("^");
+    let mut rest = glob;
+    while let Some(c) = rest.chars().next() {
+        if let Some(after) = rest.strip_prefix("**/") {
+            re.push_str("(?:.*/)?");
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("**") {
+            re.push_str(".*");
+            rest = after;
+        } else {
+            match c {
+                '*' => re.push_str("[^/]*"),
+                '?' => re.push_str("[^/]"),
+                c => re.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+            }
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    re.push('$');
+    re
+}
+
+/// Selection of repositories from the catalog by include and exclude patterns
+#[derive(Debug, Default)]
+pub struct RepoFilter {
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+}
+
+impl RepoFilter {
+    /// Build the filter from `--repo-include`, `--repo-exclude` and `--repo-file`.
+    ///
+    /// The file holds one pattern per line; lines starting with `!` exclude,
+    /// blank lines and `#` comments are ignored.
+    pub fn new(include: &[String], exclude: &[String], file: Option<&Path>) -> Result<Self> {
+        let mut filter = Self::default();
+        for pattern in include {
+            filter.include.push(Pattern::parse(pattern)?);
+        }
+        for pattern in exclude {
+            filter.exclude.push(Pattern::parse(pattern)?);
+        }
+
+        if let Some(path) = file {
+            let text = fs::read_to_string(path)
+                .with_context(|| format!("Failed to read repository file {}", path.display()))?;
+            for line in text.lines().map(str::trim) {
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                match line.strip_prefix('!') {
+                    Some(pattern) => filter.exclude.push(Pattern::parse(pattern.trim())?),
+                    None => filter.include.push(Pattern::parse(line)?),
+                }
+            }
+        }
+        Ok(filter)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    /// Whether a repository is selected: it matches an include pattern (or
+    /// there are none) and no exclude pattern
+    pub fn matches(&self, repo: &str) -> bool {
+        let included = self.include.is_empty() || self.include.iter().any(|p| p.matches(repo));
+        included && !self.exclude.iter().any(|p| p.matches(repo))
+    }
+
+    /// Keep the selected repositories, reporting each exclusion
+    pub fn apply(&self, repos: Vec<String>, reporter: &dyn Reporter) -> Vec<String> {
+        if self.is_empty() {
+            return repos;
+        }
+        repos
+            .into_iter()
+            .filter(|repo| {
+                let keep = self.matches(repo);
+                if !keep {
+                    match self.exclude.iter().find(|p| p.matches(repo)) {
+                        Some(p) => {
+                            reporter.debug(format!("Skipping {} (excluded by {})", repo, p.source))
+                        }
+                        None => reporter.debug(format!("Skipping {} (not included)", repo)),
+                    }
+                }
+                keep
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::Silent;
+
+    fn filter(include: &[&str], exclude: &[&str]) -> RepoFilter {
+        let include: Vec<String> = include.iter().map(|s| s.to_string()).collect();
+        let exclude: Vec<String> = exclude.iter().map(|s| s.to_string()).collect();
+        RepoFilter::new(&include, &exclude, None).unwrap()
+    }
+
+    #[test]
+    fn test_glob() {
+        let f = filter(&["team-a/**"], &[]);
+        assert!(f.matches("team-a/app"));
+        assert!(f.matches("team-a/x/y"));
+        assert!(!f.matches("team-a"));
+        assert!(!f.matches("team-ab/app"));
+
+        let f = filter(&["*/app", "lib?"], &[]);
+        assert!(f.matches("team-a/app"));
+        assert!(!f.matches("a/b/app"));
+        assert!(f.matches("lib1"));
+        assert!(!f.matches("lib12"));
+
+        let f = filter(&["ci/**/base"], &[]);
+        assert!(f.matches("ci/base"));
+        assert!(f.matches("ci/x/y/base"));
+
+        // Regex metacharacters in globs are literal
+        assert!(filter(&["app.v1"], &[]).matches("app.v1"));
+        assert!(!filter(&["app.v1"], &[]).matches("appxv1"));
+    }
+
+    #[test]
+    fn test_include_exclude() {
+        let f = filter(&["ci/**"], &["ci/base-images"]);
+        let repos = vec![
+            "ci/runner".to_string(),
+            "ci/base-images".to_string(),
+            "ci/tools/lint".to_string(),
+            "web".to_string(),
+        ];
+        assert_eq!(f.apply(repos, &Silent), vec!["ci/runner", "ci/tools/lint"]);
+
+        // Exclusions alone keep everything else
+        let f = filter(&[], &["re:-tmp$"]);
+        assert!(f.matches("web"));
+        assert!(!f.matches("web-tmp"));
+
+        assert!(RepoFilter::new(&["re:(".to_string()], &[], None).is_err());
+    }
+
+    #[test]
+    fn test_repo_file() {
+        let path = std::env::temp_dir().join(format!("regtidy-repos-{}.txt", std::process::id()));
+        fs::write(&path, "# CI images\nci/**\n\n!ci/base-images\nweb\n").unwrap();
+        let f = RepoFilter::new(&[], &[], Some(&path)).unwrap();
+        assert!(f.matches("ci/runner"));
+        assert!(f.matches("web"));
+        assert!(!f.matches("ci/base-images"));
+        assert!(!f.matches("api"));
+        let _ = fs::remove_file(&path);
+    }
+}
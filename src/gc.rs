@@ -0,0 +1,301 @@
+use std::process::Stdio;
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::Command;
+use tokio::sync::mpsc;
+
+use regtidy::models::GcReport;
+use regtidy::report::Reporter;
+
+use crate::cli::{GcArgs, GcRunner};
+
+/// Run the registry garbage collector, streaming its output to stdout
+pub async fn run_gc(args: &GcArgs, dry_run: bool, reporter: &dyn Reporter) -> Result<GcReport> {
+    if args.gc_runner != GcRunner::Local && args.gc_container.is_none() {
+        anyhow::bail!("--gc-container is required with --gc-runner docker/kubectl");
+    }
+
+    if let Some(cmd) = &args.gc_read_only_cmd {
+        println!("Switching registry to read-only mode...");
+        run_shell(cmd).await.context("Failed to enable read-only mode")?;
+    }
+
+    let result = collect(args, dry_run, reporter).await;
+
+    if let Some(cmd) = &args.gc_read_write_cmd {
+        println!("Restoring registry read-write mode...");
+        let restored = run_shell(cmd)
+            .await
+            .context("Failed to restore read-write mode");
+        // Report both failures, the collector's first
+        match (result, restored) {
+            (result, Ok(())) => return result,
+            (Ok(_), Err(e)) => return Err(e),
+            (Err(gc), Err(e)) => anyhow::bail!("{:#}; {:#}", gc, e),
+        }
+    }
+
+    result
+}
+
+async fn collect(args: &GcArgs, dry_run: bool, reporter: &dyn Reporter) -> Result<GcReport> {
+    // Storage size can only change when blobs are actually removed
+    let measure = args.gc_storage_root.as_deref().filter(|_| !dry_run);
+
+    let size_before = match measure {
+        Some(root) => Some(storage_size(args, root).await?),
+        None => None,
+    };
+
+    let mut gc_argv = vec![
+        args.gc_binary.as_str(),
+        "garbage-collect",
+        args.gc_config.as_str(),
+    ];
+    if args.gc_delete_untagged {
+        gc_argv.push("--delete-untagged");
+    }
+    if dry_run {
+        gc_argv.push("--dry-run");
+    }
+
+    let mut cmd = build_command(args, &gc_argv);
+    reporter.debug(format!("Running {:?}", cmd.as_std()));
+
+    let mut child = cmd
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to start garbage collector")?;
+
+    // Forward stdout and stderr line by line as they arrive
+    let (tx, mut rx) = mpsc::unbounded_channel::<String>();
+    if let Some(stdout) = child.stdout.take() {
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stdout).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let _ = tx.send(line);
+            }
+        });
+    }
+    if let Some(stderr) = child.stderr.take() {
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stderr).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let _ = tx.send(line);
+            }
+        });
+    }
+    drop(tx);
+
+    let parser = OutputParser::new();
+    let mut report = GcReport {
+        dry_run,
+        ..Default::default()
+    };
+    while let Some(line) = rx.recv().await {
+        println!("  gc | {}", line);
+        parser.parse_line(&line, &mut report);
+    }
+
+    let status = child
+        .wait()
+        .await
+        .context("Failed to wait for garbage collector")?;
+    if !status.success() {
+        anyhow::bail!("Garbage collector exited with {}", status);
+    }
+
+    if let (Some(root), Some(before)) = (measure, size_before) {
+        let after = storage_size(args, root).await?;
+        report.reclaimed_bytes = Some(before.saturating_sub(after));
+    }
+
+    Ok(report)
+}
+
+/// Wrap a command so it runs wherever the registry lives
+fn build_command(args: &GcArgs, argv: &[&str]) -> Command {
+    let container = args.gc_container.as_deref().unwrap_or_default();
+    let mut cmd = match args.gc_runner {
+        GcRunner::Local => {
+            let mut cmd = Command::new(argv[0]);
+            cmd.args(&argv[1..]);
+            return cmd;
+        }
+        GcRunner::Docker => {
+            let mut cmd = Command::new("docker");
+            cmd.arg("exec").arg(container);
+            cmd
+        }
+        GcRunner::Kubectl => {
+            let mut cmd = Command::new("kubectl");
+            cmd.arg("exec");
+            if let Some(ns) = &args.gc_namespace {
+                cmd.arg("-n").arg(ns);
+            }
+            cmd.arg(container);
+            if let Some(name) = &args.gc_pod_container {
+                cmd.arg("-c").arg(name);
+            }
+            cmd.arg("--");
+            cmd
+        }
+    };
+    cmd.args(argv);
+    cmd
+}
+
+/// Total size of the storage root in bytes, measured with `du`
+async fn storage_size(args: &GcArgs, root: &str) -> Result<u64> {
+    let output = build_command(args, &["du", "-sk", root])
+        .output()
+        .await
+        .context("Failed to run du")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "du -sk {} failed: {}",
+            root,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let kib: u64 = stdout
+        .split_whitespace()
+        .next()
+        .and_then(|s| s.parse().ok())
+        .with_context(|| format!("Unexpected du output: {}", stdout.trim()))?;
+    Ok(kib * 1024)
+}
+
+async fn run_shell(cmd: &str) -> Result<()> {
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(cmd)
+        .status()
+        .await
+        .with_context(|| format!("Failed to run `{}`", cmd))?;
+    if !status.success() {
+        anyhow::bail!("`{}` exited with {}", cmd, status);
+    }
+    Ok(())
+}
+
+struct OutputParser {
+    summary: Regex,
+}
+
+impl OutputParser {
+    fn new() -> Self {
+        Self {
+            // e.g. "12 blobs marked, 3 blobs and 1 manifests eligible for deletion"
+            summary: Regex::new(
+                r"(\d+) blobs marked, (\d+) blobs and (\d+) manifests eligible for deletion",
+            )
+            .unwrap(),
+        }
+    }
+
+    fn parse_line(&self, line: &str, report: &mut GcReport) {
+        if let Some(caps) = self.summary.captures(line) {
+            report.blobs_marked = caps[1].parse().unwrap_or(0);
+            report.blobs_eligible = caps[2].parse().unwrap_or(0);
+            report.manifests_eligible = caps[3].parse().unwrap_or(0);
+        } else if line.contains("Deleting blob:") {
+            report.blobs_deleted += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_gc_output() {
+        let output = [
+            "myapp",
+            "myapp: marking manifest sha256:aaaa",
+            "myapp: marking blob sha256:bbbb",
+            "",
+            "12 blobs marked, 3 blobs and 1 manifests eligible for deletion",
+            "blob eligible for deletion: sha256:cccc",
+            r#"time="2024-01-01T00:00:00Z" level=info msg="Deleting blob: /docker/registry/v2/blobs/sha256/cc/cccc" go.version=go1.20"#,
+            r#"time="2024-01-01T00:00:00Z" level=info msg="Deleting blob: /docker/registry/v2/blobs/sha256/dd/dddd" go.version=go1.20"#,
+        ];
+
+        let parser = OutputParser::new();
+        let mut report = GcReport::default();
+        for line in output {
+            parser.parse_line(line, &mut report);
+        }
+
+        assert_eq!(report.blobs_marked, 12);
+        assert_eq!(report.blobs_eligible, 3);
+        assert_eq!(report.manifests_eligible, 1);
+        assert_eq!(report.blobs_deleted, 2);
+    }
+
+    #[test]
+    fn test_build_command_kubectl() {
+        let args = GcArgs {
+            gc_runner: GcRunner::Kubectl,
+            gc_container: Some("registry-0".to_string()),
+            gc_namespace: Some("infra".to_string()),
+            gc_pod_container: Some("registry".to_string()),
+            gc_binary: "bin/registry".to_string(),
+            gc_config: "/etc/docker/registry/config.yml".to_string(),
+            gc_delete_untagged: false,
+            gc_storage_root: None,
+            gc_read_only_cmd: None,
+            gc_read_write_cmd: None,
+        };
+
+        let cmd = build_command(&args, &["bin/registry", "garbage-collect"]);
+        let std_cmd = cmd.as_std();
+        let argv: Vec<_> = std_cmd.get_args().map(|a| a.to_str().unwrap()).collect();
+        assert_eq!(std_cmd.get_program(), "kubectl");
+        assert_eq!(
+            argv,
+            [
+                "exec",
+                "-n",
+                "infra",
+                "registry-0",
+                "-c",
+                "registry",
+                "--",
+                "bin/registry",
+                "garbage-collect"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_restore_failure_keeps_gc_error() {
+        let args = GcArgs {
+            gc_runner: GcRunner::Local,
+            gc_container: None,
+            gc_namespace: None,
+            gc_pod_container: None,
+            gc_binary: "false".to_string(),
+            gc_config: "config.yml".to_string(),
+            gc_delete_untagged: false,
+            gc_storage_root: None,
+            gc_read_only_cmd: Some("true".to_string()),
+            gc_read_write_cmd: Some("exit 3".to_string()),
+        };
+
+        let err = run_gc(&args, false, &regtidy::report::Silent)
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("Garbage collector exited with"), "{}", err);
+        assert!(err.contains("Failed to restore read-write mode: `exit 3` exited with"));
+    }
+}
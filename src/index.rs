@@ -0,0 +1,173 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::models::{Descriptor, TagInfo};
+
+/// Tags referencing one manifest digest
+#[derive(Debug, Default)]
+struct DigestRefs {
+    size: Option<u64>,
+    /// Config and layer blobs of the manifest, if known
+    blobs: Vec<Descriptor>,
+    /// (repository, tag) pairs
+    tags: Vec<(String, String)>,
+}
+
+/// Registry-wide map from manifest digest to the tags that reference it.
+///
+/// Cross-repository mounts make the same manifest appear in several
+/// repositories; deleting it from one of them frees nothing while another
+/// still references it. Likewise a layer frees nothing while any kept
+/// manifest still references it.
+#[derive(Debug, Default)]
+pub struct DigestIndex {
+    refs: HashMap<String, DigestRefs>,
+}
+
+impl DigestIndex {
+    /// Record the tags of one repository
+    pub fn add(&mut self, tags: &[TagInfo]) {
+        for tag in tags {
+            let refs = self.refs.entry(tag.digest.clone()).or_default();
+            refs.size = refs.size.or(tag.size);
+            if refs.blobs.is_empty() {
+                refs.blobs = tag.blobs.clone();
+            }
+            refs.tags.push((tag.repository.clone(), tag.tag.clone()));
+        }
+    }
+
+    /// Tags referencing `digest`, as (repository, tag) pairs
+    pub fn tags(&self, digest: &str) -> &[(String, String)] {
+        self.refs
+            .get(digest)
+            .map(|r| r.tags.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// A tag referencing `digest` that is not among `deleted`, if any
+    pub fn remaining_tag<'a>(
+        &'a self,
+        digest: &str,
+        deleted: &HashSet<(&str, &str)>,
+    ) -> Option<&'a (String, String)> {
+        self.tags(digest)
+            .iter()
+            .find(|(repo, tag)| !deleted.contains(&(repo.as_str(), tag.as_str())))
+    }
+
+    /// Size of the blobs that no kept manifest references once `deleted` is
+    /// gone, each blob counted once. A manifest whose blobs are unknown counts
+    /// its whole size once no tag references it anymore.
+    pub fn reclaimable_bytes(&self, deleted: &[TagInfo]) -> u64 {
+        let gone: HashSet<(&str, &str)> = deleted
+            .iter()
+            .map(|t| (t.repository.as_str(), t.tag.as_str()))
+            .collect();
+        let is_kept =
+            |(repo, tag): &(String, String)| !gone.contains(&(repo.as_str(), tag.as_str()));
+
+        // Blobs of every manifest that still has a tag
+        let in_use: HashSet<&str> = self
+            .refs
+            .values()
+            .filter(|refs| refs.tags.iter().any(is_kept))
+            .flat_map(|refs| refs.blobs.iter().map(|b| b.digest.as_str()))
+            .collect();
+
+        let mut manifests: HashSet<&str> = HashSet::new();
+        let mut counted: HashSet<&str> = HashSet::new();
+        let mut bytes = 0;
+        for tag in deleted {
+            if !manifests.insert(tag.digest.as_str())
+                || self.remaining_tag(&tag.digest, &gone).is_some()
+            {
+                continue;
+            }
+            let refs = self.refs.get(&tag.digest);
+            let blobs = refs.map_or(tag.blobs.as_slice(), |r| r.blobs.as_slice());
+            if blobs.is_empty() {
+                bytes += refs.and_then(|r| r.size).or(tag.size).unwrap_or(0);
+                continue;
+            }
+            for blob in blobs {
+                if !in_use.contains(blob.digest.as_str()) && counted.insert(blob.digest.as_str()) {
+                    bytes += blob.size;
+                }
+            }
+        }
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tag(repo: &str, name: &str, digest: &str, size: u64) -> TagInfo {
+        TagInfo {
+            repository: repo.to_string(),
+            tag: name.to_string(),
+            digest: digest.to_string(),
+            created: None,
+            size: Some(size),
+            pushed: None,
+            last_pulled: None,
+            blobs: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_reclaimable_bytes() {
+        let app = vec![
+            tag("app", "v1", "sha256:a", 100),
+            tag("app", "v2", "sha256:b", 200),
+        ];
+        let release = vec![tag("app-release", "1.0", "sha256:a", 100)];
+        let mut index = DigestIndex::default();
+        index.add(&app);
+        index.add(&release);
+
+        assert_eq!(index.tags("sha256:a").len(), 2);
+
+        // sha256:a is still tagged in app-release
+        assert_eq!(index.reclaimable_bytes(&app), 200);
+
+        // Deleted everywhere, counted once
+        let all: Vec<TagInfo> = app.iter().chain(&release).cloned().collect();
+        assert_eq!(index.reclaimable_bytes(&all), 300);
+    }
+
+    fn blob(digest: &str, size: u64) -> Descriptor {
+        Descriptor {
+            media_type: String::new(),
+            size,
+            digest: digest.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_shared_layers() {
+        let base = blob("sha256:base", 1000);
+        let mut v1 = tag("app", "v1", "sha256:a", 1110);
+        v1.blobs = vec![
+            blob("sha256:cfg1", 10),
+            base.clone(),
+            blob("sha256:l1", 100),
+        ];
+        let mut v2 = tag("app", "v2", "sha256:b", 1220);
+        v2.blobs = vec![
+            blob("sha256:cfg2", 20),
+            base.clone(),
+            blob("sha256:l2", 200),
+        ];
+        let mut v3 = tag("app", "v3", "sha256:c", 1330);
+        v3.blobs = vec![blob("sha256:cfg3", 30), base, blob("sha256:l3", 300)];
+        let mut index = DigestIndex::default();
+        index.add(&[v1.clone(), v2.clone(), v3.clone()]);
+
+        // The base layer is still used by the kept v3
+        assert_eq!(index.reclaimable_bytes(&[v1.clone(), v2.clone()]), 330);
+        // Once nothing keeps it, the base layer is counted once
+        assert_eq!(index.reclaimable_bytes(&[v1, v2, v3]), 1660);
+    }
+}
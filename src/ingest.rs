@@ -0,0 +1,292 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use serde_json::Value;
+
+use regtidy::pulls::{Access, PullStore};
+
+use crate::cli::{IngestArgs, LogFormat};
+
+/// One HTTP request extracted from a log line
+#[derive(Debug, PartialEq, Eq)]
+struct Request {
+    method: String,
+    path: String,
+    status: Option<u16>,
+    time: Option<DateTime<Utc>>,
+}
+
+/// Counters reported after ingesting
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct IngestStats {
+    pub lines: usize,
+    pub requests: usize,
+    pub pulls: usize,
+    /// Manifest pulls skipped because their line has no usable timestamp
+    pub untimed: usize,
+}
+
+struct LogParser {
+    format: LogFormat,
+    clf: Regex,
+    logrus_time: Regex,
+    logrus_method: Regex,
+    logrus_uri: Regex,
+    logrus_status: Regex,
+    manifest_path: Regex,
+}
+
+impl LogParser {
+    fn new(format: LogFormat) -> Self {
+        Self {
+            format,
+            // 1.2.3.4 - user [10/Oct/2023:13:55:36 +0000] "GET /v2/app/manifests/v1 HTTP/1.1" 200 ...
+            clf: Regex::new(r#"^\S+ \S+ \S+ \[([^\]]+)\] "(\S+) (\S+)[^"]*" (\d{3})"#).unwrap(),
+            logrus_time: Regex::new(r#"\btime="([^"]+)""#).unwrap(),
+            logrus_method: Regex::new(r#"\bhttp\.request\.method=(\S+)"#).unwrap(),
+            logrus_uri: Regex::new(r#"\bhttp\.request\.uri="([^"]+)""#).unwrap(),
+            logrus_status: Regex::new(r#"\bhttp\.response\.status=(\d{3})"#).unwrap(),
+            manifest_path: Regex::new(r"^/v2/(.+)/manifests/([^/?#]+)").unwrap(),
+        }
+    }
+
+    fn parse(&self, line: &str) -> Option<Request> {
+        let line = line.trim();
+        match self.format {
+            LogFormat::Auto => {
+                if line.starts_with('{') {
+                    self.parse_json(line)
+                } else if line.contains("http.request.uri=") {
+                    self.parse_logrus_text(line)
+                } else {
+                    self.parse_clf(line)
+                }
+            }
+            LogFormat::DistributionJson | LogFormat::TraefikJson => self.parse_json(line),
+            LogFormat::DistributionText => self.parse_logrus_text(line),
+            LogFormat::Clf => self.parse_clf(line),
+        }
+    }
+
+    fn parse_clf(&self, line: &str) -> Option<Request> {
+        let caps = self.clf.captures(line)?;
+        Some(Request {
+            time: parse_clf_time(&caps[1]),
+            method: caps[2].to_string(),
+            path: caps[3].to_string(),
+            status: caps[4].parse().ok(),
+        })
+    }
+
+    fn parse_logrus_text(&self, line: &str) -> Option<Request> {
+        Some(Request {
+            method: self.logrus_method.captures(line)?[1].to_string(),
+            path: self.logrus_uri.captures(line)?[1].to_string(),
+            status: self
+                .logrus_status
+                .captures(line)
+                .and_then(|c| c[1].parse().ok()),
+            time: self
+                .logrus_time
+                .captures(line)
+                .and_then(|c| parse_time(&c[1])),
+        })
+    }
+
+    /// Distribution uses dotted logrus field names, Traefik uses CamelCase ones
+    fn parse_json(&self, line: &str) -> Option<Request> {
+        let value: Value = serde_json::from_str(line).ok()?;
+        let field = |names: &[&str]| names.iter().find_map(|n| value.get(*n));
+
+        let method = field(&["http.request.method", "RequestMethod"])?.as_str()?;
+        let path = field(&["http.request.uri", "RequestPath"])?.as_str()?;
+        let status = field(&["http.response.status", "DownstreamStatus", "OriginStatus"])
+            .and_then(|v| v.as_u64())
+            .and_then(|s| u16::try_from(s).ok());
+        let time = field(&["time", "StartUTC", "time_local"])
+            .and_then(|v| v.as_str())
+            .and_then(parse_time);
+
+        Some(Request {
+            method: method.to_string(),
+            path: path.to_string(),
+            status,
+            time,
+        })
+    }
+
+    /// Repository and reference of a successful `GET` or `HEAD` of
+    /// `/v2/<repo>/manifests/<ref>`: clients that already have the image
+    /// only check its digest with a `HEAD`
+    fn manifest_pull<'a>(&self, request: &'a Request) -> Option<(&'a str, String)> {
+        if !matches!(request.method.as_str(), "GET" | "HEAD")
+            || request.status.is_some_and(|s| !(200..300).contains(&s))
+        {
+            return None;
+        }
+        let caps = self.manifest_path.captures(&request.path)?;
+        let repo = caps.get(1)?.as_str();
+        let reference = caps[2].replace("%3A", ":").replace("%3a", ":");
+        Some((repo, reference))
+    }
+}
+
+/// An RFC 3339 timestamp, or the `10/Oct/2023:13:55:36 +0000` form of
+/// common log format that nginx also writes into `time_local`
+fn parse_time(s: &str) -> Option<DateTime<Utc>> {
+    s.parse().ok().or_else(|| parse_clf_time(s))
+}
+
+fn parse_clf_time(s: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_str(s, "%d/%b/%Y:%H:%M:%S %z")
+        .ok()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+/// Parse access logs and record every manifest GET or HEAD as a pull
+pub fn run_ingest(args: &IngestArgs) -> Result<()> {
+    let store = PullStore::open_or_default(args.pull_store.as_deref())?;
+    let parser = LogParser::new(args.format);
+    let mut stats = IngestStats::default();
+
+    for path in &args.files {
+        let reader: Box<dyn BufRead> = if path == "-" {
+            Box::new(BufReader::new(io::stdin()))
+        } else {
+            let file = File::open(path).with_context(|| format!("Failed to open {}", path))?;
+            Box::new(BufReader::new(file))
+        };
+        tracing::debug!("Reading {}", path);
+        ingest(reader, &parser, &store, &mut stats)
+            .with_context(|| format!("Failed to read {}", path))?;
+    }
+
+    store.save()?;
+    println!(
+        "Read {} lines, {} requests, recorded {} manifest pulls into {}.",
+        stats.lines,
+        stats.requests,
+        stats.pulls,
+        store.path().display()
+    );
+    if stats.untimed > 0 {
+        tracing::warn!(
+            "Skipped {} manifest pulls without a usable timestamp",
+            stats.untimed
+        );
+    }
+    Ok(())
+}
+
+fn ingest(
+    reader: impl BufRead,
+    parser: &LogParser,
+    store: &PullStore,
+    stats: &mut IngestStats,
+) -> Result<()> {
+    for line in reader.lines() {
+        let line = line?;
+        stats.lines += 1;
+
+        let Some(request) = parser.parse(&line) else {
+            continue;
+        };
+        stats.requests += 1;
+
+        let Some((repo, reference)) = parser.manifest_pull(&request) else {
+            continue;
+        };
+        // Recording such a pull as "now" would make old logs look fresh
+        let Some(at) = request.time else {
+            tracing::debug!("No usable timestamp in: {}", line);
+            stats.untimed += 1;
+            continue;
+        };
+        if reference.contains(':') {
+            store.record(repo, Some(&reference), None, Access::Pull, at);
+        } else {
+            store.record(repo, None, Some(&reference), Access::Pull, at);
+        }
+        stats.pulls += 1;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    const LOG: &str = r#"172.17.0.1 - - [01/May/2024:10:00:00 +0000] "GET /v2/team/app/manifests/v1 HTTP/1.1" 200 1234 "" "docker/24.0.7"
+172.17.0.1 - - [01/May/2024:10:00:01 +0000] "GET /v2/team/app/blobs/sha256:abc HTTP/1.1" 200 99 "" "docker/24.0.7"
+10.0.0.5 - - [02/May/2024:08:30:00 +0200] "GET /v2/base/manifests/sha256:def HTTP/1.1" 200 512 "-" "containerd/1.7"
+10.0.0.5 - - [02/May/2024:08:30:00 +0200] "GET /v2/base/manifests/missing HTTP/1.1" 404 0 "-" "containerd/1.7"
+10.0.0.5 - - [02/May/2024:08:30:00 +0200] "HEAD /v2/base/manifests/latest HTTP/1.1" 200 0 "-" "containerd/1.7"
+time="2024-05-03T11:00:00.123456789Z" level=info msg="response completed" go.version=go1.20 http.request.method=GET http.request.uri="/v2/cli/manifests/stable" http.response.status=200
+{"http.request.method":"GET","http.request.uri":"/v2/json/manifests/v2","http.response.status":200,"level":"info","msg":"response completed","time":"2024-05-04T00:00:00Z"}
+{"RequestMethod":"GET","RequestPath":"/v2/traefik/manifests/edge","DownstreamStatus":200,"StartUTC":"2024-05-05T00:00:00Z"}
+garbage line
+"#;
+
+    #[test]
+    fn test_ingest_mixed_formats() {
+        let store = PullStore::open(Path::new("/nonexistent/pulls.json")).unwrap();
+        let parser = LogParser::new(LogFormat::Auto);
+        let mut stats = IngestStats::default();
+
+        ingest(LOG.as_bytes(), &parser, &store, &mut stats).unwrap();
+
+        assert_eq!(stats.lines, 9);
+        assert_eq!(stats.requests, 8);
+        assert_eq!(stats.pulls, 6);
+
+        let app = store.activity("team/app", "sha256:x", "v1").last_pull;
+        assert_eq!(app.unwrap().to_rfc3339(), "2024-05-01T10:00:00+00:00");
+        let base = store.activity("base", "sha256:def", "").last_pull;
+        assert_eq!(base.unwrap().to_rfc3339(), "2024-05-02T06:30:00+00:00");
+        assert!(store.activity("base", "", "missing").last_pull.is_none());
+        let latest = store.activity("base", "", "latest").last_pull;
+        assert_eq!(latest.unwrap().to_rfc3339(), "2024-05-02T06:30:00+00:00");
+        assert!(store.activity("cli", "", "stable").last_pull.is_some());
+        assert!(store.activity("json", "", "v2").last_pull.is_some());
+        assert!(store.activity("traefik", "", "edge").last_pull.is_some());
+    }
+
+    #[test]
+    fn test_pulls_without_time() {
+        let store = PullStore::open(Path::new("/nonexistent/pulls.json")).unwrap();
+        let parser = LogParser::new(LogFormat::Auto);
+        let mut stats = IngestStats::default();
+        let log = r#"{"RequestMethod":"GET","RequestPath":"/v2/nginx/manifests/v1","DownstreamStatus":200,"time_local":"01/May/2024:10:00:00 +0200"}
+{"RequestMethod":"GET","RequestPath":"/v2/untimed/manifests/v1","DownstreamStatus":200,"time_local":"yesterday"}
+{"RequestMethod":"GET","RequestPath":"/v2/untimed/manifests/v2","DownstreamStatus":200}
+"#;
+
+        ingest(log.as_bytes(), &parser, &store, &mut stats).unwrap();
+
+        assert_eq!(stats.pulls, 1);
+        assert_eq!(stats.untimed, 2);
+        let nginx = store.activity("nginx", "", "v1").last_pull;
+        assert_eq!(nginx.unwrap().to_rfc3339(), "2024-05-01T08:00:00+00:00");
+        assert!(store.activity("untimed", "", "v1").last_pull.is_none());
+        assert!(store.activity("untimed", "", "v2").last_pull.is_none());
+    }
+
+    #[test]
+    fn test_forced_format_skips_other_lines() {
+        let parser = LogParser::new(LogFormat::Clf);
+        assert!(parser
+            .parse(r#"{"RequestMethod":"GET","RequestPath":"/v2/a/manifests/b"}"#)
+            .is_none());
+        assert_eq!(
+            parser
+                .parse(r#"1.2.3.4 - - [01/May/2024:10:00:00 +0000] "GET /v2/a/manifests/b HTTP/1.1" 200 1"#)
+                .unwrap()
+                .path,
+            "/v2/a/manifests/b"
+        );
+    }
+}
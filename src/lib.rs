@@ -0,0 +1,28 @@
+//! Clean up unused images in Docker Registry V2 instances.
+//!
+//! The library resolves the tags of a registry's repositories, plans which
+//! to delete with a [`Strategy`] and carries the plans out with an
+//! [`Executor`]. It never prints: everything worth showing is sent to a
+//! [`Reporter`] as an [`Event`].
+
+pub mod backend;
+pub mod cache;
+pub mod doctor;
+pub mod error;
+pub mod executor;
+pub mod filter;
+pub mod index;
+pub mod limits;
+pub mod models;
+pub mod pulls;
+pub mod registry;
+pub mod report;
+pub mod strategy;
+pub mod untagged;
+
+pub use backend::RegistryBackend;
+pub use executor::{CleanHooks, CleanOptions, Executor, Outcome, Resolved, Unattended};
+pub use models::{CleanReport, CleanupPlan, TagInfo};
+pub use registry::RegistryClient;
+pub use report::{Event, Reporter};
+pub use strategy::Strategy;
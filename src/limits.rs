@@ -0,0 +1,123 @@
+use std::collections::HashSet;
+
+use crate::models::CleanupPlan;
+
+/// Hard limits on how much one `clean` run may delete
+#[derive(Debug, Default)]
+pub struct Limits {
+    pub max_tags: Option<usize>,
+    pub max_digests: Option<usize>,
+    /// Largest share of a repository's tags that may be deleted, from 0 to 1
+    pub max_repo_fraction: Option<f64>,
+    pub allow_empty_repo: bool,
+}
+
+impl Limits {
+    /// Every limit the plans exceed, as human-readable messages
+    pub fn check(&self, plans: &[CleanupPlan]) -> Vec<String> {
+        let mut violations = Vec::new();
+
+        let tags: usize = plans.iter().map(|p| p.to_delete.len()).sum();
+        if let Some(max) = self.max_tags.filter(|max| tags > *max) {
+            violations.push(format!(
+                "{} tags would be deleted (--max-tags {})",
+                tags, max
+            ));
+        }
+
+        let digests: HashSet<(&str, &str)> = plans
+            .iter()
+            .flat_map(|p| p.to_delete.iter())
+            .map(|t| (t.repository.as_str(), t.digest.as_str()))
+            .collect();
+        if let Some(max) = self.max_digests.filter(|max| digests.len() > *max) {
+            violations.push(format!(
+                "{} digests would be deleted (--max-digests {})",
+                digests.len(),
+                max
+            ));
+        }
+
+        for plan in plans.iter().filter(|p| !p.to_delete.is_empty()) {
+            let total = plan.to_delete.len() + plan.to_keep.len();
+            if plan.to_keep.is_empty() && !self.allow_empty_repo {
+                violations.push(format!(
+                    "{} would lose all {} tags (pass --allow-empty-repo to permit)",
+                    plan.repository, total
+                ));
+                continue;
+            }
+            let fraction = plan.to_delete.len() as f64 / total as f64;
+            if let Some(max) = self.max_repo_fraction.filter(|max| fraction > *max) {
+                violations.push(format!(
+                    "{} would lose {:.0}% of its tags (--max-repo-fraction {})",
+                    plan.repository,
+                    fraction * 100.0,
+                    max
+                ));
+            }
+        }
+
+        violations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::TagInfo;
+
+    fn plan(repo: &str, delete: &[&str], keep: &[&str]) -> CleanupPlan {
+        let tag = |name: &&str| TagInfo {
+            repository: repo.to_string(),
+            tag: name.to_string(),
+            digest: format!("sha256:{}", name),
+            created: None,
+            size: None,
+            pushed: None,
+            last_pulled: None,
+            blobs: Vec::new(),
+        };
+        CleanupPlan {
+            repository: repo.to_string(),
+            to_delete: delete.iter().map(tag).collect(),
+            to_keep: keep.iter().map(tag).collect(),
+        }
+    }
+
+    #[test]
+    fn test_empty_repo() {
+        let plans = vec![plan("app", &["v1", "v2"], &[]), plan("web", &[], &["v1"])];
+        let violations = Limits::default().check(&plans);
+        assert_eq!(violations.len(), 1);
+        assert!(violations[0].starts_with("app would lose all 2 tags"));
+
+        let limits = Limits {
+            allow_empty_repo: true,
+            ..Limits::default()
+        };
+        assert!(limits.check(&plans).is_empty());
+    }
+
+    #[test]
+    fn test_limits() {
+        let plans = vec![
+            plan("app", &["v1", "v2", "v3"], &["v4"]),
+            plan("web", &["v1"], &["v2", "v3"]),
+        ];
+        let limits = Limits {
+            max_tags: Some(3),
+            max_digests: Some(4),
+            max_repo_fraction: Some(0.5),
+            allow_empty_repo: false,
+        };
+        let violations = limits.check(&plans);
+        assert_eq!(
+            violations,
+            vec![
+                "4 tags would be deleted (--max-tags 3)",
+                "app would lose 75% of its tags (--max-repo-fraction 0.5)",
+            ]
+        );
+    }
+}
@@ -0,0 +1,329 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode};
+use axum::routing::{get, post};
+use axum::Router;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use subtle::ConstantTimeEq;
+
+use regtidy::pulls::{Access, PullStore};
+
+use crate::cli::ListenArgs;
+use crate::metrics::Metrics;
+
+/// docker/distribution notification envelope. Events are parsed one by one,
+/// so a malformed event does not make the registry retry the whole envelope.
+#[derive(Debug, Deserialize)]
+struct Envelope {
+    events: Vec<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Event {
+    action: String,
+    timestamp: Option<DateTime<Utc>>,
+    target: Target,
+}
+
+#[derive(Debug, Deserialize)]
+struct Target {
+    #[serde(rename = "mediaType", default)]
+    media_type: String,
+    repository: String,
+    digest: Option<String>,
+    tag: Option<String>,
+}
+
+struct ListenState {
+    store: PullStore,
+    auth_token: Option<String>,
+    dirty: AtomicBool,
+    metrics: Arc<Metrics>,
+}
+
+/// Run the notification endpoint until SIGINT/SIGTERM
+pub async fn run_listen(args: &ListenArgs) -> Result<()> {
+    let store = PullStore::open_or_default(args.pull_store.as_deref())?;
+    tracing::info!(
+        path = %store.path().display(),
+        entries = store.len(),
+        "Recording pull events"
+    );
+
+    let state = Arc::new(ListenState {
+        store,
+        auth_token: args.auth_token.clone(),
+        dirty: AtomicBool::new(false),
+        metrics: Arc::new(Metrics::default()),
+    });
+
+    // Flush periodically rather than on every event; pulls can be very frequent
+    let flusher = {
+        let state = state.clone();
+        let interval = Duration::from_secs(args.flush_interval.max(1));
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                flush(&state);
+            }
+        })
+    };
+
+    let listener = tokio::net::TcpListener::bind(args.bind)
+        .await
+        .with_context(|| format!("Failed to bind {}", args.bind))?;
+    tracing::info!(
+        "Listening for registry notifications on http://{}/events",
+        args.bind
+    );
+
+    axum::serve(listener, router(state.clone()))
+        .with_graceful_shutdown(shutdown_signal())
+        .await
+        .context("Notification server failed")?;
+
+    flusher.abort();
+    flush(&state);
+    tracing::info!("Stopped listening");
+    Ok(())
+}
+
+fn router(state: Arc<ListenState>) -> Router {
+    Router::new()
+        .route("/events", post(receive_events))
+        .route("/healthz", get(|| async { "ok" }))
+        .with_state(state.clone())
+        .merge(state.metrics.clone().router())
+}
+
+fn flush(state: &ListenState) {
+    if state.dirty.swap(false, Ordering::Relaxed) {
+        if let Err(e) = state.store.save() {
+            tracing::error!("Failed to save pull store: {:#}", e);
+            state.dirty.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+async fn receive_events(
+    State(state): State<Arc<ListenState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> StatusCode {
+    if let Some(token) = &state.auth_token {
+        let expected = format!("Bearer {}", token);
+        let given = headers
+            .get(axum::http::header::AUTHORIZATION)
+            .map(|v| v.as_bytes())
+            .unwrap_or_default();
+        if !bool::from(given.ct_eq(expected.as_bytes())) {
+            return StatusCode::UNAUTHORIZED;
+        }
+    }
+
+    // The registry sends application/vnd.docker.distribution.events.v1+json,
+    // so parse the body directly instead of relying on the content type
+    let envelope: Envelope = match serde_json::from_slice(&body) {
+        Ok(envelope) => envelope,
+        Err(e) => {
+            tracing::warn!("Ignoring malformed notification: {}", e);
+            return StatusCode::BAD_REQUEST;
+        }
+    };
+
+    let recorded = record_events(&state.store, envelope);
+    state
+        .metrics
+        .record_notification(recorded, state.store.len());
+    if recorded > 0 {
+        state.dirty.store(true, Ordering::Relaxed);
+    }
+    StatusCode::OK
+}
+
+/// Record manifest pull/push events; blob events and malformed events are ignored
+fn record_events(store: &PullStore, envelope: Envelope) -> usize {
+    let mut recorded = 0;
+    for value in envelope.events {
+        let event: Event = match serde_json::from_value(value) {
+            Ok(event) => event,
+            Err(e) => {
+                tracing::warn!("Ignoring malformed notification event: {}", e);
+                continue;
+            }
+        };
+        let access = match event.action.as_str() {
+            "pull" => Access::Pull,
+            "push" => Access::Push,
+            _ => continue,
+        };
+        if !is_manifest_media_type(&event.target.media_type) {
+            continue;
+        }
+
+        let at = event.timestamp.unwrap_or_else(Utc::now);
+        tracing::debug!(
+            "{} {}@{} ({})",
+            event.action,
+            event.target.repository,
+            event.target.digest.as_deref().unwrap_or("-"),
+            event.target.tag.as_deref().unwrap_or("-"),
+        );
+        store.record(
+            &event.target.repository,
+            event.target.digest.as_deref(),
+            event.target.tag.as_deref(),
+            access,
+            at,
+        );
+        recorded += 1;
+    }
+    recorded
+}
+
+fn is_manifest_media_type(media_type: &str) -> bool {
+    media_type.contains("manifest") || media_type.contains("image.index")
+}
+
+/// Resolve when the process receives SIGINT or SIGTERM
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut sig) => {
+                sig.recv().await;
+            }
+            Err(_) => std::future::pending::<()>().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    const NOTIFICATION: &str = r#"{
+        "events": [
+            {
+                "id": "1",
+                "timestamp": "2024-05-01T10:00:00Z",
+                "action": "pull",
+                "target": {
+                    "mediaType": "application/vnd.docker.distribution.manifest.v2+json",
+                    "digest": "sha256:aaa",
+                    "repository": "app",
+                    "tag": "v1"
+                }
+            },
+            {
+                "id": "2",
+                "timestamp": "2024-05-01T10:00:01Z",
+                "action": "pull",
+                "target": {
+                    "mediaType": "application/vnd.docker.image.rootfs.diff.tar.gzip",
+                    "digest": "sha256:layer",
+                    "repository": "app"
+                }
+            },
+            {
+                "id": "3",
+                "timestamp": "2024-04-01T00:00:00Z",
+                "action": "push",
+                "target": {
+                    "mediaType": "application/vnd.oci.image.index.v1+json",
+                    "digest": "sha256:bbb",
+                    "repository": "app",
+                    "tag": "v2"
+                }
+            },
+            {
+                "id": "4",
+                "timestamp": "2024-04-01T00:00:00Z",
+                "action": "delete",
+                "target": {"repository": "app", "digest": "sha256:ccc"}
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn test_record_events() {
+        let store = PullStore::open(Path::new("/nonexistent/pulls.json")).unwrap();
+        let envelope: Envelope = serde_json::from_str(NOTIFICATION).unwrap();
+
+        assert_eq!(record_events(&store, envelope), 2);
+
+        let pulled = store.activity("app", "sha256:aaa", "v1");
+        assert_eq!(
+            pulled.last_pull.unwrap().to_rfc3339(),
+            "2024-05-01T10:00:00+00:00"
+        );
+        let pushed = store.activity("app", "sha256:bbb", "v2");
+        assert!(pushed.last_pull.is_none());
+        assert!(pushed.last_push.is_some());
+        assert!(store
+            .activity("app", "sha256:layer", "")
+            .last_pull
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_auth_token() {
+        let state = Arc::new(ListenState {
+            store: PullStore::open(Path::new("/nonexistent/pulls.json")).unwrap(),
+            auth_token: Some("secret".to_string()),
+            dirty: AtomicBool::new(false),
+            metrics: Arc::new(Metrics::default()),
+        });
+        let send = |authorization: Option<&'static str>| {
+            let mut headers = HeaderMap::new();
+            if let Some(value) = authorization {
+                headers.insert(axum::http::header::AUTHORIZATION, value.parse().unwrap());
+            }
+            receive_events(State(state.clone()), headers, Bytes::from(NOTIFICATION))
+        };
+
+        assert_eq!(send(None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(send(Some("Bearer secreT")).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(send(Some("Bearer secret2")).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(send(Some("Bearer secret")).await, StatusCode::OK);
+    }
+
+    #[test]
+    fn test_malformed_event_is_skipped() {
+        let store = PullStore::open(Path::new("/nonexistent/pulls.json")).unwrap();
+        let envelope: Envelope = serde_json::from_str(
+            r#"{"events": [
+                {"action": "pull", "target": {"mediaType": "application/vnd.oci.image.manifest.v1+json"}},
+                {"action": "pull", "timestamp": "2024-05-01T10:00:00Z", "target": {
+                    "mediaType": "application/vnd.oci.image.manifest.v1+json",
+                    "repository": "app", "digest": "sha256:aaa", "tag": "v1"}}
+            ]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(record_events(&store, envelope), 1);
+        assert!(store
+            .activity("app", "sha256:aaa", "v1")
+            .last_pull
+            .is_some());
+    }
+}
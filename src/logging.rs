@@ -0,0 +1,137 @@
+use std::fmt;
+
+use tracing::{Event, Subscriber};
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields, FormattedFields};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::EnvFilter;
+
+use crate::cli::LogLineFormat;
+
+/// Install the subscriber that writes log lines to stderr.
+///
+/// The filter follows -v/-q unless RUST_LOG is set, e.g.
+/// `RUST_LOG=regtidy::backend=debug` for the requests alone.
+pub fn init(verbose: u8, quiet: bool, format: LogLineFormat) {
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new(default_filter(verbose, quiet)));
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_ansi(false)
+        .with_writer(std::io::stderr);
+    match format {
+        LogLineFormat::Text => builder.event_format(TextFormat).init(),
+        LogLineFormat::Json => builder.json().with_current_span(true).init(),
+    }
+}
+
+/// The log filter for -v/-q: warnings and progress by default, debug
+/// messages and requests with -v, everything including dependencies with -vv
+fn default_filter(verbose: u8, quiet: bool) -> &'static str {
+    match verbose {
+        _ if quiet => "error",
+        0 => "warn,regtidy=info",
+        1 => "warn,regtidy=debug",
+        _ => "debug,regtidy=trace",
+    }
+}
+
+/// `[LEVEL] message key=value` followed by the fields of the enclosing spans
+struct TextFormat;
+
+impl<S, N> FormatEvent<S, N> for TextFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        write!(writer, "[{}] ", event.metadata().level())?;
+        ctx.field_format().format_fields(writer.by_ref(), event)?;
+        for span in ctx
+            .event_scope()
+            .into_iter()
+            .flat_map(|scope| scope.from_root())
+        {
+            let extensions = span.extensions();
+            if let Some(fields) = extensions.get::<FormattedFields<N>>() {
+                if !fields.is_empty() {
+                    write!(writer, " {}", fields)?;
+                }
+            }
+        }
+        writeln!(writer)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod testing {
+    use std::io;
+    use std::sync::{Arc, Mutex};
+
+    use tracing_subscriber::EnvFilter;
+
+    use super::TextFormat;
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// The text log lines written while `f` runs, at `filter`
+    pub fn capture(filter: &str, f: impl FnOnce()) -> String {
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_env_filter(EnvFilter::new(filter))
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .event_format(TextFormat)
+            .finish();
+        tracing::subscriber::with_default(subscriber, f);
+        let bytes = buffer.0.lock().unwrap().clone();
+        String::from_utf8(bytes).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::capture;
+    use super::*;
+
+    #[test]
+    fn test_default_filter() {
+        assert_eq!(default_filter(0, false), "warn,regtidy=info");
+        assert_eq!(default_filter(1, false), "warn,regtidy=debug");
+        assert_eq!(default_filter(3, false), "debug,regtidy=trace");
+        assert_eq!(default_filter(0, true), "error");
+    }
+
+    #[test]
+    fn test_text_format() {
+        let output = capture("debug", || {
+            let span = tracing::debug_span!("request", method = "GET", status = 200);
+            let _enter = span.enter();
+            tracing::warn!("slow registry");
+            tracing::debug!(retries = 1, "request finished");
+        });
+        assert_eq!(
+            output,
+            "[WARN] slow registry method=\"GET\" status=200\n\
+             [DEBUG] request finished retries=1 method=\"GET\" status=200\n"
+        );
+    }
+}
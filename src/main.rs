@@ -1,69 +1,568 @@
+mod audit;
 mod cli;
-mod error;
-mod models;
+mod config;
+mod daemon;
+mod gc;
+mod ingest;
+mod listen;
+mod logging;
+mod metrics;
+mod notify;
 mod output;
-mod registry;
-mod strategy;
+mod reporter;
+mod safeguard;
+mod tui;
 
-use std::collections::HashSet;
 use std::process;
-
-use anyhow::Result;
-use clap::Parser;
-
-use cli::{Cli, Command};
-use output::{print_plan, print_repo_tags, print_summary};
-use registry::RegistryClient;
-use strategy::Strategy;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use tokio_util::sync::CancellationToken;
+
+use regtidy::backend::{
+    Auth, FilesystemBackend, GitLabBackend, GiteaBackend, HarborBackend, RegistryBackend, Tls,
+};
+use regtidy::cache::MetadataCache;
+use regtidy::doctor;
+use regtidy::error::{AppError, ErrorKind, ErrorRecord};
+use regtidy::filter::RepoFilter;
+use regtidy::index::DigestIndex;
+use regtidy::models::{CleanReport, CleanupFailure, CleanupPlan};
+use regtidy::report::Reporter;
+use regtidy::{CleanHooks, CleanOptions, Executor, Outcome, RegistryClient};
+
+use audit::AuditLog;
+use cli::{AuditCommand, BackendKind, CacheCommand, Cli, Command, ConfigCommand, ErrorFormat};
+use config::{Effective, Target};
+use metrics::Metrics;
+use output::{
+    print_checks, print_gc_report, print_plan, print_repo_tags, print_summary, print_untagged_plan,
+    print_untagged_summary,
+};
 
 #[tokio::main]
 async fn main() {
-    if let Err(e) = run().await {
-        eprintln!("Error: {:#}", e);
-        process::exit(1);
+    let (cli, effective) = match config::parse_cli() {
+        Ok(parsed) => parsed,
+        Err(e) => exit_with(&e, ErrorFormat::Text),
+    };
+    logging::init(cli.verbose, cli.quiet, cli.log_format);
+    let format = cli.error_format;
+    if let Err(e) = run(cli, effective).await {
+        exit_with(&e, format);
+    }
+}
+
+/// Report the error that ended the run and exit with its class's code
+fn exit_with(err: &anyhow::Error, format: ErrorFormat) -> ! {
+    let record = ErrorRecord::new(err);
+    match format {
+        ErrorFormat::Text => eprintln!("Error: {:#}", err),
+        ErrorFormat::Json => match serde_json::to_string(&record) {
+            Ok(json) => eprintln!("{}", json),
+            Err(_) => eprintln!("Error: {:#}", err),
+        },
+    }
+    process::exit(record.exit_code)
+}
+
+async fn run(cli: Cli, effective: Effective) -> Result<()> {
+    let reporter = reporter::from_cli(
+        cli.progress,
+        cli.verbose,
+        cli.quiet,
+        effective.targets.len(),
+    );
+    match &cli.command {
+        Command::Cache { command } => return run_cache(command),
+        Command::Audit { command } => return run_audit(command),
+        Command::Config {
+            command: ConfigCommand::Show,
+        } => {
+            config::show(&effective);
+            return Ok(());
+        }
+        Command::Listen(args) => return listen::run_listen(args).await,
+        Command::IngestLogs(args) => return ingest::run_ingest(args),
+        Command::Gc { gc, dry_run } => {
+            let report = gc::run_gc(gc, *dry_run, reporter.as_ref()).await?;
+            println!("\n{}", "═".repeat(60));
+            print_gc_report(&report);
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    let targets = &effective.targets;
+    if targets.len() > 1 {
+        match &cli.command {
+            Command::Daemon(_) => anyhow::bail!("daemon supports a single registry"),
+            Command::Untagged { .. } => anyhow::bail!("untagged supports a single registry"),
+            Command::Clean(_)
+                if targets
+                    .iter()
+                    .any(|t| t.clean.as_ref().is_some_and(|a| a.gc)) =>
+            {
+                anyhow::bail!("--gc supports a single registry")
+            }
+            Command::Clean(_)
+                if targets
+                    .iter()
+                    .any(|t| t.clean.as_ref().is_some_and(|a| a.interactive)) =>
+            {
+                anyhow::bail!("--interactive supports a single registry")
+            }
+            _ => {}
+        }
+    }
+    if let Command::Doctor = cli.command {
+        return run_doctor(targets, cli.repo.as_deref(), &reporter).await;
+    }
+
+    let filter = RepoFilter::new(
+        &cli.repo_include,
+        &cli.repo_exclude,
+        cli.repo_file.as_deref(),
+    )?;
+    let cache = open_cache(targets, reporter.as_ref())?;
+
+    if matches!(cli.command, Command::Daemon(_) | Command::Untagged { .. }) {
+        let client = build_backend(&targets[0], cache.as_ref(), &reporter)?;
+        let client = client.as_ref();
+        let result = match &cli.command {
+            Command::Daemon(args) => {
+                return daemon::run_daemon(client, &cli, &filter, args, reporter.as_ref()).await
+            }
+            Command::Untagged { dry_run } => {
+                let repos =
+                    select_repos(client, cli.repo.as_deref(), &filter, reporter.as_ref()).await?;
+                if repos.is_empty() {
+                    println!("No repositories found.");
+                    return Ok(());
+                }
+                run_untagged(client, &repos, *dry_run, reporter.as_ref()).await
+            }
+            _ => unreachable!("matched above"),
+        };
+        if let Err(e) = client.save_cache() {
+            reporter.warn(format!("Failed to save metadata cache: {:#}", e));
+        }
+        return result;
+    }
+
+    let metrics = Metrics::default();
+    let started = std::time::Instant::now();
+
+    let result = match &cli.command {
+        Command::List => {
+            let results = for_each_target(
+                targets,
+                cache.as_ref(),
+                &cli,
+                &filter,
+                &reporter,
+                async |client, repos, _, label| {
+                    run_list(client, repos, label, reporter.as_ref(), &metrics).await
+                },
+            )
+            .await;
+            let (results, failed) = split_failures(results, reporter.as_ref());
+            if !results.is_empty() {
+                let repos: usize = results.iter().map(|(_, (repos, _))| repos).sum();
+                let tags: usize = results.iter().map(|(_, (_, tags))| tags).sum();
+                if targets.len() > 1 {
+                    println!(
+                        "\n{} registries, {} repositories, {} tags total.",
+                        results.len(),
+                        repos,
+                        tags
+                    );
+                } else {
+                    println!("\n{} repositories, {} tags total.", repos, tags);
+                }
+            }
+            failed
+        }
+        Command::Dangling => {
+            let results = for_each_target(
+                targets,
+                cache.as_ref(),
+                &cli,
+                &filter,
+                &reporter,
+                async |client, repos, _, _| run_dangling(client, repos, reporter.as_ref()).await,
+            )
+            .await;
+            let (results, failed) = split_failures(results, reporter.as_ref());
+            if !results.is_empty() {
+                let dangling: Vec<String> = results
+                    .into_iter()
+                    .flat_map(|(label, repos)| {
+                        repos.into_iter().map(move |repo| match targets.len() {
+                            1 => repo,
+                            _ => format!("[{}] {}", label, repo),
+                        })
+                    })
+                    .collect();
+                print_dangling(&dangling);
+            }
+            failed
+        }
+        Command::Clean(_) => {
+            let cancel = CancellationToken::new();
+            let results = for_each_target(
+                targets,
+                cache.as_ref(),
+                &cli,
+                &filter,
+                &reporter,
+                async |client, repos, target, label| {
+                    // Set by parse_cli for the clean subcommand
+                    let args = target.clean.as_ref().context("Missing clean options")?;
+                    let report =
+                        run_clean(client, repos, args, label, reporter.as_ref(), &cancel).await?;
+                    Ok((client.location(), report))
+                },
+            )
+            .await;
+            notify_clean(targets, &results).await;
+            for (_, result) in &results {
+                match result {
+                    Ok(Some((_, report))) => {
+                        metrics.record_clean(report, started.elapsed().as_secs_f64())
+                    }
+                    Ok(None) => {}
+                    Err(_) => metrics.record_failure(started.elapsed().as_secs_f64()),
+                }
+            }
+            let (results, failed) = split_failures(results, reporter.as_ref());
+            let mut reports = results.into_iter().map(|(_, (_, report))| report);
+            match reports.next() {
+                Some(first) => {
+                    let combined = reports.fold(first, |mut total, report| {
+                        total.merge(report);
+                        total
+                    });
+                    print_summary(&combined);
+                    if combined.errors > 0 && failed.is_ok() {
+                        let kinds = combined.failures.iter().map(|f| f.kind);
+                        Err(AppError::Failures {
+                            message: format!("{} errors occurred during cleanup", combined.errors),
+                            kind: ErrorKind::common(kinds),
+                        }
+                        .into())
+                    } else {
+                        failed
+                    }
+                }
+                None => failed,
+            }
+        }
+        Command::Untagged { .. }
+        | Command::Doctor
+        | Command::Cache { .. }
+        | Command::Audit { .. }
+        | Command::Config { .. }
+        | Command::Gc { .. }
+        | Command::Listen(_)
+        | Command::IngestLogs(_)
+        | Command::Daemon(_) => {
+            unreachable!("handled above")
+        }
+    };
+
+    if let Some(path) = &cli.metrics_file {
+        if let Err(e) = metrics.write_textfile(path) {
+            reporter.warn(format!("Failed to write metrics: {:#}", e));
+        }
     }
+
+    result
 }
 
-async fn run() -> Result<()> {
-    let cli = Cli::parse();
+/// Run `task` against every registry concurrently. A registry that fails is
+/// reported and does not stop the others; `None` means it had no repositories.
+async fn for_each_target<T>(
+    targets: &[Target],
+    cache: Option<&Arc<MetadataCache>>,
+    cli: &Cli,
+    filter: &RepoFilter,
+    reporter: &Arc<dyn Reporter>,
+    task: impl AsyncFn(&dyn RegistryBackend, &[String], &Target, Option<&str>) -> Result<T>,
+) -> Vec<(String, Result<Option<T>>)> {
+    let multiple = targets.len() > 1;
+    let runs = targets.iter().map(|target| {
+        let task = &task;
+        async move {
+            let label = target.label();
+            let result = async {
+                let client = build_backend(target, cache, reporter)?;
+                let client = client.as_ref();
+                let repos =
+                    select_repos(client, cli.repo.as_deref(), filter, reporter.as_ref()).await?;
+                if repos.is_empty() {
+                    match multiple {
+                        true => println!("[{}] No repositories found.", label),
+                        false => println!("No repositories found."),
+                    }
+                    return Ok(None);
+                }
+                let result = task(client, &repos, target, multiple.then_some(label.as_str())).await;
+                if let Err(e) = client.save_cache() {
+                    reporter.warn(format!("Failed to save metadata cache: {:#}", e));
+                }
+                result.map(Some)
+            }
+            .await;
+            (label, result)
+        }
+    });
+    futures::future::join_all(runs).await
+}
+
+/// Separate per-registry results from failures; the error names the failed registries
+/// and carries their class if they all failed the same way.
+/// With a single registry its own error is returned unchanged.
+fn split_failures<T>(
+    results: Vec<(String, Result<Option<T>>)>,
+    reporter: &dyn Reporter,
+) -> (Vec<(String, T)>, Result<()>) {
+    let total = results.len();
+    let mut ok = Vec::new();
+    let mut failed = Vec::new();
+    let mut kinds = Vec::new();
+    for (label, result) in results {
+        match result {
+            Ok(Some(value)) => ok.push((label, value)),
+            Ok(None) => {}
+            Err(e) if total == 1 => return (ok, Err(e)),
+            Err(e) => {
+                reporter.error(format!("{}: {:#}", label, e));
+                failed.push(label);
+                kinds.push(ErrorKind::of(&e));
+            }
+        }
+    }
+    if failed.is_empty() {
+        (ok, Ok(()))
+    } else {
+        let err = AppError::Failures {
+            message: format!(
+                "{} of {} registries failed: {}",
+                failed.len(),
+                total,
+                failed.join(", ")
+            ),
+            kind: ErrorKind::common(kinds),
+        };
+        (ok, Err(err.into()))
+    }
+}
 
-    let client = RegistryClient::new(&cli.registry, cli.verbose);
+/// Print the preflight checks of every registry; fails if any check failed
+async fn run_doctor(
+    targets: &[Target],
+    repo: Option<&str>,
+    reporter: &Arc<dyn Reporter>,
+) -> Result<()> {
+    let mut results = Vec::new();
+    for target in targets {
+        let result = match build_backend(target, None, reporter) {
+            Ok(client) => {
+                let checks = client.diagnose(repo).await;
+                print_checks(&client.location(), &checks);
+                doctor::ensure(&checks).map(Some)
+            }
+            Err(e) => Err(e),
+        };
+        results.push((target.label(), result));
+    }
+    split_failures(results, reporter.as_ref()).1
+}
 
-    // Determine which repositories to process
-    let repos = match &cli.repo {
-        Some(repo) => vec![repo.clone()],
+/// The single --repo, or the catalog repositories selected by the filter
+async fn select_repos(
+    client: &dyn RegistryBackend,
+    repo: Option<&str>,
+    filter: &RepoFilter,
+    reporter: &dyn Reporter,
+) -> Result<Vec<String>> {
+    match repo {
+        Some(repo) => Ok(vec![repo.to_string()]),
         None => {
-            if cli.verbose {
-                eprintln!("[DEBUG] No --repo specified, fetching catalog...");
+            reporter.debug("No --repo specified, fetching catalog...");
+            let catalog = client.list_repositories().await?;
+            let total = catalog.len();
+            let repos = filter.apply(catalog, reporter);
+            if !filter.is_empty() {
+                reporter.debug(format!(
+                    "{} of {} repositories selected by filters",
+                    repos.len(),
+                    total
+                ));
+            }
+            Ok(repos)
+        }
+    }
+}
+
+/// The metadata cache shared by all registries, unless every one disables it.
+/// Entries are keyed by digest, so registries can share them.
+fn open_cache(targets: &[Target], reporter: &dyn Reporter) -> Result<Option<Arc<MetadataCache>>> {
+    let needed = targets
+        .iter()
+        .any(|t| !t.no_cache && t.registry.is_some() && t.backend == BackendKind::Distribution);
+    if !needed {
+        return Ok(None);
+    }
+    let path = MetadataCache::default_path()?;
+    reporter.debug(format!("Using metadata cache {}", path.display()));
+    Ok(Some(Arc::new(MetadataCache::open(&path)?)))
+}
+
+/// Build the backend of one registry: one of the registry APIs,
+/// or the storage directory when --storage-root is given
+fn build_backend(
+    target: &Target,
+    cache: Option<&Arc<MetadataCache>>,
+    reporter: &Arc<dyn Reporter>,
+) -> Result<Box<dyn RegistryBackend>> {
+    let auth = Auth::from_args(
+        target.username.as_deref(),
+        target.password.as_deref(),
+        target.token.as_deref(),
+    )?;
+    let tls = Tls {
+        ca_cert: target.ca_cert.clone(),
+        insecure: target.insecure,
+    };
+
+    if let Some(url) = &target.registry {
+        match target.backend {
+            BackendKind::Distribution => {}
+            BackendKind::Harbor => {
+                let mut backend = HarborBackend::new(url, &auth, &tls, reporter.clone())?;
+                if let Some(n) = target.page_size {
+                    backend = backend.with_page_size(n);
+                }
+                return Ok(Box::new(backend));
+            }
+            BackendKind::Gitlab => {
+                let project = target
+                    .gitlab_project
+                    .as_deref()
+                    .context("--gitlab-project is required with --backend gitlab")?;
+                let mut backend = GitLabBackend::new(url, project, &auth, &tls, reporter.clone())?;
+                if let Some(n) = target.page_size {
+                    backend = backend.with_page_size(n);
+                }
+                return Ok(Box::new(backend));
+            }
+            BackendKind::Gitea => {
+                let owner = target
+                    .gitea_owner
+                    .as_deref()
+                    .context("--gitea-owner is required with --backend gitea")?;
+                let mut backend = GiteaBackend::new(url, owner, &auth, &tls, reporter.clone())?;
+                if let Some(n) = target.page_size {
+                    backend = backend.with_page_size(n);
+                }
+                return Ok(Box::new(backend));
             }
-            client.list_repositories().await?
         }
+    } else if target.backend != BackendKind::Distribution {
+        anyhow::bail!("--registry is required with --backend {:?}", target.backend);
+    }
+
+    let registry = match &target.registry {
+        Some(url) => {
+            let mut client = RegistryClient::new(url, reporter.clone()).with_auth(&auth, &tls)?;
+            if let Some(cache) = cache.filter(|_| !target.no_cache) {
+                client = client.with_cache(cache.clone());
+            }
+            if let Some(n) = target.page_size {
+                client = client.with_page_size(n);
+            }
+            Some(client)
+        }
+        None => None,
     };
 
-    if repos.is_empty() {
-        println!("No repositories found.");
-        return Ok(());
+    match (&target.storage_root, registry) {
+        (Some(root), registry) => {
+            let mut backend = FilesystemBackend::new(root, reporter.clone())?;
+            if let Some(client) = registry {
+                backend = backend.with_delete_client(client);
+            }
+            Ok(Box::new(backend))
+        }
+        (None, Some(client)) => Ok(Box::new(client)),
+        (None, None) => {
+            anyhow::bail!("--registry (or REGTIDY_REGISTRY) or --storage-root is required")
+        }
+    }
+}
+
+fn run_cache(command: &CacheCommand) -> Result<()> {
+    match command {
+        CacheCommand::Prune { older_than, all } => {
+            let cache = MetadataCache::open(&MetadataCache::default_path()?)?;
+            let max_age = if *all {
+                None
+            } else {
+                Some(chrono::Duration::days(*older_than as i64))
+            };
+
+            let removed = cache.prune(max_age);
+            cache.save()?;
+
+            println!(
+                "Removed {} cache {}, {} remaining ({}).",
+                removed,
+                if removed == 1 { "entry" } else { "entries" },
+                cache.len(),
+                cache.path().display()
+            );
+            Ok(())
+        }
     }
+}
 
-    match cli.command {
-        Command::List => run_list(&client, &repos, cli.verbose).await,
-        Command::Dangling => run_dangling(&client, &repos, cli.verbose).await,
-        Command::Clean(args) => run_clean(&client, &repos, &args, cli.verbose).await,
+fn run_audit(command: &AuditCommand) -> Result<()> {
+    match command {
+        AuditCommand::Verify { path } => {
+            let report = audit::verify(path)?;
+            println!(
+                "{}: {} entries, hash chain intact.",
+                path.display(),
+                report.entries
+            );
+            if report.unchained > 0 {
+                println!(
+                    "{} entries at the start of the log are not hash-chained.",
+                    report.unchained
+                );
+            }
+            Ok(())
+        }
     }
 }
 
-async fn run_dangling(client: &RegistryClient, repos: &[String], verbose: bool) -> Result<()> {
+/// Repositories without any tags
+async fn run_dangling(
+    client: &dyn RegistryBackend,
+    repos: &[String],
+    reporter: &dyn Reporter,
+) -> Result<Vec<String>> {
     let mut dangling: Vec<String> = Vec::new();
 
     for repo in repos {
-        if verbose {
-            eprintln!("[DEBUG] Checking repository: {}", repo);
-        }
+        reporter.debug(format!("Checking repository: {}", repo));
 
         let tags = match client.list_tags(repo).await {
             Ok(tags) => tags,
             Err(e) => {
-                eprintln!("[ERROR] Failed to list tags for {}: {}", repo, e);
+                reporter.error(format!("Failed to list tags for {}: {}", repo, e));
                 continue;
             }
         };
@@ -73,6 +572,10 @@ async fn run_dangling(client: &RegistryClient, repos: &[String], verbose: bool)
         }
     }
 
+    Ok(dangling)
+}
+
+fn print_dangling(dangling: &[String]) {
     if dangling.is_empty() {
         println!("No dangling repositories found.");
     } else {
@@ -85,7 +588,7 @@ async fn run_dangling(client: &RegistryClient, repos: &[String], verbose: bool)
                 "repositories"
             }
         );
-        for repo in &dangling {
+        for repo in dangling {
             println!("  - {}", repo);
         }
         println!(
@@ -95,142 +598,268 @@ async fn run_dangling(client: &RegistryClient, repos: &[String], verbose: bool)
             "  docker exec <registry-container> bin/registry garbage-collect /etc/docker/registry/config.yml"
         );
     }
+}
+
+async fn run_untagged(
+    client: &dyn RegistryBackend,
+    repos: &[String],
+    dry_run: bool,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    if !dry_run && !client.can_delete() {
+        anyhow::bail!(
+            "{} does not support deletion; use --dry-run or pass --registry",
+            client.location()
+        );
+    }
+
+    let executor = Executor::new(client, reporter).dry_run(dry_run);
+    let (plans, failures) = executor.plan_untagged(repos).await;
+    for plan in &plans {
+        print_untagged_plan(plan, dry_run);
+    }
+    let report = executor.execute_untagged(&plans).await;
+
+    let total_protected: usize = plans.iter().map(|p| p.protected.len()).sum();
+    let total_errors = failures.len() + report.errors;
+    print_untagged_summary(
+        report.deleted_digests,
+        total_protected,
+        total_errors,
+        dry_run,
+    );
+
+    if total_errors > 0 {
+        anyhow::bail!("{} errors occurred during cleanup", total_errors);
+    }
 
     Ok(())
 }
 
-async fn run_list(client: &RegistryClient, repos: &[String], verbose: bool) -> Result<()> {
+/// Print the tags of each repository; returns the repository and tag counts
+async fn run_list(
+    client: &dyn RegistryBackend,
+    repos: &[String],
+    registry: Option<&str>,
+    reporter: &dyn Reporter,
+    metrics: &Metrics,
+) -> Result<(usize, usize)> {
     let mut total_tags: usize = 0;
 
     for repo in repos {
-        if verbose {
-            eprintln!("[DEBUG] Listing repository: {}", repo);
-        }
+        reporter.repository_started(repo);
 
-        let tags = match client.resolve_all_tags(repo).await {
+        let result = client.resolve_all_tags(repo).await;
+        reporter.repository_resolved(repo, result.as_ref().map_or(0, Vec::len));
+        let tags = match result {
             Ok(tags) => tags,
             Err(e) => {
-                eprintln!("[ERROR] Failed to resolve tags for {}: {}", repo, e);
+                reporter.error(format!("Failed to resolve tags for {}: {}", repo, e));
                 continue;
             }
         };
 
         total_tags += tags.len();
-        print_repo_tags(repo, &tags);
+        metrics.record_repository_tags(&client.location(), repo, tags.len());
+        print_repo_tags(registry, repo, &tags);
     }
 
-    println!(
-        "\n{} repositories, {} tags total.",
-        repos.len(),
-        total_tags
-    );
+    Ok((repos.len(), total_tags))
+}
 
-    Ok(())
+/// Terminal side of a `clean` run: printing, interactive review,
+/// confirmation and the audit log
+struct CliHooks<'a> {
+    args: &'a cli::CleanArgs,
+    registry: Option<&'a str>,
+    location: String,
+    rule: String,
+    audit: Option<AuditLog>,
 }
 
-async fn run_clean(
-    client: &RegistryClient,
-    repos: &[String],
-    args: &cli::CleanArgs,
-    verbose: bool,
-) -> Result<()> {
-    let strategy = Strategy::from_args(args)?;
+#[async_trait::async_trait]
+impl CleanHooks for CliHooks<'_> {
+    fn review(
+        &mut self,
+        plans: Vec<CleanupPlan>,
+        index: &DigestIndex,
+    ) -> Result<Option<Vec<CleanupPlan>>> {
+        let plans = if self.args.interactive && !plans.is_empty() {
+            let reviewed =
+                tokio::task::block_in_place(|| tui::review(plans, index, self.args.dry_run))?;
+            match reviewed {
+                Some(plans) => plans,
+                None => {
+                    println!("Interactive review cancelled; nothing was deleted.");
+                    return Ok(None);
+                }
+            }
+        } else {
+            plans
+        };
 
-    if verbose {
-        eprintln!("[DEBUG] Strategy: {:?}", strategy);
-        eprintln!("[DEBUG] Dry run: {}", args.dry_run);
+        // Print every plan before anything is deleted
+        for plan in &plans {
+            print_plan(self.registry, plan, self.args.dry_run);
+        }
+        Ok(Some(plans))
     }
 
-    let mut total_deleted: usize = 0;
-    let mut total_kept: usize = 0;
-    let mut total_errors: usize = 0;
-    let mut all_deleted_digests: HashSet<String> = HashSet::new();
-
-    for repo in repos {
-        if verbose {
-            eprintln!("[DEBUG] Processing repository: {}", repo);
+    async fn confirm(&mut self, plans: &[CleanupPlan]) -> Result<bool> {
+        if self.args.yes || self.args.interactive {
+            return Ok(true);
         }
+        let tags_to_delete: usize = plans.iter().map(|p| p.to_delete.len()).sum();
+        let repos_affected = plans.iter().filter(|p| !p.to_delete.is_empty()).count();
+        let question = format!(
+            "Delete {} tags in {} repositories of {}?",
+            tags_to_delete, repos_affected, self.location
+        );
+        safeguard::confirm(&question).await
+    }
 
-        // Resolve all tags
-        let tags = match client.resolve_all_tags(repo).await {
-            Ok(tags) => tags,
-            Err(e) => {
-                eprintln!("[ERROR] Failed to resolve tags for {}: {}", repo, e);
-                total_errors += 1;
-                continue;
-            }
-        };
+    fn record(
+        &mut self,
+        repo: &str,
+        digest: &str,
+        tags: &[String],
+        outcome: Outcome,
+    ) -> Result<()> {
+        match self.audit.as_mut() {
+            Some(audit) => audit.record(repo, digest, tags, &self.rule, self.args.dry_run, outcome),
+            None => Ok(()),
+        }
+    }
+}
 
-        if tags.is_empty() {
-            if verbose {
-                eprintln!("[DEBUG] No tags found for {}", repo);
-            }
+/// A registry's result, with the location of its backend
+type Located<T> = (String, T);
+
+/// Send the notifications of a `clean` run once, from the report merged over
+/// all registries. Registries that failed outright count as failures of it.
+/// Profiles may configure notifications differently: each distinct setup is
+/// notified about the registries that use it.
+async fn notify_clean(
+    targets: &[Target],
+    results: &[(String, Result<Option<Located<CleanReport>>>)],
+) {
+    let mut groups: Vec<(&cli::CleanArgs, Vec<usize>)> = Vec::new();
+    for (i, target) in targets.iter().enumerate() {
+        let Some(args) = &target.clean else {
             continue;
+        };
+        match groups.iter_mut().find(|(a, _)| a.notify == args.notify) {
+            Some((_, members)) => members.push(i),
+            None => groups.push((args, vec![i])),
         }
+    }
 
-        // Apply strategy
-        let plan = strategy.apply(repo, tags);
-
-        // Print the plan
-        print_plan(&plan, args.dry_run);
-
-        total_kept += plan.to_keep.len();
-
-        // Execute deletions (unless dry-run)
-        if args.dry_run {
-            total_deleted += plan.to_delete.len();
-            for tag in &plan.to_delete {
-                all_deleted_digests.insert(tag.digest.clone());
-            }
-        } else {
-            // Collect unique digests to delete
-            let mut digests_to_delete: Vec<String> = Vec::new();
-            let mut seen: HashSet<String> = HashSet::new();
-
-            for tag in &plan.to_delete {
-                if seen.insert(tag.digest.clone()) {
-                    digests_to_delete.push(tag.digest.clone());
-                }
-            }
-
-            let mut deleted_digests_this_repo: HashSet<String> = HashSet::new();
-
-            for digest in &digests_to_delete {
-                match client.delete_manifest(repo, digest).await {
-                    Ok(()) => {
-                        if verbose {
-                            eprintln!("[DEBUG] Deleted digest {}", digest);
-                        }
-                        all_deleted_digests.insert(digest.clone());
-                        deleted_digests_this_repo.insert(digest.clone());
-                    }
-                    Err(e) => {
-                        eprintln!("[ERROR] Failed to delete digest {}: {}", digest, e);
-                        total_errors += 1;
+    for (args, members) in groups {
+        let mut registries = Vec::new();
+        let mut merged: Option<CleanReport> = None;
+        let mut errors = Vec::new();
+        for (label, result) in members.into_iter().filter_map(|i| results.get(i)) {
+            match result {
+                Ok(Some((location, report))) => {
+                    registries.push(location.clone());
+                    match &mut merged {
+                        Some(total) => total.merge(report.clone()),
+                        None => merged = Some(report.clone()),
                     }
                 }
+                Ok(None) => {}
+                Err(e) => {
+                    registries.push(label.clone());
+                    errors.push((label, e));
+                }
             }
+        }
 
-            // Only count tags whose digests were actually deleted
-            for tag in &plan.to_delete {
-                if deleted_digests_this_repo.contains(&tag.digest) {
-                    total_deleted += 1;
+        let outcome = match (merged, errors.as_slice()) {
+            (None, []) => continue,
+            (None, [(_, e)]) => Err(anyhow::anyhow!("{:#}", e)),
+            (None, errors) => Err(anyhow::anyhow!(
+                "{}",
+                errors
+                    .iter()
+                    .map(|(label, e)| format!("{}: {:#}", label, e))
+                    .collect::<Vec<_>>()
+                    .join("; ")
+            )),
+            (Some(mut report), errors) => {
+                for (label, e) in errors {
+                    report.failures.push(CleanupFailure {
+                        repository: None,
+                        digest: None,
+                        tags: Vec::new(),
+                        kind: ErrorKind::of(e),
+                        error: format!("Registry {} failed: {:#}", label, e),
+                    });
                 }
+                report.errors = report.failures.len();
+                Ok(report)
             }
-        }
+        };
+        notify::notify(&args.notify, &registries.join(", "), args.dry_run, &outcome).await;
     }
+}
 
-    print_summary(
-        total_deleted,
-        all_deleted_digests.len(),
-        total_kept,
-        total_errors,
-        args.dry_run,
-    );
+async fn run_clean(
+    client: &dyn RegistryBackend,
+    repos: &[String],
+    args: &cli::CleanArgs,
+    registry: Option<&str>,
+    reporter: &dyn Reporter,
+    cancel: &CancellationToken,
+) -> Result<CleanReport> {
+    let strategy = args.strategy()?;
+
+    let audit = match &args.audit_log {
+        Some(path) => Some(AuditLog::open(path, &client.location(), args.audit_chain)?),
+        None => None,
+    };
+    if let Some(audit) = &audit {
+        reporter.debug(format!("Audit run ID: {}", audit.run_id()));
+    }
 
-    if total_errors > 0 {
-        process::exit(1);
+    let mut hooks = CliHooks {
+        args,
+        registry,
+        location: client.location(),
+        rule: strategy.rule(),
+        audit,
+    };
+    let options = CleanOptions {
+        preflight: !args.skip_preflight,
+        protect_across_repos: args.protect_across_repos,
+        limits: args.limits(),
+    };
+    let mut report = Executor::new(client, reporter)
+        .dry_run(args.dry_run)
+        .with_cancel(cancel.clone())
+        .clean(repos, &strategy, &options, &mut hooks)
+        .await?;
+
+    if args.gc && !cancel.is_cancelled() {
+        println!("\nRunning registry garbage collection...");
+        match gc::run_gc(&args.gc_args, args.dry_run, reporter).await {
+            Ok(gc_report) => {
+                report.gc_reclaimed_bytes = gc_report.reclaimed_bytes;
+                report.gc = Some(gc_report);
+            }
+            Err(e) => {
+                reporter.error(format!("Garbage collection failed: {:#}", e));
+                report.failures.push(CleanupFailure {
+                    repository: None,
+                    digest: None,
+                    tags: Vec::new(),
+                    kind: ErrorKind::of(&e),
+                    error: format!("Garbage collection failed: {:#}", e),
+                });
+            }
+        }
     }
 
-    Ok(())
+    report.errors = report.failures.len();
+    Ok(report)
 }
@@ -0,0 +1,295 @@
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context, Result};
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::Router;
+use chrono::Utc;
+
+use regtidy::models::CleanReport;
+
+/// Counters and gauges exported in the Prometheus text format
+#[derive(Debug, Default)]
+pub struct Metrics {
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    runs_success: u64,
+    runs_failure: u64,
+    tags_deleted: u64,
+    digests_deleted: u64,
+    deleted_bytes: u64,
+    gc_reclaimed_bytes: u64,
+    errors: u64,
+    /// Keyed by (registry, repository)
+    repository_tags: BTreeMap<(String, String), usize>,
+    last_run_duration: Option<f64>,
+    last_run_timestamp: Option<i64>,
+    notifications: u64,
+    events_recorded: u64,
+    pull_store_entries: Option<usize>,
+}
+
+impl Metrics {
+    /// Account a finished `clean` run; dry runs only update the gauges
+    pub fn record_clean(&self, report: &CleanReport, duration_secs: f64) {
+        let mut state = self.state.lock().unwrap();
+        if report.errors > 0 {
+            state.runs_failure += 1;
+        } else {
+            state.runs_success += 1;
+        }
+        state.errors += report.errors as u64;
+        if !report.dry_run {
+            state.tags_deleted += report.deleted_tags as u64;
+            state.digests_deleted += report.deleted_digests as u64;
+            state.deleted_bytes += report.deleted_bytes;
+            state.gc_reclaimed_bytes += report.gc_reclaimed_bytes.unwrap_or(0);
+        }
+        for (registry, repos) in &report.repository_tags {
+            for (repo, count) in repos {
+                state
+                    .repository_tags
+                    .insert((registry.clone(), repo.clone()), *count);
+            }
+        }
+        state.last_run_duration = Some(duration_secs);
+        state.last_run_timestamp = Some(Utc::now().timestamp());
+    }
+
+    /// Account a run that failed before producing a report
+    pub fn record_failure(&self, duration_secs: f64) {
+        let mut state = self.state.lock().unwrap();
+        state.runs_failure += 1;
+        state.errors += 1;
+        state.last_run_duration = Some(duration_secs);
+        state.last_run_timestamp = Some(Utc::now().timestamp());
+    }
+
+    /// Tag counts seen by `list`
+    pub fn record_repository_tags(&self, registry: &str, repo: &str, count: usize) {
+        let mut state = self.state.lock().unwrap();
+        state
+            .repository_tags
+            .insert((registry.to_string(), repo.to_string()), count);
+    }
+
+    /// Account one notification request and the events recorded from it
+    pub fn record_notification(&self, recorded: usize, store_entries: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.notifications += 1;
+        state.events_recorded += recorded as u64;
+        state.pull_store_entries = Some(store_entries);
+    }
+
+    /// Render everything in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let state = self.state.lock().unwrap();
+        let mut out = String::new();
+
+        let mut family = |name: &str, kind: &str, help: &str, samples: &[(String, String)]| {
+            if samples.is_empty() {
+                return;
+            }
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} {}", name, kind);
+            for (labels, value) in samples {
+                let _ = writeln!(out, "{}{} {}", name, labels, value);
+            }
+        };
+        let plain = |value: String| vec![(String::new(), value)];
+
+        family(
+            "regtidy_runs_total",
+            "counter",
+            "Cleanup runs by result.",
+            &[
+                (
+                    "{result=\"success\"}".to_string(),
+                    state.runs_success.to_string(),
+                ),
+                (
+                    "{result=\"failure\"}".to_string(),
+                    state.runs_failure.to_string(),
+                ),
+            ],
+        );
+        family(
+            "regtidy_tags_deleted_total",
+            "counter",
+            "Tags deleted.",
+            &plain(state.tags_deleted.to_string()),
+        );
+        family(
+            "regtidy_digests_deleted_total",
+            "counter",
+            "Manifest digests deleted.",
+            &plain(state.digests_deleted.to_string()),
+        );
+        family(
+            "regtidy_deleted_image_bytes_total",
+            "counter",
+            "Size of deleted blobs that no kept image references.",
+            &plain(state.deleted_bytes.to_string()),
+        );
+        family(
+            "regtidy_gc_reclaimed_bytes_total",
+            "counter",
+            "Storage space freed by garbage collection.",
+            &plain(state.gc_reclaimed_bytes.to_string()),
+        );
+        family(
+            "regtidy_errors_total",
+            "counter",
+            "Errors during cleanup runs.",
+            &plain(state.errors.to_string()),
+        );
+
+        let repos: Vec<(String, String)> = state
+            .repository_tags
+            .iter()
+            .map(|((registry, repo), count)| {
+                (
+                    format!(
+                        "{{registry=\"{}\",repository=\"{}\"}}",
+                        escape_label(registry),
+                        escape_label(repo)
+                    ),
+                    count.to_string(),
+                )
+            })
+            .collect();
+        family(
+            "regtidy_repository_tags",
+            "gauge",
+            "Tags in a repository after the last run.",
+            &repos,
+        );
+
+        if let Some(duration) = state.last_run_duration {
+            family(
+                "regtidy_last_run_duration_seconds",
+                "gauge",
+                "Duration of the last cleanup run.",
+                &plain(duration.to_string()),
+            );
+        }
+        if let Some(timestamp) = state.last_run_timestamp {
+            family(
+                "regtidy_last_run_timestamp_seconds",
+                "gauge",
+                "Unix time the last cleanup run finished.",
+                &plain(timestamp.to_string()),
+            );
+        }
+        if let Some(entries) = state.pull_store_entries {
+            family(
+                "regtidy_notifications_total",
+                "counter",
+                "Notification requests received.",
+                &plain(state.notifications.to_string()),
+            );
+            family(
+                "regtidy_pull_events_recorded_total",
+                "counter",
+                "Pull and push events recorded.",
+                &plain(state.events_recorded.to_string()),
+            );
+            family(
+                "regtidy_pull_store_entries",
+                "gauge",
+                "Entries in the pull store.",
+                &plain(entries.to_string()),
+            );
+        }
+
+        out
+    }
+
+    /// Write a node_exporter textfile (atomically, as the collector may read at any time)
+    pub fn write_textfile(&self, path: &Path) -> Result<()> {
+        let tmp = path.with_extension("prom.tmp");
+        fs::write(&tmp, self.render())
+            .with_context(|| format!("Failed to write {}", tmp.display()))?;
+        fs::rename(&tmp, path).with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(())
+    }
+
+    /// `GET /metrics` route serving these metrics
+    pub fn router(self: Arc<Self>) -> Router {
+        Router::new().route(
+            "/metrics",
+            get(move || {
+                let metrics = self.clone();
+                async move {
+                    (
+                        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+                        metrics.render(),
+                    )
+                        .into_response()
+                }
+            }),
+        )
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_clean_run() {
+        let metrics = Metrics::default();
+        let mut report = CleanReport {
+            deleted_tags: 3,
+            deleted_digests: 2,
+            deleted_bytes: 2048,
+            ..Default::default()
+        };
+        report.repository_tags.insert(
+            "http://a:5000".to_string(),
+            BTreeMap::from([("team/app".to_string(), 5)]),
+        );
+        report.repository_tags.insert(
+            "http://b:5000".to_string(),
+            BTreeMap::from([("team/app".to_string(), 7)]),
+        );
+        metrics.record_clean(&report, 1.5);
+
+        report.dry_run = true;
+        metrics.record_clean(&report, 0.5);
+
+        let text = metrics.render();
+        assert!(text.contains("# TYPE regtidy_tags_deleted_total counter\n"));
+        assert!(text.contains("regtidy_tags_deleted_total 3\n"));
+        assert!(text.contains("regtidy_deleted_image_bytes_total 2048\n"));
+        assert!(text.contains("regtidy_runs_total{result=\"success\"} 2\n"));
+        assert!(text.contains(
+            "regtidy_repository_tags{registry=\"http://a:5000\",repository=\"team/app\"} 5\n"
+        ));
+        assert!(text.contains(
+            "regtidy_repository_tags{registry=\"http://b:5000\",repository=\"team/app\"} 7\n"
+        ));
+        assert!(text.contains("regtidy_last_run_duration_seconds 0.5\n"));
+        assert!(!text.contains("regtidy_pull_store_entries"));
+    }
+
+    #[test]
+    fn test_escape_label() {
+        assert_eq!(escape_label(r#"a"b\c"#), r#"a\"b\\c"#);
+    }
+}
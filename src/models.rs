@@ -1,5 +1,9 @@
+use std::collections::BTreeMap;
+
 use chrono::{DateTime, Utc};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+
+use crate::error::ErrorKind;
 
 /// GET /v2/_catalog response
 #[derive(Debug, Deserialize)]
@@ -9,7 +13,6 @@ pub struct Catalog {
 
 /// GET /v2/<repo>/tags/list response
 #[derive(Debug, Deserialize)]
-#[allow(dead_code)]
 pub struct TagList {
     pub name: String,
     pub tags: Option<Vec<String>>,
@@ -17,17 +20,36 @@ pub struct TagList {
 
 /// GET /v2/<repo>/manifests/<tag> (schema v2)
 #[derive(Debug, Deserialize)]
-#[allow(dead_code)]
 pub struct Manifest {
     #[serde(rename = "schemaVersion")]
     pub schema_version: u32,
-    pub config: Option<ManifestConfig>,
+    pub config: Option<Descriptor>,
+    #[serde(default)]
+    pub layers: Vec<Descriptor>,
+    /// Child manifests of a manifest list / OCI image index
+    #[serde(default)]
+    pub manifests: Vec<Descriptor>,
+    /// Manifest this one refers to (OCI referrers: signatures, SBOMs, attestations)
+    pub subject: Option<Descriptor>,
 }
 
-#[derive(Debug, Deserialize)]
-#[allow(dead_code)]
-pub struct ManifestConfig {
-    #[serde(rename = "mediaType")]
+impl Manifest {
+    /// Total size of the config blob and all layers, if a config is present
+    pub fn total_size(&self) -> Option<u64> {
+        let config = self.config.as_ref()?;
+        Some(config.size + self.layers.iter().map(|l| l.size).sum::<u64>())
+    }
+
+    /// The config blob and all layers
+    pub fn blobs(&self) -> Vec<Descriptor> {
+        self.config.iter().chain(&self.layers).cloned().collect()
+    }
+}
+
+/// Content descriptor referencing a blob (config or layer)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Descriptor {
+    #[serde(rename = "mediaType", default)]
     pub media_type: String,
     pub size: u64,
     pub digest: String,
@@ -37,22 +59,135 @@ pub struct ManifestConfig {
 #[derive(Debug, Deserialize)]
 pub struct ImageConfig {
     pub created: Option<DateTime<Utc>>,
+    pub config: Option<ContainerConfig>,
+}
+
+impl ImageConfig {
+    pub fn labels(&self) -> BTreeMap<String, String> {
+        self.config
+            .as_ref()
+            .and_then(|c| c.labels.clone())
+            .unwrap_or_default()
+    }
+}
+
+/// The `config` section of an image config blob
+#[derive(Debug, Deserialize)]
+pub struct ContainerConfig {
+    #[serde(rename = "Labels")]
+    pub labels: Option<BTreeMap<String, String>>,
 }
 
 /// Internal struct combining tag metadata
-#[derive(Debug, Clone)]
-#[allow(dead_code)]
+#[derive(Debug, Clone, Serialize)]
 pub struct TagInfo {
     pub repository: String,
     pub tag: String,
     pub digest: String,
     pub created: Option<DateTime<Utc>>,
+    pub size: Option<u64>,
+    /// When the tag was last pushed, if the registry reports it
+    pub pushed: Option<DateTime<Utc>>,
+    /// When the image was last pulled, if known
+    pub last_pulled: Option<DateTime<Utc>>,
+    /// Config and layer blobs of the image; empty when the backend only reports a total size
+    #[serde(skip)]
+    pub blobs: Vec<Descriptor>,
+}
+
+/// A manifest stored in a repository, tagged or not, with its references to other manifests
+#[derive(Debug, Clone)]
+pub struct ManifestRevision {
+    pub digest: String,
+    pub tags: Vec<String>,
+    /// Digests of child manifests, if this is an index
+    pub children: Vec<String>,
+    /// Digest of the manifest this one refers to, if it is a referrer
+    pub subject: Option<String>,
+    pub size: Option<u64>,
 }
 
 /// Result of applying a cleanup strategy to a repository
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize)]
 pub struct CleanupPlan {
     pub repository: String,
     pub to_delete: Vec<TagInfo>,
     pub to_keep: Vec<TagInfo>,
 }
+
+/// Totals of a `clean` run
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct CleanReport {
+    pub deleted_tags: usize,
+    pub deleted_digests: usize,
+    pub kept_tags: usize,
+    pub errors: usize,
+    /// Size of the blobs of deleted digests that no kept manifest in the
+    /// registry references
+    pub deleted_bytes: u64,
+    /// Space freed by garbage collection, when it ran and could be measured
+    pub gc_reclaimed_bytes: Option<u64>,
+    /// Tags left in each processed repository, by registry location
+    pub repository_tags: BTreeMap<String, BTreeMap<String, usize>>,
+    pub dry_run: bool,
+    /// Set when the run stopped early because of a shutdown request
+    pub interrupted: bool,
+    pub failures: Vec<CleanupFailure>,
+    /// Plans of every processed repository
+    #[serde(skip)]
+    pub plans: Vec<CleanupPlan>,
+    /// Tags that were deleted (or would be, in a dry run)
+    #[serde(skip)]
+    pub deleted: Vec<TagInfo>,
+    /// Garbage collection result, when it ran
+    #[serde(skip)]
+    pub gc: Option<GcReport>,
+}
+
+impl CleanReport {
+    /// Add another registry's results, for a combined summary
+    pub fn merge(&mut self, other: CleanReport) {
+        self.deleted_tags += other.deleted_tags;
+        self.deleted_digests += other.deleted_digests;
+        self.kept_tags += other.kept_tags;
+        self.errors += other.errors;
+        self.deleted_bytes += other.deleted_bytes;
+        self.gc_reclaimed_bytes = match (self.gc_reclaimed_bytes, other.gc_reclaimed_bytes) {
+            (Some(a), Some(b)) => Some(a + b),
+            (a, b) => a.or(b),
+        };
+        for (registry, repos) in other.repository_tags {
+            self.repository_tags
+                .entry(registry)
+                .or_default()
+                .extend(repos);
+        }
+        self.dry_run |= other.dry_run;
+        self.interrupted |= other.interrupted;
+        self.failures.extend(other.failures);
+        self.plans.extend(other.plans);
+        self.deleted.extend(other.deleted);
+        self.gc = self.gc.take().or(other.gc);
+    }
+}
+
+/// An error that occurred during a `clean` run
+#[derive(Debug, Clone, Serialize)]
+pub struct CleanupFailure {
+    pub repository: Option<String>,
+    pub digest: Option<String>,
+    pub tags: Vec<String>,
+    pub kind: ErrorKind,
+    pub error: String,
+}
+
+/// Counts reported by the registry's `garbage-collect` command
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct GcReport {
+    pub blobs_marked: u64,
+    pub blobs_eligible: u64,
+    pub manifests_eligible: u64,
+    pub blobs_deleted: u64,
+    pub reclaimed_bytes: Option<u64>,
+    pub dry_run: bool,
+}
@@ -0,0 +1,390 @@
+use std::fs;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use minijinja::Environment;
+use reqwest::Client;
+use serde::Serialize;
+use serde_json::json;
+
+use regtidy::models::{CleanReport, CleanupFailure, CleanupPlan, TagInfo};
+
+use crate::cli::{NotifyArgs, NotifyOn, SmtpTls};
+use crate::output::format_bytes;
+
+/// Message body used unless --notify-template is given
+const DEFAULT_TEMPLATE: &str = r#"regtidy {{ "dry run" if dry_run else "cleanup" }} of {{ registry }} {{ status }}
+{%- if error %}
+Error: {{ error }}
+{%- endif %}
+{%- if report %}
+{{ "Would delete" if dry_run else "Deleted" }} {{ report.deleted_tags }} tags ({{ report.deleted_digests }} digests, {{ deleted_bytes }}), kept {{ report.kept_tags }}, {{ report.errors }} errors
+{%- if reclaimed_bytes %}
+Garbage collection reclaimed {{ reclaimed_bytes }}
+{%- endif %}
+{%- for tag in deleted[:50] %}
+  - {{ tag.repository }}:{{ tag.tag }} ({{ tag.digest[:19] }})
+{%- endfor %}
+{%- if deleted|length > 50 %}
+  ... and {{ deleted|length - 50 }} more
+{%- endif %}
+{%- for failure in failures %}
+  ! {{ failure.repository or "gc" }}{% if failure.tags %}:{{ failure.tags|join(",") }}{% endif %}: {{ failure.error }}
+{%- endfor %}
+{%- endif %}
+"#;
+
+/// Everything a notification template can refer to
+#[derive(Serialize)]
+struct TemplateContext<'a> {
+    registry: &'a str,
+    status: &'static str,
+    failed: bool,
+    dry_run: bool,
+    error: Option<String>,
+    report: Option<&'a CleanReport>,
+    plans: &'a [CleanupPlan],
+    deleted: &'a [TagInfo],
+    failures: &'a [CleanupFailure],
+    deleted_bytes: String,
+    reclaimed_bytes: Option<String>,
+}
+
+/// Send the configured notifications for a finished `clean` run.
+///
+/// Delivery problems are reported as warnings; they never fail the run.
+pub async fn notify(
+    args: &NotifyArgs,
+    registry: &str,
+    dry_run: bool,
+    outcome: &Result<CleanReport>,
+) {
+    if args.notify_webhook.is_empty()
+        && args.notify_slack.is_empty()
+        && args.notify_email.is_empty()
+    {
+        return;
+    }
+
+    let report = outcome.as_ref().ok();
+    let failed = report.is_none_or(|r| r.errors > 0);
+    if args.notify_on == NotifyOn::Failure && !failed {
+        tracing::debug!("Run succeeded, skipping notifications");
+        return;
+    }
+
+    let context = TemplateContext {
+        registry,
+        status: if failed { "failed" } else { "succeeded" },
+        failed,
+        dry_run: report.map_or(dry_run, |r| r.dry_run),
+        error: outcome.as_ref().err().map(|e| format!("{:#}", e)),
+        report,
+        plans: report.map_or(&[], |r| &r.plans),
+        deleted: report.map_or(&[], |r| &r.deleted),
+        failures: report.map_or(&[], |r| &r.failures),
+        deleted_bytes: format_bytes(report.map_or(0, |r| r.deleted_bytes)),
+        reclaimed_bytes: report.and_then(|r| r.gc_reclaimed_bytes).map(format_bytes),
+    };
+
+    let text = match render(args, &context) {
+        Ok(text) => text,
+        Err(e) => {
+            tracing::warn!("Failed to render notification: {:#}", e);
+            return;
+        }
+    };
+
+    let client = match Client::builder().timeout(Duration::from_secs(30)).build() {
+        Ok(client) => client,
+        Err(e) => {
+            tracing::warn!("Failed to create HTTP client: {}", e);
+            return;
+        }
+    };
+
+    let payload = json!({
+        "event": "clean_finished",
+        "registry": registry,
+        "status": context.status,
+        "dry_run": context.dry_run,
+        "error": context.error,
+        "report": report,
+        "text": text,
+    });
+    for url in &args.notify_webhook {
+        if let Err(e) = post_json(&client, url, &payload).await {
+            tracing::warn!("Webhook notification failed: {:#}", e);
+        } else {
+            tracing::debug!("Notified {}", redact_url(url));
+        }
+    }
+
+    // Slack and Mattermost incoming webhooks both accept {"text": ...}
+    let chat = json!({ "text": text });
+    for url in &args.notify_slack {
+        if let Err(e) = post_json(&client, url, &chat).await {
+            tracing::warn!("Chat notification failed: {:#}", e);
+        }
+    }
+
+    if !args.notify_email.is_empty() {
+        let subject = format!("regtidy: cleanup of {} {}", registry, context.status);
+        if let Err(e) = send_email(args, &subject, &text).await {
+            tracing::warn!("Email notification failed: {:#}", e);
+        }
+    }
+}
+
+fn render(args: &NotifyArgs, context: &TemplateContext) -> Result<String> {
+    let template = match &args.notify_template {
+        Some(path) => fs::read_to_string(path)
+            .with_context(|| format!("Failed to read template {}", path.display()))?,
+        None => DEFAULT_TEMPLATE.to_string(),
+    };
+    Environment::new()
+        .render_str(&template, context)
+        .context("Invalid notification template")
+}
+
+async fn post_json(client: &Client, url: &str, body: &serde_json::Value) -> Result<()> {
+    let what = format!("POST {}", redact_url(url));
+    let resp = client
+        .post(url)
+        .json(body)
+        .send()
+        .await
+        .map_err(|e| e.without_url())
+        .with_context(|| format!("Failed to {}", what))?;
+    regtidy::backend::check_status(resp, &what).await?;
+    Ok(())
+}
+
+/// Webhook URLs carry their secret in the path or query: only the scheme,
+/// host and port are fit for logs
+fn redact_url(url: &str) -> String {
+    match reqwest::Url::parse(url) {
+        Ok(parsed) => {
+            let host = parsed.host_str().unwrap_or_default();
+            match parsed.port() {
+                Some(port) => format!("{}://{}:{}/…", parsed.scheme(), host, port),
+                None => format!("{}://{}/…", parsed.scheme(), host),
+            }
+        }
+        Err(_) => "<invalid URL>".to_string(),
+    }
+}
+
+async fn send_email(args: &NotifyArgs, subject: &str, text: &str) -> Result<()> {
+    let server = args
+        .smtp_server
+        .as_deref()
+        .context("--notify-email requires --smtp-server")?;
+    let (host, port) = match server.rsplit_once(':') {
+        Some((host, port)) => (
+            host,
+            Some(port.parse::<u16>().context("Invalid SMTP port")?),
+        ),
+        None => (server, None),
+    };
+
+    let mut transport = match args.smtp_tls {
+        SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+        SmtpTls::Starttls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?,
+        SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host)?,
+    };
+    if let Some(port) = port {
+        transport = transport.port(port);
+    }
+    if let (Some(username), Some(password)) = (&args.smtp_username, &args.smtp_password) {
+        transport = transport.credentials(Credentials::new(username.clone(), password.clone()));
+    }
+
+    let mut message = Message::builder()
+        .from(
+            args.smtp_from
+                .parse::<Mailbox>()
+                .context("Invalid --smtp-from")?,
+        )
+        .subject(subject);
+    for to in &args.notify_email {
+        message = message.to(to
+            .parse::<Mailbox>()
+            .with_context(|| format!("Invalid address {}", to))?);
+    }
+    let message = message
+        .body(text.to_string())
+        .context("Failed to build email")?;
+
+    transport
+        .build()
+        .send(message)
+        .await
+        .with_context(|| format!("Failed to send email via {}", server))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::post;
+    use axum::{Json, Router};
+    use regtidy::error::ErrorKind;
+    use serde_json::Value;
+    use std::sync::{Arc, Mutex};
+
+    async fn serve(router: Router) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, router).await.unwrap();
+        });
+        format!("http://{}", addr)
+    }
+
+    fn args(url: &str, notify_on: NotifyOn) -> NotifyArgs {
+        NotifyArgs {
+            notify_webhook: vec![format!("{}/hook", url)],
+            notify_slack: vec![format!("{}/slack", url)],
+            notify_email: Vec::new(),
+            notify_on,
+            notify_template: None,
+            smtp_server: None,
+            smtp_tls: SmtpTls::None,
+            smtp_username: None,
+            smtp_password: None,
+            smtp_from: "regtidy@localhost".to_string(),
+        }
+    }
+
+    fn report() -> CleanReport {
+        let tag = TagInfo {
+            repository: "team/app".to_string(),
+            tag: "v1".to_string(),
+            digest: "sha256:0123456789abcdef0123".to_string(),
+            created: None,
+            size: Some(2048),
+            pushed: None,
+            last_pulled: None,
+            blobs: Vec::new(),
+        };
+        CleanReport {
+            deleted_tags: 1,
+            deleted_digests: 1,
+            kept_tags: 4,
+            errors: 1,
+            deleted_bytes: 2048,
+            deleted: vec![tag],
+            failures: vec![CleanupFailure {
+                repository: Some("team/app".to_string()),
+                digest: Some("sha256:bad".to_string()),
+                tags: vec!["v0".to_string()],
+                kind: ErrorKind::DeletionDisabled,
+                error: "DELETE returned 405".to_string(),
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn recorder(received: Arc<Mutex<Vec<(String, Value)>>>) -> Router {
+        let slack = received.clone();
+        Router::new()
+            .route(
+                "/hook",
+                post(move |Json(body): Json<Value>| async move {
+                    received.lock().unwrap().push(("hook".to_string(), body));
+                }),
+            )
+            .route(
+                "/slack",
+                post(move |Json(body): Json<Value>| async move {
+                    slack.lock().unwrap().push(("slack".to_string(), body));
+                }),
+            )
+    }
+
+    #[tokio::test]
+    async fn test_webhook_and_chat() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let url = serve(recorder(received.clone())).await;
+
+        let outcome = Ok(report());
+        notify(
+            &args(&url, NotifyOn::Failure),
+            "registry:5000",
+            false,
+            &outcome,
+        )
+        .await;
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 2);
+        let (_, hook) = &received[0];
+        assert_eq!(hook["status"], "failed");
+        assert_eq!(hook["report"]["deleted_tags"], 1);
+        assert_eq!(hook["report"]["failures"][0]["tags"][0], "v0");
+
+        let (_, chat) = &received[1];
+        let text = chat["text"].as_str().unwrap();
+        assert!(text.starts_with("regtidy cleanup of registry:5000 failed\n"));
+        assert!(text.contains("Deleted 1 tags (1 digests, 2.0 KiB), kept 4, 1 errors"));
+        assert!(text.contains("  - team/app:v1 (sha256:0123456789ab)"));
+        assert!(text.contains("  ! team/app:v0: DELETE returned 405"));
+    }
+
+    #[tokio::test]
+    async fn test_post_error_redacts_url() {
+        let url = serve(Router::new()).await;
+        let client = Client::new();
+
+        let err = post_json(&client, &format!("{}/hooks/s3cret", url), &json!({}))
+            .await
+            .unwrap_err();
+        let message = format!("{:#}", err);
+        assert!(message.contains(&format!("POST {}/…", url)));
+        assert!(!message.contains("s3cret"));
+
+        let err = post_json(&client, "http://127.0.0.1:1/hooks/s3cret", &json!({}))
+            .await
+            .unwrap_err();
+        assert!(!format!("{:#}", err).contains("s3cret"));
+    }
+
+    #[test]
+    fn test_redact_url() {
+        assert_eq!(
+            redact_url("https://hooks.slack.com/services/T0/B0/XyZ?token=1"),
+            "https://hooks.slack.com/…"
+        );
+        assert_eq!(
+            redact_url("http://user:pw@ci:8080/hook"),
+            "http://ci:8080/…"
+        );
+        assert_eq!(redact_url("not a url"), "<invalid URL>");
+    }
+
+    #[tokio::test]
+    async fn test_failure_only_mode() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let url = serve(recorder(received.clone())).await;
+
+        let mut ok = report();
+        ok.errors = 0;
+        ok.failures.clear();
+        notify(&args(&url, NotifyOn::Failure), "r", false, &Ok(ok)).await;
+        assert!(received.lock().unwrap().is_empty());
+
+        let outcome = Err(anyhow::anyhow!("catalog unavailable"));
+        notify(&args(&url, NotifyOn::Failure), "r", true, &outcome).await;
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 2);
+        assert_eq!(received[0].1["error"], "catalog unavailable");
+        assert_eq!(
+            received[1].1["text"],
+            "regtidy dry run of r failed\nError: catalog unavailable"
+        );
+    }
+}
@@ -1,10 +1,15 @@
 use colored::Colorize;
 
-use crate::models::{CleanupPlan, TagInfo};
+use regtidy::doctor::{Check, CheckStatus};
+use regtidy::models::{CleanReport, CleanupPlan, GcReport, ManifestRevision, TagInfo};
+use regtidy::untagged::{Protection, UntaggedPlan};
 
-/// Print a repository's tags (for the list subcommand)
-pub fn print_repo_tags(repo: &str, tags: &[TagInfo]) {
-    println!("\nRepository: {}", repo.bold());
+/// Print a repository's tags (for the list subcommand).
+/// `registry` labels the block when several registries are processed.
+pub fn print_repo_tags(registry: Option<&str>, repo: &str, tags: &[TagInfo]) {
+    // Hold stdout so blocks of registries processed in parallel don't interleave
+    let _stdout = std::io::stdout().lock();
+    println!("\n{}Repository: {}", registry_prefix(registry), repo.bold());
     println!("{}", "─".repeat(60));
 
     if tags.is_empty() {
@@ -19,7 +24,8 @@ pub fn print_repo_tags(repo: &str, tags: &[TagInfo]) {
 }
 
 /// Print the cleanup plan for a repository
-pub fn print_plan(plan: &CleanupPlan, dry_run: bool) {
+pub fn print_plan(registry: Option<&str>, plan: &CleanupPlan, dry_run: bool) {
+    let _stdout = std::io::stdout().lock();
     let header = if dry_run {
         format!(" {} ", "DRY RUN".yellow().bold())
     } else {
@@ -27,8 +33,9 @@ pub fn print_plan(plan: &CleanupPlan, dry_run: bool) {
     };
 
     println!(
-        "\n{}Repository: {}{}",
+        "\n{}{}Repository: {}{}",
         header,
+        registry_prefix(registry),
         plan.repository.bold(),
         if dry_run { " (no changes will be made)" } else { "" }
     );
@@ -53,12 +60,74 @@ pub fn print_plan(plan: &CleanupPlan, dry_run: bool) {
     }
 }
 
+/// Print the untagged-manifest plan for a repository
+pub fn print_untagged_plan(plan: &UntaggedPlan, dry_run: bool) {
+    let header = if dry_run {
+        format!(" {} ", "DRY RUN".yellow().bold())
+    } else {
+        String::new()
+    };
+
+    println!(
+        "\n{}Repository: {} ({} tagged manifests)",
+        header,
+        plan.repository.bold(),
+        plan.tagged
+    );
+    println!("{}", "─".repeat(60));
+
+    if !plan.to_delete.is_empty() {
+        println!("  {} ({}):", "UNTAGGED".red().bold(), plan.to_delete.len());
+        for rev in &plan.to_delete {
+            print_revision_line(rev, &"DELETE".red().bold().to_string(), "");
+        }
+    }
+
+    if !plan.protected.is_empty() {
+        println!("  {} ({}):", "PROTECTED".green().bold(), plan.protected.len());
+        for (rev, reason) in &plan.protected {
+            let reason = match reason {
+                Protection::IndexChild(index) => {
+                    format!("child of index {}", truncate_digest(index))
+                }
+                Protection::ReferrerSubject(referrer) => {
+                    format!("subject of referrer {}", truncate_digest(referrer))
+                }
+                Protection::Referrer(subject) => {
+                    format!("refers to {}", truncate_digest(subject))
+                }
+                Protection::SignatureTag(tag) => format!("signed by tag {}", tag),
+            };
+            print_revision_line(rev, &"  KEEP".green().bold().to_string(), &reason);
+        }
+    }
+
+    if plan.to_delete.is_empty() {
+        println!("  {}", "No untagged manifests to delete.".green());
+    }
+}
+
+fn print_revision_line(rev: &ManifestRevision, label: &str, note: &str) {
+    let size = rev.size.map(format_bytes).unwrap_or_default();
+    println!(
+        "    [{}] {:<30} {} {}",
+        label,
+        truncate_digest(&rev.digest),
+        size.dimmed(),
+        note.dimmed(),
+    );
+}
+
 fn print_tag_line(tag: &TagInfo, action: &str) {
     let digest_short = truncate_digest(&tag.digest);
     let created_str = match &tag.created {
         Some(dt) => dt.format("%Y-%m-%d %H:%M:%S UTC").to_string(),
         None => "unknown".to_string(),
     };
+    let pulled_str = match &tag.last_pulled {
+        Some(dt) => format!("pulled {}", dt.format("%Y-%m-%d")),
+        None => String::new(),
+    };
 
     let label = match action {
         "DELETE" => "DELETE".red().bold().to_string(),
@@ -68,14 +137,22 @@ fn print_tag_line(tag: &TagInfo, action: &str) {
     };
 
     println!(
-        "    [{}] {:<30} {} {}",
+        "    [{}] {:<30} {} {} {}",
         label,
         tag.tag,
         digest_short.dimmed(),
         created_str.dimmed(),
+        pulled_str.dimmed(),
     );
 }
 
+fn registry_prefix(registry: Option<&str>) -> String {
+    match registry {
+        Some(name) => format!("[{}] ", name.cyan()),
+        None => String::new(),
+    }
+}
+
 fn truncate_digest(digest: &str) -> &str {
     if digest.len() > 19 {
         &digest[..19]
@@ -85,41 +162,42 @@ fn truncate_digest(digest: &str) -> &str {
 }
 
 /// Print final summary
-pub fn print_summary(
-    deleted: usize,
-    unique_digests_deleted: usize,
-    kept: usize,
-    errors: usize,
-    dry_run: bool,
-) {
+pub fn print_summary(report: &CleanReport) {
+    let deleted = report.deleted_tags;
+    let errors = report.errors;
     println!("\n{}", "═".repeat(60));
-    if dry_run {
+    if report.dry_run {
         println!(
             "{} Would delete {} tags ({} unique digests), keep {} tags, {} errors",
             "DRY RUN SUMMARY:".yellow().bold(),
             deleted.to_string().red().bold(),
-            unique_digests_deleted,
-            kept.to_string().green().bold(),
+            report.deleted_digests,
+            report.kept_tags.to_string().green().bold(),
             if errors > 0 {
                 errors.to_string().red().bold().to_string()
             } else {
                 errors.to_string()
             }
         );
+        if let Some(gc) = &report.gc {
+            print_gc_report(gc);
+        }
     } else {
         println!(
             "{} Deleted {} tags ({} unique digests), kept {} tags, {} errors",
             "SUMMARY:".bold(),
             deleted.to_string().red().bold(),
-            unique_digests_deleted,
-            kept.to_string().green().bold(),
+            report.deleted_digests,
+            report.kept_tags.to_string().green().bold(),
             if errors > 0 {
                 errors.to_string().red().bold().to_string()
             } else {
                 errors.to_string()
             }
         );
-        if deleted > 0 {
+        if let Some(gc) = &report.gc {
+            print_gc_report(gc);
+        } else if deleted > 0 {
             println!(
                 "\n{} Run registry garbage collection to reclaim disk space:",
                 "REMINDER:".yellow().bold()
@@ -128,3 +206,88 @@ pub fn print_summary(
         }
     }
 }
+
+/// Print the final summary of an untagged-manifest cleanup
+pub fn print_untagged_summary(deleted: usize, protected: usize, errors: usize, dry_run: bool) {
+    println!("\n{}", "═".repeat(60));
+    let errors = if errors > 0 {
+        errors.to_string().red().bold().to_string()
+    } else {
+        errors.to_string()
+    };
+    if dry_run {
+        println!(
+            "{} Would delete {} untagged manifests, protect {}, {} errors",
+            "DRY RUN SUMMARY:".yellow().bold(),
+            deleted.to_string().red().bold(),
+            protected.to_string().green().bold(),
+            errors
+        );
+    } else {
+        println!(
+            "{} Deleted {} untagged manifests, protected {}, {} errors",
+            "SUMMARY:".bold(),
+            deleted.to_string().red().bold(),
+            protected.to_string().green().bold(),
+            errors
+        );
+    }
+}
+
+/// Print the result of a registry garbage collection run
+pub fn print_gc_report(report: &GcReport) {
+    let reclaimed = match report.reclaimed_bytes {
+        Some(bytes) => format_bytes(bytes),
+        None => "unknown".to_string(),
+    };
+
+    if report.dry_run {
+        println!(
+            "{} {} blobs marked, {} blobs and {} manifests eligible for deletion",
+            "GC DRY RUN:".yellow().bold(),
+            report.blobs_marked,
+            report.blobs_eligible.to_string().red().bold(),
+            report.manifests_eligible,
+        );
+    } else {
+        println!(
+            "{} {} blobs marked, {} blobs and {} manifests eligible, {} blobs deleted, {} reclaimed",
+            "GC:".bold(),
+            report.blobs_marked,
+            report.blobs_eligible,
+            report.manifests_eligible,
+            report.blobs_deleted.to_string().red().bold(),
+            reclaimed.green().bold(),
+        );
+    }
+}
+
+/// Print the preflight checks of one registry
+pub fn print_checks(location: &str, checks: &[Check]) {
+    println!("\n{}", location.bold());
+    println!("{}", "─".repeat(60));
+    for check in checks {
+        let status = match check.status {
+            CheckStatus::Ok => " OK ".green().bold(),
+            CheckStatus::Warn => "WARN".yellow().bold(),
+            CheckStatus::Fail => "FAIL".red().bold(),
+        };
+        println!("  [{}] {:<8} {}", status, check.name, check.detail);
+    }
+}
+
+/// Format a byte count with binary units (e.g. "1.5 GiB")
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
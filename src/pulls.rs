@@ -0,0 +1,274 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+const STORE_VERSION: u32 = 1;
+
+/// Last known pull and push of a manifest (or tag)
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Activity {
+    pub last_pull: Option<DateTime<Utc>>,
+    pub last_push: Option<DateTime<Utc>>,
+}
+
+impl Activity {
+    fn merge(&mut self, other: Activity) {
+        self.last_pull = self.last_pull.max(other.last_pull);
+        self.last_push = self.last_push.max(other.last_push);
+    }
+}
+
+/// Kind of registry access being recorded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Pull,
+    Push,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+struct RepoActivity {
+    #[serde(default)]
+    digests: HashMap<String, Activity>,
+    /// Accesses that only named a tag (e.g. from access logs)
+    #[serde(default)]
+    tags: HashMap<String, Activity>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoreFile {
+    version: u32,
+    /// Earliest access the store has recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    observed_since: Option<DateTime<Utc>>,
+    repositories: HashMap<String, RepoActivity>,
+}
+
+/// Local JSON index of the last pull/push per repository and digest
+#[derive(Debug)]
+pub struct PullStore {
+    path: PathBuf,
+    repositories: Mutex<HashMap<String, RepoActivity>>,
+    observed_since: Mutex<Option<DateTime<Utc>>>,
+}
+
+impl PullStore {
+    /// Default location: `$XDG_DATA_HOME/regtidy/pulls.json`
+    pub fn default_path() -> Result<PathBuf> {
+        let dir = dirs::data_dir().context("Could not determine data directory")?;
+        Ok(dir.join("regtidy").join("pulls.json"))
+    }
+
+    /// Open the store at `path`, or the default location
+    pub fn open_or_default(path: Option<&Path>) -> Result<Self> {
+        match path {
+            Some(path) => Self::open(path),
+            None => Self::open(&Self::default_path()?),
+        }
+    }
+
+    /// Load the store from `path`; a missing file yields an empty store
+    pub fn open(path: &Path) -> Result<Self> {
+        let (repositories, observed_since) = match fs::read(path) {
+            Ok(bytes) => {
+                let file: StoreFile = serde_json::from_slice(&bytes)
+                    .with_context(|| format!("Failed to parse {}", path.display()))?;
+                if file.version != STORE_VERSION {
+                    anyhow::bail!(
+                        "{} has unsupported version {}",
+                        path.display(),
+                        file.version
+                    );
+                }
+                // Stores written before observed_since was tracked start
+                // at their earliest recorded access
+                let observed_since = file.observed_since.or_else(|| {
+                    file.repositories
+                        .values()
+                        .flat_map(|r| r.digests.values().chain(r.tags.values()))
+                        .flat_map(|a| [a.last_pull, a.last_push])
+                        .flatten()
+                        .min()
+                });
+                (file.repositories, observed_since)
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => (HashMap::new(), None),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        };
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            repositories: Mutex::new(repositories),
+            observed_since: Mutex::new(observed_since),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Record an access by digest and/or tag; older timestamps never overwrite newer ones
+    pub fn record(
+        &self,
+        repo: &str,
+        digest: Option<&str>,
+        tag: Option<&str>,
+        access: Access,
+        at: DateTime<Utc>,
+    ) {
+        let activity = match access {
+            Access::Pull => Activity {
+                last_pull: Some(at),
+                last_push: None,
+            },
+            Access::Push => Activity {
+                last_pull: None,
+                last_push: Some(at),
+            },
+        };
+
+        let mut since = self.observed_since.lock().unwrap();
+        *since = Some(since.map_or(at, |since| since.min(at)));
+
+        let mut repos = self.repositories.lock().unwrap();
+        let entry = repos.entry(repo.to_string()).or_default();
+        if let Some(digest) = digest {
+            entry
+                .digests
+                .entry(digest.to_string())
+                .or_default()
+                .merge(activity);
+        }
+        if let Some(tag) = tag {
+            entry
+                .tags
+                .entry(tag.to_string())
+                .or_default()
+                .merge(activity);
+        }
+    }
+
+    /// Combined activity recorded for a tag, by its digest or its name
+    pub fn activity(&self, repo: &str, digest: &str, tag: &str) -> Activity {
+        let repos = self.repositories.lock().unwrap();
+        let mut activity = Activity::default();
+        if let Some(entry) = repos.get(repo) {
+            if let Some(a) = entry.digests.get(digest) {
+                activity.merge(*a);
+            }
+            if let Some(a) = entry.tags.get(tag) {
+                activity.merge(*a);
+            }
+        }
+        activity
+    }
+
+    /// Time since which accesses are recorded; a tag without activity was
+    /// only unused since then (`None` for an empty store)
+    pub fn observed_since(&self) -> Option<DateTime<Utc>> {
+        *self.observed_since.lock().unwrap()
+    }
+
+    /// Number of (repository, digest or tag) entries
+    pub fn len(&self) -> usize {
+        self.repositories
+            .lock()
+            .unwrap()
+            .values()
+            .map(|r| r.digests.len() + r.tags.len())
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+
+        let file = StoreFile {
+            version: STORE_VERSION,
+            observed_since: self.observed_since(),
+            repositories: self.repositories.lock().unwrap().clone(),
+        };
+        let json = serde_json::to_vec_pretty(&file).context("Failed to serialize pull store")?;
+
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, json).with_context(|| format!("Failed to write {}", tmp.display()))?;
+        fs::rename(&tmp, &self.path)
+            .with_context(|| format!("Failed to write {}", self.path.display()))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_record_keeps_latest() {
+        let store = PullStore::open(Path::new("/nonexistent/pulls.json")).unwrap();
+        let now = Utc::now();
+
+        store.record("app", Some("sha256:a"), Some("v1"), Access::Pull, now);
+        store.record(
+            "app",
+            Some("sha256:a"),
+            None,
+            Access::Pull,
+            now - Duration::days(3),
+        );
+        store.record(
+            "app",
+            Some("sha256:a"),
+            None,
+            Access::Push,
+            now - Duration::days(9),
+        );
+
+        let activity = store.activity("app", "sha256:a", "other");
+        assert_eq!(activity.last_pull, Some(now));
+        assert_eq!(activity.last_push, Some(now - Duration::days(9)));
+        assert_eq!(store.len(), 2);
+        assert_eq!(store.observed_since(), Some(now - Duration::days(9)));
+    }
+
+    #[test]
+    fn test_observed_since_persisted() {
+        let path = std::env::temp_dir().join(format!("regtidy-pulls-{}.json", std::process::id()));
+        let store = PullStore::open(&path).unwrap();
+        assert_eq!(store.observed_since(), None);
+
+        let at = Utc::now() - Duration::days(2);
+        store.record("app", Some("sha256:a"), None, Access::Pull, at);
+        store.save().unwrap();
+
+        assert_eq!(PullStore::open(&path).unwrap().observed_since(), Some(at));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_tag_only_access() {
+        let store = PullStore::open(Path::new("/nonexistent/pulls.json")).unwrap();
+        let now = Utc::now();
+        store.record("app", None, Some("latest"), Access::Pull, now);
+
+        assert_eq!(
+            store.activity("app", "sha256:x", "latest").last_pull,
+            Some(now)
+        );
+        assert_eq!(store.activity("app", "sha256:x", "v1").last_pull, None);
+        assert_eq!(
+            store.activity("other", "sha256:x", "latest").last_pull,
+            None
+        );
+    }
+}
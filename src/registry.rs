@@ -1,121 +1,166 @@
 use anyhow::{Context, Result};
+use async_trait::async_trait;
 use reqwest::header::{ACCEPT, LINK};
 use reqwest::Client;
+use serde::de::DeserializeOwned;
+use sha2::{Digest, Sha256, Sha512};
+use std::collections::HashSet;
 use std::sync::Arc;
 use tokio::sync::Semaphore;
 
-use crate::models::{Catalog, ImageConfig, Manifest, TagInfo, TagList};
-
-const MANIFEST_V2_MEDIA_TYPE: &str = "application/vnd.docker.distribution.manifest.v2+json";
-
+use crate::backend::{
+    check_status, http_client, send, Auth, RegistryBackend, Tls, DEFAULT_RETRIES,
+};
+use crate::cache::{CachedMetadata, MetadataCache};
+use crate::doctor::{self, Check, CheckStatus};
+use crate::error::ErrorKind;
+use crate::models::{Catalog, Descriptor, ImageConfig, Manifest, TagInfo, TagList};
+use crate::report::Reporter;
+
+/// Every manifest type regtidy understands. A registry serves a manifest
+/// list or index as is only if it is accepted; otherwise it substitutes a
+/// platform manifest or fails.
+const MANIFEST_ACCEPT: &str = "application/vnd.docker.distribution.manifest.v2+json, \
+    application/vnd.docker.distribution.manifest.list.v2+json, \
+    application/vnd.oci.image.manifest.v1+json, \
+    application/vnd.oci.image.index.v1+json";
+const DOCKER_CONTENT_DIGEST: &str = "Docker-Content-Digest";
+/// A digest no content hashes to, so deleting it can never remove anything
+const PROBE_DIGEST: &str =
+    "sha256:0000000000000000000000000000000000000000000000000000000000000000";
+
+#[derive(Clone)]
 pub struct RegistryClient {
     client: Client,
     base_url: String,
-    verbose: bool,
+    reporter: Arc<dyn Reporter>,
+    cache: Option<Arc<MetadataCache>>,
+    auth: Auth,
+    page_size: Option<u32>,
+    retries: u32,
 }
 
 impl RegistryClient {
-    pub fn new(base_url: &str, verbose: bool) -> Self {
+    pub fn new(base_url: &str, reporter: Arc<dyn Reporter>) -> Self {
         let base_url = base_url.trim_end_matches('/').to_string();
         Self {
             client: Client::new(),
             base_url,
-            verbose,
+            reporter,
+            cache: None,
+            auth: Auth::None,
+            page_size: None,
+            retries: DEFAULT_RETRIES,
         }
     }
 
-    /// GET /v2/_catalog with pagination
-    pub async fn list_repositories(&self) -> Result<Vec<String>> {
-        let mut repos = Vec::new();
-        let mut url = format!("{}/v2/_catalog", self.base_url);
+    /// Send `auth` with every request and apply the TLS settings
+    pub fn with_auth(mut self, auth: &Auth, tls: &Tls) -> Result<Self> {
+        self.client = http_client(auth, tls)?;
+        self.auth = auth.clone();
+        Ok(self)
+    }
 
-        loop {
-            if self.verbose {
-                eprintln!("[DEBUG] GET {}", url);
-            }
-            let resp = self
-                .client
-                .get(&url)
-                .send()
-                .await
-                .context("Failed to fetch catalog")?;
+    /// Use an on-disk metadata cache so already-seen digests only need a HEAD request
+    pub fn with_cache(mut self, cache: Arc<MetadataCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
 
-            let next_link = Self::parse_next_link(&resp);
+    /// Ask for pages of `n` repositories or tags instead of the registry's default
+    pub fn with_page_size(mut self, n: u32) -> Self {
+        self.page_size = Some(n);
+        self
+    }
 
-            let catalog: Catalog = resp.json().await.context("Failed to parse catalog JSON")?;
-            repos.extend(catalog.repositories);
+    /// Retry a GET or HEAD up to `n` times after a transient failure
+    pub fn with_retries(mut self, n: u32) -> Self {
+        self.retries = n;
+        self
+    }
 
-            match next_link {
-                Some(next) => url = self.resolve_url(&next),
-                None => break,
-            }
+    /// Persist the metadata cache, if one is configured
+    pub fn save_cache(&self) -> Result<()> {
+        match &self.cache {
+            Some(cache) => cache.save(),
+            None => Ok(()),
         }
+    }
 
-        Ok(repos)
+    /// GET /v2/_catalog with pagination
+    pub async fn list_repositories(&self) -> Result<Vec<String>> {
+        let pages: Vec<Catalog> = self.get_pages("/v2/_catalog", "catalog").await?;
+        Ok(pages.into_iter().flat_map(|c| c.repositories).collect())
     }
 
     /// GET /v2/<repo>/tags/list with pagination
     pub async fn list_tags(&self, repo: &str) -> Result<Vec<String>> {
-        let mut tags = Vec::new();
-        let mut url = format!("{}/v2/{}/tags/list", self.base_url, repo);
+        let path = format!("/v2/{}/tags/list", repo);
+        let what = format!("tags for {}", repo);
+        let pages: Vec<TagList> = self.get_pages(&path, &what).await?;
+        Ok(pages
+            .into_iter()
+            .flat_map(|t| t.tags.unwrap_or_default())
+            .collect())
+    }
+
+    /// GET every page of a list, following `rel="next"` links.
+    ///
+    /// Fails if a link leads back to a page already fetched, as a registry
+    /// that keeps returning the same `last` would otherwise be paged forever.
+    async fn get_pages<T: DeserializeOwned>(&self, path: &str, what: &str) -> Result<Vec<T>> {
+        let mut pages = Vec::new();
+        let mut url = match self.page_size {
+            Some(n) => format!("{}{}?n={}", self.base_url, path, n),
+            None => format!("{}{}", self.base_url, path),
+        };
+        let mut seen = HashSet::from([page_key(&url)]);
 
         loop {
-            if self.verbose {
-                eprintln!("[DEBUG] GET {}", url);
-            }
-            let resp = self
-                .client
-                .get(&url)
-                .send()
+            let resp = send(self.client.get(&url), self.retries)
                 .await
-                .with_context(|| format!("Failed to fetch tags for {}", repo))?;
+                .with_context(|| format!("Failed to fetch {}", what))?;
+            let resp = check_status(resp, &format!("GET {}", what)).await?;
 
-            let next_link = Self::parse_next_link(&resp);
+            let links = resp.headers().get_all(LINK);
+            let next = next_link(links.iter().filter_map(|v| v.to_str().ok()));
 
-            let tag_list: TagList = resp
+            let page = resp
                 .json()
                 .await
-                .with_context(|| format!("Failed to parse tag list for {}", repo))?;
-
-            if let Some(t) = tag_list.tags {
-                tags.extend(t);
-            }
-
-            match next_link {
-                Some(next) => url = self.resolve_url(&next),
-                None => break,
+                .with_context(|| format!("Failed to parse {}", what))?;
+            pages.push(page);
+
+            let Some(next) = next else {
+                break;
+            };
+            url = self.resolve_url(&next);
+            if !seen.insert(page_key(&url)) {
+                anyhow::bail!(
+                    "Pagination loop while listing {}: {} was returned as the next page again",
+                    what,
+                    url
+                );
             }
         }
 
-        Ok(tags)
+        Ok(pages)
     }
 
     /// HEAD /v2/<repo>/manifests/<tag> — extract Docker-Content-Digest header
     pub async fn get_digest(&self, repo: &str, tag: &str) -> Result<String> {
         let url = format!("{}/v2/{}/manifests/{}", self.base_url, repo, tag);
-        if self.verbose {
-            eprintln!("[DEBUG] HEAD {}", url);
-        }
-        let resp = self
-            .client
-            .head(&url)
-            .header(ACCEPT, MANIFEST_V2_MEDIA_TYPE)
-            .send()
-            .await
-            .with_context(|| format!("Failed to HEAD manifest for {}:{}", repo, tag))?;
-
-        let status = resp.status();
-        if !status.is_success() {
-            anyhow::bail!(
-                "HEAD manifest for {}:{} returned status {}",
-                repo,
-                tag,
-                status
-            );
-        }
+        let resp = send(
+            self.client.head(&url).header(ACCEPT, MANIFEST_ACCEPT),
+            self.retries,
+        )
+        .await
+        .with_context(|| format!("Failed to HEAD manifest for {}:{}", repo, tag))?;
+
+        let resp = check_status(resp, &format!("HEAD manifest for {}:{}", repo, tag)).await?;
 
         resp.headers()
-            .get("Docker-Content-Digest")
+            .get(DOCKER_CONTENT_DIGEST)
             .and_then(|v| v.to_str().ok())
             .map(|s| s.to_string())
             .with_context(|| {
@@ -126,105 +171,185 @@ impl RegistryClient {
             })
     }
 
-    /// GET /v2/<repo>/manifests/<tag> — parse manifest JSON
-    pub async fn get_manifest(&self, repo: &str, tag: &str) -> Result<Manifest> {
+    /// GET /v2/<repo>/manifests/<tag> — parse manifest JSON and determine its digest.
+    ///
+    /// The digest is taken from the Docker-Content-Digest header and verified against
+    /// the body; if a proxy stripped the header, it is computed from the body instead.
+    pub async fn get_manifest(&self, repo: &str, tag: &str) -> Result<(String, Manifest)> {
         let url = format!("{}/v2/{}/manifests/{}", self.base_url, repo, tag);
-        if self.verbose {
-            eprintln!("[DEBUG] GET {}", url);
-        }
-        let resp = self
-            .client
-            .get(&url)
-            .header(ACCEPT, MANIFEST_V2_MEDIA_TYPE)
-            .send()
-            .await
-            .with_context(|| format!("Failed to GET manifest for {}:{}", repo, tag))?;
-
-        let status = resp.status();
-        if !status.is_success() {
-            anyhow::bail!(
-                "GET manifest for {}:{} returned status {}",
-                repo,
-                tag,
-                status
-            );
-        }
+        let resp = send(
+            self.client.get(&url).header(ACCEPT, MANIFEST_ACCEPT),
+            self.retries,
+        )
+        .await
+        .with_context(|| format!("Failed to GET manifest for {}:{}", repo, tag))?;
+
+        let resp = check_status(resp, &format!("GET manifest for {}:{}", repo, tag)).await?;
+
+        let header_digest = resp
+            .headers()
+            .get(DOCKER_CONTENT_DIGEST)
+            .and_then(|v| v.to_str().ok())
+            .map(|s| s.to_string());
 
-        resp.json()
+        let body = resp
+            .bytes()
             .await
-            .with_context(|| format!("Failed to parse manifest for {}:{}", repo, tag))
+            .with_context(|| format!("Failed to read manifest for {}:{}", repo, tag))?;
+
+        let digest = match header_digest {
+            Some(expected) => {
+                verify_digest(&expected, &body)
+                    .with_context(|| format!("Manifest for {}:{} failed verification", repo, tag))?;
+                expected
+            }
+            None => {
+                self.reporter.debug(format!(
+                    "No {} header for {}:{}, computing digest from body",
+                    DOCKER_CONTENT_DIGEST, repo, tag
+                ));
+                sha256_digest(&body)
+            }
+        };
+
+        let manifest = serde_json::from_slice(&body)
+            .with_context(|| format!("Failed to parse manifest for {}:{}", repo, tag))?;
+
+        Ok((digest, manifest))
     }
 
     /// GET /v2/<repo>/blobs/<config_digest> — parse created timestamp
     pub async fn get_image_config(&self, repo: &str, config_digest: &str) -> Result<ImageConfig> {
         let url = format!("{}/v2/{}/blobs/{}", self.base_url, repo, config_digest);
-        if self.verbose {
-            eprintln!("[DEBUG] GET {}", url);
-        }
-        let resp = self
-            .client
-            .get(&url)
-            .send()
+        let resp = send(self.client.get(&url), self.retries)
             .await
             .with_context(|| format!("Failed to GET blob {} for {}", config_digest, repo))?;
 
-        let status = resp.status();
-        if !status.is_success() {
-            anyhow::bail!(
-                "GET blob {} for {} returned status {}",
-                config_digest,
-                repo,
-                status
-            );
-        }
+        let what = format!("GET blob {} for {}", config_digest, repo);
+        let resp = check_status(resp, &what).await?;
 
         resp.json()
             .await
             .with_context(|| format!("Failed to parse image config for {}", repo))
     }
 
-    /// DELETE /v2/<repo>/manifests/<digest>
-    pub async fn delete_manifest(&self, repo: &str, digest: &str) -> Result<()> {
+    /// DELETE /v2/<repo>/manifests/<digest>, returning the response status
+    pub async fn delete_manifest(&self, repo: &str, digest: &str) -> Result<u16> {
         let url = format!("{}/v2/{}/manifests/{}", self.base_url, repo, digest);
-        if self.verbose {
-            eprintln!("[DEBUG] DELETE {}", url);
+        let resp = send(
+            self.client.delete(&url).header(ACCEPT, MANIFEST_ACCEPT),
+            self.retries,
+        )
+        .await
+        .with_context(|| format!("Failed to DELETE manifest {} for {}", digest, repo))?;
+
+        let resp = check_status(resp, &format!("DELETE manifest {} for {}", digest, repo)).await?;
+        Ok(resp.status().as_u16())
+    }
+
+    /// Preflight checks: `/v2/` and its API version, the accepted credentials,
+    /// the catalog, whether deletes are enabled, and clock skew.
+    ///
+    /// Deletion is probed by deleting a manifest digest that cannot exist, in
+    /// `repo` or the first catalog repository.
+    pub async fn diagnose(&self, repo: Option<&str>) -> Vec<Check> {
+        let url = format!("{}/v2/", self.base_url);
+        let resp = match send(self.client.get(&url), self.retries).await {
+            Ok(resp) => resp,
+            Err(e) => {
+                let detail = format!("{} is unreachable: {:#}", url, anyhow::Error::from(e));
+                return vec![Check::fail("api", detail, ErrorKind::Network)];
+            }
+        };
+        let header = |name: &str| {
+            resp.headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(String::from)
+        };
+        let date = header("Date");
+        let api = doctor::api_check(
+            resp.status().as_u16(),
+            header("Docker-Distribution-API-Version").as_deref(),
+            header("WWW-Authenticate").as_deref(),
+        );
+        if api.status == CheckStatus::Fail {
+            return vec![api];
         }
-        let resp = self
-            .client
-            .delete(&url)
-            .header(ACCEPT, MANIFEST_V2_MEDIA_TYPE)
-            .send()
+        let mut checks = vec![api, doctor::auth_check(&self.auth)];
+
+        let catalog = self.first_repository().await;
+        checks.push(match &catalog {
+            Ok(_) => Check::ok("catalog", "available"),
+            Err(e) => Check::warn("catalog", format!("unavailable, pass --repo ({:#})", e)),
+        });
+
+        let probe_repo = repo
+            .map(String::from)
+            .or(catalog.ok().flatten())
+            .unwrap_or_else(|| "regtidy-preflight".to_string());
+        let probe = self
+            .delete_manifest(&probe_repo, PROBE_DIGEST)
             .await
-            .with_context(|| format!("Failed to DELETE manifest {} for {}", digest, repo))?;
-
-        let status = resp.status();
-        if !status.is_success() {
-            anyhow::bail!(
-                "DELETE manifest {} for {} returned status {}",
-                digest,
-                repo,
-                status
-            );
-        }
+            .map(|_| ());
+        checks.push(doctor::delete_check(&probe_repo, &probe));
+
+        checks.push(doctor::clock_check(date.as_deref(), chrono::Utc::now()));
+        checks
+    }
 
-        Ok(())
+    /// GET /v2/_catalog?n=1 — the first repository, if any
+    async fn first_repository(&self) -> Result<Option<String>> {
+        let url = format!("{}/v2/_catalog?n=1", self.base_url);
+        let resp = send(self.client.get(&url), self.retries)
+            .await
+            .context("Failed to fetch catalog")?;
+        let resp = check_status(resp, "GET catalog").await?;
+        let catalog: Catalog = resp.json().await.context("Failed to parse catalog JSON")?;
+        Ok(catalog.repositories.into_iter().next())
     }
 
     /// Resolve a single tag into TagInfo (digest + created timestamp)
     pub async fn resolve_tag_info(&self, repo: &str, tag: &str) -> Result<TagInfo> {
-        let digest = self.get_digest(repo, tag).await?;
-        let manifest = self.get_manifest(repo, tag).await?;
+        // With a cache, a HEAD is enough to learn whether the manifest is already known.
+        // Without one, the GET below yields the digest as well, so the HEAD is skipped.
+        if let Some(cache) = &self.cache {
+            let digest = self.get_digest(repo, tag).await?;
+            if let Some(meta) = cache.get(&digest) {
+                self.reporter
+                    .debug(format!("Cache hit for {}:{} ({})", repo, tag, digest));
+                return Ok(TagInfo {
+                    repository: repo.to_string(),
+                    tag: tag.to_string(),
+                    digest,
+                    created: meta.created,
+                    size: meta.size,
+                    pushed: None,
+                    last_pulled: None,
+                    blobs: meta.blobs,
+                });
+            }
+        }
 
-        let created = if let Some(config) = &manifest.config {
+        let (digest, manifest) = self.get_manifest(repo, tag).await?;
+        let (config, size, blobs) = if manifest.config.is_none() && !manifest.manifests.is_empty() {
+            self.resolve_children(repo, tag, &manifest).await
+        } else {
+            (
+                manifest.config.clone(),
+                manifest.total_size(),
+                manifest.blobs(),
+            )
+        };
+
+        let img_config = if let Some(config) = &config {
             match self.get_image_config(repo, &config.digest).await {
-                Ok(img_config) => img_config.created,
+                Ok(img_config) => Some(img_config),
                 Err(e) => {
-                    if self.verbose {
-                        eprintln!(
-                            "[WARN] Could not fetch image config for {}:{}: {}",
-                            repo, tag, e
-                        );
-                    }
+                    self.reporter.debug(format!(
+                        "Could not fetch image config for {}:{}: {}",
+                        repo, tag, e
+                    ));
                     None
                 }
             }
@@ -232,20 +357,77 @@ impl RegistryClient {
             None
         };
 
+        let created = img_config.as_ref().and_then(|c| c.created);
+
+        // Only cache complete results so a transient blob failure is retried next run
+        if let (Some(cache), Some(img_config)) = (&self.cache, &img_config) {
+            cache.insert(
+                &digest,
+                CachedMetadata {
+                    config_digest: config.as_ref().map(|c| c.digest.clone()),
+                    created,
+                    size,
+                    blobs: blobs.clone(),
+                    labels: img_config.labels(),
+                    last_used: chrono::Utc::now(),
+                },
+            );
+        }
+
         Ok(TagInfo {
             repository: repo.to_string(),
             tag: tag.to_string(),
             digest,
             created,
+            size,
+            pushed: None,
+            last_pulled: None,
+            blobs,
         })
     }
 
+    /// Config, total size and blobs of an index or manifest list, which has
+    /// none of its own: dated by its first platform image and sized by all
+    /// of them. The size is unknown if any platform image cannot be read.
+    async fn resolve_children(
+        &self,
+        repo: &str,
+        tag: &str,
+        index: &Manifest,
+    ) -> (Option<Descriptor>, Option<u64>, Vec<Descriptor>) {
+        let mut config = None;
+        let mut size = Some(0);
+        let mut blobs: Vec<Descriptor> = Vec::new();
+        for child in &index.manifests {
+            let manifest = match self.get_manifest(repo, &child.digest).await {
+                Ok((_, manifest)) => manifest,
+                Err(e) => {
+                    self.reporter.debug(format!(
+                        "Could not fetch platform manifest {} of {}:{}: {}",
+                        child.digest, repo, tag, e
+                    ));
+                    size = None;
+                    continue;
+                }
+            };
+            config = config.or_else(|| manifest.config.clone());
+            size = size.zip(manifest.total_size()).map(|(a, b)| a + b);
+            for blob in manifest.blobs() {
+                if !blobs.iter().any(|b| b.digest == blob.digest) {
+                    blobs.push(blob);
+                }
+            }
+        }
+        (config, size, blobs)
+    }
+
     /// Resolve all tags in a repo with bounded concurrency
     pub async fn resolve_all_tags(&self, repo: &str) -> Result<Vec<TagInfo>> {
         let tags = self.list_tags(repo).await?;
         if tags.is_empty() {
             return Ok(Vec::new());
         }
+        self.reporter.tags_listed(repo, tags.len());
 
         let semaphore = Arc::new(Semaphore::new(10));
         let mut handles = Vec::with_capacity(tags.len());
@@ -254,18 +436,14 @@ impl RegistryClient {
             let permit = semaphore.clone().acquire_owned().await.unwrap();
             let repo = repo.to_string();
             let tag = tag.clone();
-            let client = self.client.clone();
-            let base_url = self.base_url.clone();
-            let verbose = self.verbose;
+            let rc = self.clone();
 
             handles.push(tokio::spawn(async move {
-                let rc = RegistryClient {
-                    client,
-                    base_url,
-                    verbose,
-                };
                 let result = rc.resolve_tag_info(&repo, &tag).await;
                 drop(permit);
+                if let Ok(info) = &result {
+                    rc.reporter.tag_resolved(info);
+                }
                 (tag, result)
             }));
         }
@@ -276,7 +454,8 @@ impl RegistryClient {
             match result {
                 Ok(info) => infos.push(info),
                 Err(e) => {
-                    eprintln!("[ERROR] Failed to resolve {}:{}: {}", repo, tag, e);
+                    self.reporter
+                        .error(format!("Failed to resolve {}:{}: {}", repo, tag, e));
                 }
             }
         }
@@ -284,19 +463,6 @@ impl RegistryClient {
         Ok(infos)
     }
 
-    /// Parse the Link header for pagination (next URL)
-    fn parse_next_link(resp: &reqwest::Response) -> Option<String> {
-        let link = resp.headers().get(LINK)?.to_str().ok()?;
-        // Link: </v2/_catalog?n=100&last=xxx>; rel="next"
-        if link.contains("rel=\"next\"") {
-            let start = link.find('<')? + 1;
-            let end = link.find('>')?;
-            Some(link[start..end].to_string())
-        } else {
-            None
-        }
-    }
-
     /// Resolve a relative URL path against the base URL
     fn resolve_url(&self, path: &str) -> String {
         if path.starts_with("http://") || path.starts_with("https://") {
@@ -307,28 +473,351 @@ impl RegistryClient {
     }
 }
 
+#[async_trait]
+impl RegistryBackend for RegistryClient {
+    fn location(&self) -> String {
+        self.base_url.clone()
+    }
+
+    async fn list_repositories(&self) -> Result<Vec<String>> {
+        RegistryClient::list_repositories(self).await
+    }
+
+    async fn list_tags(&self, repo: &str) -> Result<Vec<String>> {
+        RegistryClient::list_tags(self, repo).await
+    }
+
+    async fn resolve_all_tags(&self, repo: &str) -> Result<Vec<TagInfo>> {
+        RegistryClient::resolve_all_tags(self, repo).await
+    }
+
+    async fn delete_manifest(&self, repo: &str, digest: &str) -> Result<u16> {
+        RegistryClient::delete_manifest(self, repo, digest).await
+    }
+
+    async fn diagnose(&self, repo: Option<&str>) -> Vec<Check> {
+        RegistryClient::diagnose(self, repo).await
+    }
+
+    fn save_cache(&self) -> Result<()> {
+        RegistryClient::save_cache(self)
+    }
+}
+
+/// The target of the first `rel="next"` link among `Link` header values
+pub(crate) fn next_link<'a>(values: impl IntoIterator<Item = &'a str>) -> Option<String> {
+    values
+        .into_iter()
+        .flat_map(parse_links)
+        .find(|link| link.rels.iter().any(|r| r.eq_ignore_ascii_case("next")))
+        .map(|link| link.target)
+}
+
+/// One link of a `Link` header
+#[derive(Debug, PartialEq)]
+struct Link {
+    target: String,
+    /// Relation types of the `rel` parameter
+    rels: Vec<String>,
+}
+
+/// Parse a `Link` header value (RFC 8288): comma-separated `<target>`s, each
+/// followed by `; name=value` parameters with token or quoted-string values.
+/// Malformed links are skipped.
+fn parse_links(value: &str) -> Vec<Link> {
+    let mut links = Vec::new();
+    let mut rest = value;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c == ',' || c.is_whitespace());
+        if rest.is_empty() {
+            break;
+        }
+        let Some((target, after)) = rest.strip_prefix('<').and_then(|r| r.split_once('>')) else {
+            // Not a link, skip to the next one
+            rest = rest.find(',').map_or("", |i| &rest[i..]);
+            continue;
+        };
+        rest = after;
+
+        let mut rels = None;
+        while let Some(params) = rest.trim_start().strip_prefix(';') {
+            let (name, value, after) = parse_param(params);
+            rest = after;
+            // Only the first rel parameter counts
+            if name.eq_ignore_ascii_case("rel") && rels.is_none() {
+                rels = Some(value.split_whitespace().map(String::from).collect());
+            }
+        }
+        links.push(Link {
+            target: target.trim().to_string(),
+            rels: rels.unwrap_or_default(),
+        });
+
+        // Skip anything unparseable up to the next link
+        rest = rest.find(',').map_or("", |i| &rest[i..]);
+    }
+    links
+}
+
+/// Parse `name[=value]` at the start of `s`; returns the name, the unquoted
+/// value and the remainder from the next `;` or `,`
+fn parse_param(s: &str) -> (&str, String, &str) {
+    let s = s.trim_start();
+    let name_end = s.find(['=', ';', ',']).unwrap_or(s.len());
+    let name = s[..name_end].trim();
+    let Some(value) = s[name_end..].strip_prefix('=') else {
+        return (name, String::new(), &s[name_end..]);
+    };
+    let value = value.trim_start();
+
+    let Some(quoted) = value.strip_prefix('"') else {
+        let end = value.find([';', ',']).unwrap_or(value.len());
+        return (name, value[..end].trim().to_string(), &value[end..]);
+    };
+    let mut unquoted = String::new();
+    let mut chars = quoted.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return (name, unquoted, &quoted[i + 1..]),
+            '\\' => unquoted.extend(chars.next().map(|(_, c)| c)),
+            c => unquoted.push(c),
+        }
+    }
+    // Unterminated quoted string
+    (name, unquoted, "")
+}
+
+/// What identifies a page: its `last` parameter, or else the whole URL
+fn page_key(url: &str) -> String {
+    reqwest::Url::parse(url)
+        .ok()
+        .and_then(|u| {
+            u.query_pairs()
+                .find(|(k, _)| k == "last")
+                .map(|(_, v)| v.into_owned())
+        })
+        .unwrap_or_else(|| url.to_string())
+}
+
+/// Compute the canonical sha256 digest string of a manifest body
+fn sha256_digest(body: &[u8]) -> String {
+    format!("sha256:{:x}", Sha256::digest(body))
+}
+
+/// Check that `body` hashes to `expected`. Digests using an algorithm we
+/// cannot compute are accepted as-is.
+fn verify_digest(expected: &str, body: &[u8]) -> Result<()> {
+    let computed = match expected.split_once(':') {
+        Some(("sha256", _)) => sha256_digest(body),
+        Some(("sha512", _)) => format!("sha512:{:x}", Sha512::digest(body)),
+        _ => return Ok(()),
+    };
+
+    if computed != expected {
+        anyhow::bail!(
+            "digest mismatch: header says {}, body hashes to {}",
+            expected,
+            computed
+        );
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::report::Silent;
+
+    #[test]
+    fn test_sha256_digest() {
+        assert_eq!(
+            sha256_digest(b"hello"),
+            "sha256:2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+    }
+
+    #[test]
+    fn test_verify_digest() {
+        let body = b"hello";
+        assert!(verify_digest(&sha256_digest(body), body).is_ok());
+        assert!(verify_digest(&sha256_digest(b"tampered"), body).is_err());
+        // Unknown algorithms cannot be checked and are trusted
+        assert!(verify_digest("blake3:abcd", body).is_ok());
+    }
 
     #[test]
     fn test_resolve_url_relative() {
-        let client = RegistryClient::new("http://localhost:5000", false);
+        let client = RegistryClient::new("http://localhost:5000", Arc::new(Silent));
         let resolved = client.resolve_url("/v2/_catalog?n=100&last=foo");
         assert_eq!(resolved, "http://localhost:5000/v2/_catalog?n=100&last=foo");
     }
 
     #[test]
     fn test_resolve_url_absolute() {
-        let client = RegistryClient::new("http://localhost:5000", false);
+        let client = RegistryClient::new("http://localhost:5000", Arc::new(Silent));
         let resolved = client.resolve_url("http://other:5000/v2/_catalog?n=100");
         assert_eq!(resolved, "http://other:5000/v2/_catalog?n=100");
     }
 
     #[test]
     fn test_resolve_url_strips_trailing_slash() {
-        let client = RegistryClient::new("http://localhost:5000/", false);
+        let client = RegistryClient::new("http://localhost:5000/", Arc::new(Silent));
         let resolved = client.resolve_url("/v2/_catalog");
         assert_eq!(resolved, "http://localhost:5000/v2/_catalog");
     }
+
+    #[test]
+    fn test_parse_links() {
+        assert_eq!(
+            parse_links(r#"</v2/_catalog?n=2&last=b>; rel="next""#),
+            vec![Link {
+                target: "/v2/_catalog?n=2&last=b".to_string(),
+                rels: vec!["next".to_string()],
+            }]
+        );
+
+        // Commas and semicolons inside targets and quoted strings, several
+        // relation types, unquoted values and repeated rel parameters
+        let links = parse_links(
+            r#"<https://r/v2/_catalog?a=1,2;x>; title="p; q, \"r\""; rel="first prev", </v2/_catalog?last=c>;REL=next;rel=last"#,
+        );
+        assert_eq!(links.len(), 2);
+        assert_eq!(links[0].target, "https://r/v2/_catalog?a=1,2;x");
+        assert_eq!(links[0].rels, vec!["first", "prev"]);
+        assert_eq!(links[1].rels, vec!["next"]);
+
+        assert!(parse_links("garbage").is_empty());
+        let links = parse_links("</a>; rel=next, b>; rel=prev, <c");
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].target, "/a");
+    }
+
+    #[test]
+    fn test_next_link() {
+        let values = [
+            r#"</v2/_catalog?n=1>; rel="first""#,
+            r#"</v2/_catalog?n=1&last=a>; rel=prev, </v2/_catalog?n=1&last=c>; rel="NEXT""#,
+        ];
+        assert_eq!(
+            next_link(values).as_deref(),
+            Some("/v2/_catalog?n=1&last=c")
+        );
+        assert_eq!(next_link([r#"</v2/_catalog>; rel="nextpage""#]), None);
+        assert_eq!(next_link([]), None);
+    }
+
+    #[test]
+    fn test_page_key() {
+        assert_eq!(
+            page_key("http://r/v2/_catalog?n=5&last=team%2Fapp"),
+            "team/app"
+        );
+        assert_eq!(
+            page_key("http://r/v2/_catalog?page=2"),
+            "http://r/v2/_catalog?page=2"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pagination_loop() {
+        use crate::backend::testing::serve;
+        use axum::extract::RawQuery;
+        use axum::http::header;
+        use axum::routing::get;
+        use axum::{Json, Router};
+
+        // Ignores `last` and always links to the page after "b"
+        let router = Router::new().route(
+            "/v2/_catalog",
+            get(|RawQuery(query): RawQuery| async move {
+                let link = "</v2/_catalog?n=2&last=b>; rel=\"next\"";
+                let body = serde_json::json!({ "repositories": ["a", "b"], "query": query });
+                ([(header::LINK, link)], Json(body))
+            }),
+        );
+        let client = RegistryClient::new(&serve(router).await, Arc::new(Silent)).with_page_size(2);
+
+        let err = client.list_repositories().await.unwrap_err();
+        assert!(format!("{:#}", err).contains("Pagination loop while listing catalog"));
+    }
+
+    #[tokio::test]
+    async fn test_registry_errors() {
+        use crate::backend::testing::serve;
+        use crate::error::{AppError, ErrorKind};
+        use axum::http::{header, StatusCode};
+        use axum::routing::{delete, get};
+        use axum::Router;
+
+        let router = Router::new()
+            .route(
+                "/v2/_catalog",
+                get(|| async {
+                    (
+                        StatusCode::TOO_MANY_REQUESTS,
+                        [(header::RETRY_AFTER, "30")],
+                        r#"{"errors":[{"code":"TOOMANYREQUESTS","message":"slow down"}]}"#,
+                    )
+                }),
+            )
+            .route(
+                "/v2/app/manifests/{digest}",
+                delete(|| async {
+                    (
+                        StatusCode::METHOD_NOT_ALLOWED,
+                        r#"{"errors":[{"code":"UNSUPPORTED","message":"The operation is unsupported."}]}"#,
+                    )
+                }),
+            );
+        let client = RegistryClient::new(&serve(router).await, Arc::new(Silent));
+
+        let err = client.list_repositories().await.unwrap_err();
+        assert_eq!(ErrorKind::of(&err), ErrorKind::RateLimited);
+        let http = crate::error::http_error(&err).unwrap();
+        assert_eq!(http.retry_after, Some(30));
+
+        let err = client.delete_manifest("app", "sha256:a").await.unwrap_err();
+        assert_eq!(ErrorKind::of(&err), ErrorKind::DeletionDisabled);
+        assert!(matches!(
+            err.downcast_ref::<AppError>(),
+            Some(AppError::DeletionDisabled(e)) if e.errors[0].code == "UNSUPPORTED"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_diagnose_deletion_disabled() {
+        use crate::backend::testing::serve;
+        use axum::http::StatusCode;
+        use axum::routing::{delete, get};
+        use axum::Router;
+
+        let router = Router::new()
+            .route(
+                "/v2/",
+                get(|| async { ([("Docker-Distribution-API-Version", "registry/2.0")], "{}") }),
+            )
+            .route(
+                "/v2/_catalog",
+                get(|| async { r#"{"repositories":["app"]}"# }),
+            )
+            .route(
+                "/v2/app/manifests/{digest}",
+                delete(|| async {
+                    (
+                        StatusCode::METHOD_NOT_ALLOWED,
+                        r#"{"errors":[{"code":"UNSUPPORTED","message":"The operation is unsupported."}]}"#,
+                    )
+                }),
+            );
+        let client = RegistryClient::new(&serve(router).await, Arc::new(Silent));
+
+        let checks = client.diagnose(None).await;
+        let names: Vec<&str> = checks.iter().map(|c| c.name).collect();
+        assert_eq!(names, vec!["api", "auth", "catalog", "delete", "clock"]);
+        assert_eq!(checks[0].status, CheckStatus::Ok);
+        assert_eq!(checks[3].status, CheckStatus::Fail);
+        assert_eq!(checks[3].kind, Some(ErrorKind::DeletionDisabled));
+        assert!(checks[3].detail.contains("status 405 UNSUPPORTED"));
+    }
 }
@@ -0,0 +1,163 @@
+use serde::Serialize;
+
+use crate::models::{CleanupPlan, TagInfo};
+
+/// Something that happened while talking to a registry or running a cleanup
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// Work on a repository begins
+    RepositoryStarted {
+        repository: String,
+    },
+    /// The tags of a repository were listed; each is resolved next
+    TagsListed {
+        repository: String,
+        count: usize,
+    },
+    TagResolved {
+        repository: String,
+        tag: String,
+        digest: String,
+    },
+    /// Resolution of a repository finished, `tags` of its tags successfully
+    RepositoryResolved {
+        repository: String,
+        tags: usize,
+    },
+    /// The final plan of a repository, after shared digests were protected
+    PlanReady {
+        repository: String,
+        delete: usize,
+        keep: usize,
+    },
+    /// Deletion of `digests` manifests begins
+    DeletionStarted {
+        digests: usize,
+    },
+    DigestDeleted {
+        repository: String,
+        digest: String,
+        tags: Vec<String>,
+    },
+    /// Deletion ended, `failed` digests could not be deleted
+    DeletionFinished {
+        deleted: usize,
+        failed: usize,
+    },
+    /// Diagnostic detail, normally only shown in verbose mode
+    Debug {
+        message: String,
+    },
+    Warning {
+        message: String,
+    },
+    Error {
+        message: String,
+    },
+}
+
+/// Receives the events of library operations; library code never prints.
+///
+/// Implementations decide what to show and where, e.g. log lines on stderr
+/// or JSON for another program.
+pub trait Reporter: Send + Sync {
+    fn report(&self, event: Event);
+}
+
+impl dyn Reporter + '_ {
+    pub fn repository_started(&self, repository: &str) {
+        self.report(Event::RepositoryStarted {
+            repository: repository.to_string(),
+        });
+    }
+
+    pub fn tags_listed(&self, repository: &str, count: usize) {
+        self.report(Event::TagsListed {
+            repository: repository.to_string(),
+            count,
+        });
+    }
+
+    pub fn tag_resolved(&self, info: &TagInfo) {
+        self.report(Event::TagResolved {
+            repository: info.repository.clone(),
+            tag: info.tag.clone(),
+            digest: info.digest.clone(),
+        });
+    }
+
+    pub fn repository_resolved(&self, repository: &str, tags: usize) {
+        self.report(Event::RepositoryResolved {
+            repository: repository.to_string(),
+            tags,
+        });
+    }
+
+    pub fn plan_ready(&self, plan: &CleanupPlan) {
+        self.report(Event::PlanReady {
+            repository: plan.repository.clone(),
+            delete: plan.to_delete.len(),
+            keep: plan.to_keep.len(),
+        });
+    }
+
+    pub fn digest_deleted(&self, repository: &str, digest: &str, tags: &[String]) {
+        self.report(Event::DigestDeleted {
+            repository: repository.to_string(),
+            digest: digest.to_string(),
+            tags: tags.to_vec(),
+        });
+    }
+
+    pub fn debug(&self, message: impl Into<String>) {
+        self.report(Event::Debug {
+            message: message.into(),
+        });
+    }
+
+    pub fn warn(&self, message: impl Into<String>) {
+        self.report(Event::Warning {
+            message: message.into(),
+        });
+    }
+
+    pub fn error(&self, message: impl Into<String>) {
+        self.report(Event::Error {
+            message: message.into(),
+        });
+    }
+}
+
+/// Discards every event
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Silent;
+
+impl Reporter for Silent {
+    fn report(&self, _event: Event) {}
+}
+
+#[cfg(test)]
+pub(crate) mod testing {
+    use std::sync::Mutex;
+
+    use super::{Event, Reporter};
+
+    /// Keeps the names of the events it receives
+    #[derive(Default)]
+    pub struct Recorder(Mutex<Vec<String>>);
+
+    impl Recorder {
+        pub fn events(&self) -> Vec<String> {
+            self.0.lock().unwrap().clone()
+        }
+    }
+
+    impl Reporter for Recorder {
+        fn report(&self, event: Event) {
+            let json = serde_json::to_value(&event).unwrap();
+            let name = json["event"].as_str().unwrap().to_string();
+            self.0.lock().unwrap().push(name);
+        }
+    }
+}
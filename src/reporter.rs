@@ -0,0 +1,208 @@
+use std::io::IsTerminal;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::Utc;
+use indicatif::{ProgressBar, ProgressStyle};
+use serde_json::Value;
+use tracing::Level;
+
+use regtidy::report::{Event, Reporter};
+
+use crate::cli::Progress;
+
+/// The reporter selected by --progress; the bar is only shown by default
+/// when no more than the usual log lines are expected. Registries are
+/// processed concurrently and their events cannot share one bar, so
+/// several `targets` always get log lines.
+pub fn from_cli(progress: Progress, verbose: u8, quiet: bool, targets: usize) -> Arc<dyn Reporter> {
+    match progress {
+        Progress::Auto | Progress::Bar if targets > 1 => {
+            if progress == Progress::Bar {
+                tracing::warn!(
+                    "A progress bar shows a single registry; logging progress of {} registries instead",
+                    targets
+                );
+            }
+            Arc::new(LogReporter)
+        }
+        Progress::Auto if verbose == 0 && !quiet && std::io::stderr().is_terminal() => {
+            Arc::new(ProgressReporter::default())
+        }
+        Progress::Auto | Progress::Log => Arc::new(LogReporter),
+        Progress::Bar => Arc::new(ProgressReporter::default()),
+        Progress::Json => Arc::new(JsonReporter),
+    }
+}
+
+/// Whether the log line of an event passes the log filter
+fn is_logged(event: &Event) -> bool {
+    match event {
+        Event::Warning { .. } => tracing::enabled!(Level::WARN),
+        Event::Error { .. } => tracing::enabled!(Level::ERROR),
+        Event::Debug { .. } | Event::RepositoryStarted { .. } | Event::DigestDeleted { .. } => {
+            tracing::enabled!(Level::DEBUG)
+        }
+        _ => false,
+    }
+}
+
+/// Write the log line of an event, if it has one
+fn log(event: &Event) {
+    match event {
+        Event::Warning { message } => tracing::warn!("{}", message),
+        Event::Error { message } => tracing::error!("{}", message),
+        Event::Debug { message } => tracing::debug!("{}", message),
+        Event::RepositoryStarted { repository } => {
+            tracing::debug!("Processing repository: {}", repository)
+        }
+        Event::DigestDeleted { digest, .. } => tracing::debug!("Deleted digest {}", digest),
+        _ => {}
+    }
+}
+
+/// Events as log lines: warnings and errors, plus debug detail with -v
+pub struct LogReporter;
+
+impl Reporter for LogReporter {
+    fn report(&self, event: Event) {
+        log(&event);
+    }
+}
+
+/// A progress bar on stderr while tags are resolved and digests deleted.
+/// Log lines are printed above it.
+#[derive(Default)]
+pub struct ProgressReporter {
+    bar: Mutex<Option<ProgressBar>>,
+}
+
+impl ProgressReporter {
+    /// Replace the current bar; `len` is unknown for a spinner
+    fn start(&self, len: Option<u64>, message: String) {
+        let bar = match len {
+            Some(len) => ProgressBar::new(len).with_style(bar_style()),
+            None => ProgressBar::new_spinner().with_style(spinner_style()),
+        };
+        bar.set_message(message);
+        bar.enable_steady_tick(Duration::from_millis(100));
+        if let Some(old) = self.bar.lock().unwrap().replace(bar) {
+            old.finish_and_clear();
+        }
+    }
+
+    fn finish(&self) {
+        if let Some(bar) = self.bar.lock().unwrap().take() {
+            bar.finish_and_clear();
+        }
+    }
+}
+
+fn bar_style() -> ProgressStyle {
+    ProgressStyle::with_template("{spinner} {msg} [{bar:30}] {pos}/{len} ({elapsed})")
+        .unwrap()
+        .progress_chars("=> ")
+}
+
+fn spinner_style() -> ProgressStyle {
+    ProgressStyle::with_template("{spinner} {msg} {pos} ({elapsed})").unwrap()
+}
+
+impl Reporter for ProgressReporter {
+    fn report(&self, event: Event) {
+        match &event {
+            Event::RepositoryStarted { repository } => {
+                self.start(None, format!("Resolving {}", repository));
+            }
+            Event::TagsListed { count, .. } => {
+                if let Some(bar) = self.bar.lock().unwrap().as_ref() {
+                    bar.set_style(bar_style());
+                    bar.set_length(*count as u64);
+                }
+            }
+            Event::TagResolved { .. } | Event::DigestDeleted { .. } => {
+                if let Some(bar) = self.bar.lock().unwrap().as_ref() {
+                    bar.inc(1);
+                }
+            }
+            // Plans, tag lists and the summary are printed next
+            Event::RepositoryResolved { .. }
+            | Event::PlanReady { .. }
+            | Event::DeletionFinished { .. } => self.finish(),
+            Event::DeletionStarted { digests } => {
+                self.start(Some(*digests as u64), "Deleting digests".to_string());
+            }
+            _ => {}
+        }
+
+        if is_logged(&event) {
+            match self.bar.lock().unwrap().as_ref() {
+                Some(bar) => bar.suspend(|| log(&event)),
+                None => log(&event),
+            }
+        }
+    }
+}
+
+impl Drop for ProgressReporter {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+/// Every event as one JSON object per line on stderr, with a timestamp;
+/// debug detail only with -v
+pub struct JsonReporter;
+
+impl Reporter for JsonReporter {
+    fn report(&self, event: Event) {
+        if matches!(event, Event::Debug { .. }) && !tracing::enabled!(Level::DEBUG) {
+            return;
+        }
+        let Ok(Value::Object(fields)) = serde_json::to_value(&event) else {
+            return;
+        };
+        let mut record = serde_json::Map::new();
+        record.insert("timestamp".to_string(), Utc::now().to_rfc3339().into());
+        record.extend(fields);
+        eprintln!("{}", Value::Object(record));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logging::testing::capture;
+
+    #[test]
+    fn test_log_reporter() {
+        let events = || {
+            let reporter: &dyn Reporter = &LogReporter;
+            reporter.warn("slow");
+            reporter.repository_started("app");
+            reporter.report(Event::TagResolved {
+                repository: "app".to_string(),
+                tag: "v1".to_string(),
+                digest: "sha256:a".to_string(),
+            });
+        };
+        assert_eq!(capture("warn,regtidy=info", events), "[WARN] slow\n");
+        assert_eq!(
+            capture("warn,regtidy=debug", events),
+            "[WARN] slow\n[DEBUG] Processing repository: app\n"
+        );
+    }
+
+    #[test]
+    fn test_event_json() {
+        let event = Event::PlanReady {
+            repository: "app".to_string(),
+            delete: 2,
+            keep: 5,
+        };
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            serde_json::json!({"event": "plan_ready", "repository": "app", "delete": 2, "keep": 5})
+        );
+    }
+}
@@ -0,0 +1,55 @@
+use std::io::{BufRead, IsTerminal, Write};
+
+use anyhow::{Context, Result};
+use tokio::sync::Mutex;
+
+/// Serializes prompts of registries cleaned in parallel
+static PROMPT: Mutex<()> = Mutex::const_new(());
+
+/// Ask on the terminal whether to go ahead; anything but "y" or "yes" declines.
+/// Without a terminal there is nobody to ask, so the run is refused.
+pub async fn confirm(question: &str) -> Result<bool> {
+    let _prompt = PROMPT.lock().await;
+    if !std::io::stdin().is_terminal() {
+        anyhow::bail!("Refusing to delete without confirmation; pass --yes to run unattended");
+    }
+    tokio::task::block_in_place(|| {
+        print!("\n{} [y/N] ", question);
+        std::io::stdout().flush()?;
+        let mut answer = String::new();
+        std::io::stdin()
+            .lock()
+            .read_line(&mut answer)
+            .context("Failed to read confirmation")?;
+        Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+    })
+}
+
+/// `--max-repo-fraction` value: `0.5` or `50%`
+pub fn parse_fraction(s: &str) -> Result<f64, String> {
+    let value = match s.strip_suffix('%') {
+        Some(percent) => percent.trim().parse::<f64>().map(|p| p / 100.0),
+        None => s.trim().parse::<f64>(),
+    }
+    .map_err(|_| format!("invalid fraction '{}' (use e.g. 0.5 or 50%)", s))?;
+    if !(0.0..=1.0).contains(&value) {
+        return Err(format!(
+            "fraction '{}' must be between 0 and 1 (0% and 100%)",
+            s
+        ));
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_fraction() {
+        assert_eq!(parse_fraction("0.25"), Ok(0.25));
+        assert_eq!(parse_fraction("50%"), Ok(0.5));
+        assert!(parse_fraction("150%").is_err());
+        assert!(parse_fraction("half").is_err());
+    }
+}
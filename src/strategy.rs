@@ -3,46 +3,58 @@ use std::collections::HashSet;
 use chrono::Utc;
 use regex::Regex;
 
-use crate::cli::CleanArgs;
-use crate::error::AppError;
+use crate::index::DigestIndex;
 use crate::models::{CleanupPlan, TagInfo};
+use crate::pulls::PullStore;
+use crate::report::Reporter;
 
 #[derive(Debug)]
 pub enum Strategy {
     KeepRecent(usize),
     OlderThan(u64),
     Pattern(Regex),
+    /// Delete tags whose last known pull/push (or creation) is older than the duration
+    UnusedFor(chrono::Duration, PullStore),
 }
 
 impl Strategy {
-    /// Build a Strategy from clean subcommand arguments
-    pub fn from_args(args: &CleanArgs) -> Result<Self, AppError> {
-        if let Some(n) = args.keep {
-            Ok(Strategy::KeepRecent(n))
-        } else if let Some(days) = args.older_than {
-            Ok(Strategy::OlderThan(days))
-        } else if let Some(ref pat) = args.pattern {
-            let re = Regex::new(pat)?;
-            Ok(Strategy::Pattern(re))
-        } else {
-            Err(AppError::NoStrategy)
+    /// Short description of the rule, as recorded in the audit log
+    pub fn rule(&self) -> String {
+        match self {
+            Strategy::KeepRecent(n) => format!("keep={}", n),
+            Strategy::OlderThan(days) => format!("older-than={}d", days),
+            Strategy::Pattern(re) => format!("pattern={}", re.as_str()),
+            Strategy::UnusedFor(duration, _) => {
+                let secs = duration.num_seconds();
+                let unit = [(604_800, "w"), (86_400, "d"), (3_600, "h"), (60, "m")]
+                    .into_iter()
+                    .find(|(n, _)| secs % n == 0);
+                match unit {
+                    Some((n, suffix)) => format!("unused-for={}{}", secs / n, suffix),
+                    None => format!("unused-for={}s", secs),
+                }
+            }
         }
     }
 
     /// Apply the strategy to a list of tags and produce a CleanupPlan
-    pub fn apply(&self, repo: &str, mut tags: Vec<TagInfo>) -> CleanupPlan {
+    pub fn apply(
+        &self,
+        repo: &str,
+        mut tags: Vec<TagInfo>,
+        reporter: &dyn Reporter,
+    ) -> CleanupPlan {
         let (mut to_delete, mut to_keep) = match self {
             Strategy::KeepRecent(n) => {
-                // Sort by created descending; None sorts to end (deleted first)
-                tags.sort_by(|a, b| {
-                    let a_time = a.created.map(|t| t.timestamp()).unwrap_or(i64::MIN);
-                    let b_time = b.created.map(|t| t.timestamp()).unwrap_or(i64::MIN);
-                    b_time.cmp(&a_time)
-                });
-
-                let keep_count = (*n).min(tags.len());
-                let to_keep: Vec<TagInfo> = tags.drain(..keep_count).collect();
-                let to_delete = tags;
+                // Undated tags cannot be ranked; keep them rather than delete them first
+                let (mut dated, undated): (Vec<TagInfo>, Vec<TagInfo>) =
+                    tags.drain(..).partition(|t| t.created.is_some());
+                dated.sort_by_key(|t| std::cmp::Reverse(t.created));
+
+                let keep_count = (*n).min(dated.len());
+                let mut to_keep: Vec<TagInfo> = dated.drain(..keep_count).collect();
+                to_keep.extend(undated);
+                let to_delete = dated;
                 (to_delete, to_keep)
             }
             Strategy::OlderThan(days) => {
@@ -71,6 +83,25 @@ impl Strategy {
                     }
                 }
 
+                (to_delete, to_keep)
+            }
+            Strategy::UnusedFor(duration, store) => {
+                let cutoff = Utc::now() - *duration;
+                // Missing pulls only prove disuse once the store has been
+                // recording for the whole period, or the registry tracks pulls
+                let observed = store.observed_since().is_some_and(|since| since <= cutoff);
+                let mut to_delete = Vec::new();
+                let mut to_keep = Vec::new();
+
+                for tag in tags {
+                    match last_used(&tag, store) {
+                        Some(used) if used < cutoff && (observed || tag.last_pulled.is_some()) => {
+                            to_delete.push(tag)
+                        }
+                        _ => to_keep.push(tag), // no activity known → conservative: keep
+                    }
+                }
+
                 (to_delete, to_keep)
             }
         };
@@ -85,17 +116,17 @@ impl Strategy {
         for tag in to_delete.drain(..) {
             if keep_digests.contains(&tag.digest) {
                 if warned_digests.insert(tag.digest.clone()) {
-                    eprintln!(
-                        "[WARN] Digest {} is shared with a kept tag; skipping deletion of tag '{}'",
+                    reporter.warn(format!(
+                        "Digest {} is shared with a kept tag; skipping deletion of tag '{}'",
                         truncate_digest(&tag.digest),
                         tag.tag
-                    );
+                    ));
                 } else {
-                    eprintln!(
-                        "[WARN] Skipping deletion of tag '{}' (shared digest {})",
+                    reporter.warn(format!(
+                        "Skipping deletion of tag '{}' (shared digest {})",
                         tag.tag,
                         truncate_digest(&tag.digest)
-                    );
+                    ));
                 }
                 to_keep.push(tag);
             } else {
@@ -111,11 +142,60 @@ impl Strategy {
     }
 }
 
-/// Count unique digests in a list of TagInfos
-#[allow(dead_code)]
-pub fn count_unique_digests(tags: &[TagInfo]) -> usize {
-    let digests: HashSet<&str> = tags.iter().map(|t| t.digest.as_str()).collect();
-    digests.len()
+/// Registry-scope shared-digest safety: keep a digest in every repository if
+/// any tag in the index still references it after the planned deletions
+/// (tags of repositories outside the run count as kept)
+pub fn protect_across_repos(
+    plans: &mut [CleanupPlan],
+    index: &DigestIndex,
+    reporter: &dyn Reporter,
+) {
+    let planned: Vec<(String, String)> = plans
+        .iter()
+        .flat_map(|p| p.to_delete.iter())
+        .map(|t| (t.repository.clone(), t.tag.clone()))
+        .collect();
+    let planned: HashSet<(&str, &str)> = planned
+        .iter()
+        .map(|(repo, tag)| (repo.as_str(), tag.as_str()))
+        .collect();
+
+    for plan in plans.iter_mut() {
+        let mut safe_delete = Vec::new();
+        for tag in plan.to_delete.drain(..) {
+            match index.remaining_tag(&tag.digest, &planned) {
+                Some((repo, kept)) => {
+                    reporter.warn(format!(
+                        "Digest {} is kept as {}:{}; skipping deletion of {}:{}",
+                        truncate_digest(&tag.digest),
+                        repo,
+                        kept,
+                        tag.repository,
+                        tag.tag
+                    ));
+                    plan.to_keep.push(tag);
+                }
+                None => safe_delete.push(tag),
+            }
+        }
+        plan.to_delete = safe_delete;
+    }
+}
+
+/// Most recent evidence that a tag is in use: a recorded pull or push,
+/// the registry's own pull/push times, or failing those the build date
+fn last_used(tag: &TagInfo, store: &PullStore) -> Option<chrono::DateTime<Utc>> {
+    let activity = store.activity(&tag.repository, &tag.digest, &tag.tag);
+    [
+        activity.last_pull,
+        activity.last_push,
+        tag.last_pulled,
+        tag.pushed,
+        tag.created,
+    ]
+    .into_iter()
+    .flatten()
+    .max()
 }
 
 fn truncate_digest(digest: &str) -> &str {
@@ -131,6 +211,9 @@ mod tests {
     use super::*;
     use chrono::{DateTime, Duration, Utc};
     use crate::models::TagInfo;
+    use crate::pulls::Access;
+    use crate::report::Silent;
+    use std::path::Path;
 
     fn make_tag(repo: &str, tag: &str, digest: &str, created: Option<DateTime<Utc>>) -> TagInfo {
         TagInfo {
@@ -138,6 +221,10 @@ mod tests {
             tag: tag.to_string(),
             digest: digest.to_string(),
             created,
+            size: None,
+            pushed: None,
+            last_pulled: None,
+            blobs: Vec::new(),
         }
     }
 
@@ -153,7 +240,7 @@ mod tests {
         ];
 
         let strategy = Strategy::KeepRecent(3);
-        let plan = strategy.apply("r", tags);
+        let plan = strategy.apply("r", tags, &Silent);
 
         let kept_tags: Vec<&str> = plan.to_keep.iter().map(|t| t.tag.as_str()).collect();
         let deleted_tags: Vec<&str> = plan.to_delete.iter().map(|t| t.tag.as_str()).collect();
@@ -167,6 +254,22 @@ mod tests {
         assert!(deleted_tags.contains(&"t2"));
     }
 
+    #[test]
+    fn test_keep_recent_unknown_date_kept() {
+        let now = Utc::now();
+        let tags = vec![
+            make_tag("r", "old", "d1", Some(now - Duration::days(3))),
+            make_tag("r", "unknown", "d2", None),
+            make_tag("r", "new", "d3", Some(now - Duration::days(1))),
+        ];
+
+        let plan = Strategy::KeepRecent(1).apply("r", tags, &Silent);
+
+        let kept_tags: Vec<&str> = plan.to_keep.iter().map(|t| t.tag.as_str()).collect();
+        assert_eq!(kept_tags, vec!["new", "unknown"]);
+        assert_eq!(plan.to_delete[0].tag, "old");
+    }
+
     #[test]
     fn test_keep_recent_more_than_available() {
         let now = Utc::now();
@@ -177,7 +280,7 @@ mod tests {
         ];
 
         let strategy = Strategy::KeepRecent(10);
-        let plan = strategy.apply("r", tags);
+        let plan = strategy.apply("r", tags, &Silent);
 
         assert_eq!(plan.to_keep.len(), 3);
         assert_eq!(plan.to_delete.len(), 0);
@@ -194,7 +297,7 @@ mod tests {
         ];
 
         let strategy = Strategy::OlderThan(30);
-        let plan = strategy.apply("r", tags);
+        let plan = strategy.apply("r", tags, &Silent);
 
         let kept_tags: Vec<&str> = plan.to_keep.iter().map(|t| t.tag.as_str()).collect();
         let deleted_tags: Vec<&str> = plan.to_delete.iter().map(|t| t.tag.as_str()).collect();
@@ -216,7 +319,7 @@ mod tests {
         ];
 
         let strategy = Strategy::OlderThan(30);
-        let plan = strategy.apply("r", tags);
+        let plan = strategy.apply("r", tags, &Silent);
 
         let kept_tags: Vec<&str> = plan.to_keep.iter().map(|t| t.tag.as_str()).collect();
 
@@ -235,7 +338,7 @@ mod tests {
         ];
 
         let strategy = Strategy::Pattern(Regex::new("^dev-").unwrap());
-        let plan = strategy.apply("r", tags);
+        let plan = strategy.apply("r", tags, &Silent);
 
         let kept_tags: Vec<&str> = plan.to_keep.iter().map(|t| t.tag.as_str()).collect();
         let deleted_tags: Vec<&str> = plan.to_delete.iter().map(|t| t.tag.as_str()).collect();
@@ -261,7 +364,7 @@ mod tests {
         ];
 
         let strategy = Strategy::KeepRecent(1);
-        let plan = strategy.apply("r", tags);
+        let plan = strategy.apply("r", tags, &Silent);
 
         let kept_tags: Vec<&str> = plan.to_keep.iter().map(|t| t.tag.as_str()).collect();
         let deleted_tags: Vec<&str> = plan.to_delete.iter().map(|t| t.tag.as_str()).collect();
@@ -274,4 +377,121 @@ mod tests {
         assert_eq!(plan.to_delete.len(), 1);
         assert_eq!(plan.to_keep.len(), 2);
     }
+
+    #[test]
+    fn test_unused_for() {
+        let now = Utc::now();
+        let store = PullStore::open(Path::new("/nonexistent/pulls.json")).unwrap();
+        // Old base image that is still pulled daily
+        store.record("r", Some("d1"), None, Access::Pull, now - Duration::days(1));
+        // Pulled once, long ago
+        store.record("r", Some("d2"), None, Access::Pull, now - Duration::days(90));
+
+        let tags = vec![
+            make_tag("r", "base", "d1", Some(now - Duration::days(400))),
+            make_tag("r", "stale", "d2", Some(now - Duration::days(100))),
+            make_tag("r", "never-pulled-old", "d3", Some(now - Duration::days(60))),
+            make_tag("r", "never-pulled-new", "d4", Some(now - Duration::days(5))),
+            make_tag("r", "unknown", "d5", None),
+        ];
+
+        let strategy = Strategy::UnusedFor(Duration::days(30), store);
+        let plan = strategy.apply("r", tags, &Silent);
+
+        let kept_tags: Vec<&str> = plan.to_keep.iter().map(|t| t.tag.as_str()).collect();
+        let deleted_tags: Vec<&str> = plan.to_delete.iter().map(|t| t.tag.as_str()).collect();
+
+        assert!(kept_tags.contains(&"base"));
+        assert!(kept_tags.contains(&"never-pulled-new"));
+        assert!(kept_tags.contains(&"unknown"));
+        assert!(deleted_tags.contains(&"stale"));
+        assert!(deleted_tags.contains(&"never-pulled-old"));
+    }
+
+    #[test]
+    fn test_unused_for_empty_store() {
+        let now = Utc::now();
+        let store = PullStore::open(Path::new("/nonexistent/pulls.json")).unwrap();
+        let mut pulled = make_tag("r", "pulled", "d2", Some(now - Duration::days(400)));
+        pulled.last_pulled = Some(now - Duration::days(200));
+        let tags = vec![
+            make_tag("r", "old", "d1", Some(now - Duration::days(400))),
+            pulled,
+        ];
+
+        let strategy = Strategy::UnusedFor(Duration::days(30), store);
+        let plan = strategy.apply("r", tags, &Silent);
+
+        // Nothing was observed yet, only the registry's own pull time counts
+        let deleted_tags: Vec<&str> = plan.to_delete.iter().map(|t| t.tag.as_str()).collect();
+        assert_eq!(deleted_tags, vec!["pulled"]);
+        assert_eq!(plan.to_keep[0].tag, "old");
+    }
+
+    #[test]
+    fn test_unused_for_recent_store() {
+        let now = Utc::now();
+        let store = PullStore::open(Path::new("/nonexistent/pulls.json")).unwrap();
+        store.record("r", Some("d2"), None, Access::Pull, now - Duration::days(3));
+        let tags = vec![make_tag("r", "old", "d1", Some(now - Duration::days(400)))];
+
+        // Recording for 3 days says nothing about the last 30
+        let strategy = Strategy::UnusedFor(Duration::days(30), store);
+        let plan = strategy.apply("r", tags, &Silent);
+        assert!(plan.to_delete.is_empty());
+    }
+
+    #[test]
+    fn test_rule() {
+        let store = PullStore::open(Path::new("/nonexistent/pulls.json")).unwrap();
+        assert_eq!(Strategy::KeepRecent(5).rule(), "keep=5");
+        assert_eq!(Strategy::OlderThan(30).rule(), "older-than=30d");
+        assert_eq!(
+            Strategy::UnusedFor(Duration::hours(36), store).rule(),
+            "unused-for=36h"
+        );
+    }
+
+    #[test]
+    fn test_protect_across_repos() {
+        let tags = |repo: &str| {
+            vec![
+                make_tag(repo, "v1", "shared", None),
+                make_tag(repo, "v2", &format!("{}-own", repo), None),
+            ]
+        };
+        let mut index = DigestIndex::default();
+        index.add(&tags("app"));
+        index.add(&tags("app-release"));
+        index.add(&[make_tag("other", "x", "untouched", None)]);
+
+        // app deletes both tags, app-release keeps v1 (same manifest)
+        let mut plans = vec![
+            Strategy::Pattern(Regex::new("^v").unwrap()).apply("app", tags("app"), &Silent),
+            Strategy::Pattern(Regex::new("^v2$").unwrap()).apply(
+                "app-release",
+                tags("app-release"),
+                &Silent,
+            ),
+        ];
+        protect_across_repos(&mut plans, &index, &Silent);
+
+        let deleted: Vec<&str> = plans[0].to_delete.iter().map(|t| t.tag.as_str()).collect();
+        assert_eq!(deleted, vec!["v2"]);
+        assert!(plans[0].to_keep.iter().any(|t| t.tag == "v1"));
+        assert_eq!(plans[1].to_delete.len(), 1);
+
+        // Deleted in every repository: nothing protects it
+        let mut plans = vec![
+            Strategy::Pattern(Regex::new("^v1$").unwrap()).apply("app", tags("app"), &Silent),
+            Strategy::Pattern(Regex::new("^v1$").unwrap()).apply(
+                "app-release",
+                tags("app-release"),
+                &Silent,
+            ),
+        ];
+        protect_across_repos(&mut plans, &index, &Silent);
+        assert_eq!(plans[0].to_delete.len(), 1);
+        assert_eq!(plans[1].to_delete.len(), 1);
+    }
 }
@@ -0,0 +1,582 @@
+use std::collections::HashMap;
+use std::io::IsTerminal;
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Cell, Clear, Paragraph, Row, Table, TableState};
+use ratatui::{DefaultTerminal, Frame};
+
+use regtidy::index::DigestIndex;
+use regtidy::models::{CleanupPlan, TagInfo};
+
+use crate::output::format_bytes;
+
+/// Order of tags within each repository
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SortKey {
+    /// Newest first
+    Age,
+    /// Largest first
+    Size,
+    Name,
+}
+
+impl SortKey {
+    fn next(self) -> Self {
+        match self {
+            SortKey::Age => SortKey::Size,
+            SortKey::Size => SortKey::Name,
+            SortKey::Name => SortKey::Age,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            SortKey::Age => "age",
+            SortKey::Size => "size",
+            SortKey::Name => "name",
+        }
+    }
+}
+
+/// One tag and its proposed action
+struct TagRow {
+    repo: usize,
+    tag: TagInfo,
+    delete: bool,
+    /// Shared-digest warning shown next to the tag
+    note: Option<String>,
+}
+
+/// A line of the list: a repository header or one of its tags
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Item {
+    Repo(usize),
+    Tag(usize),
+}
+
+/// The plan being reviewed, independent of the terminal
+struct Review {
+    repos: Vec<String>,
+    rows: Vec<TagRow>,
+    filter: String,
+    sort: SortKey,
+    items: Vec<Item>,
+}
+
+impl Review {
+    fn new(plans: Vec<CleanupPlan>, index: &DigestIndex) -> Self {
+        let mut repos = Vec::new();
+        let mut rows = Vec::new();
+        for (repo, plan) in plans.into_iter().enumerate() {
+            repos.push(plan.repository);
+            let tags = plan.to_delete.into_iter().map(|t| (t, true));
+            for (tag, delete) in tags.chain(plan.to_keep.into_iter().map(|t| (t, false))) {
+                rows.push(TagRow {
+                    repo,
+                    tag,
+                    delete,
+                    note: None,
+                });
+            }
+        }
+
+        // Tags of one digest are deleted together, so point out the siblings
+        let mut siblings: HashMap<(usize, &str), Vec<&str>> = HashMap::new();
+        for row in &rows {
+            siblings
+                .entry((row.repo, row.tag.digest.as_str()))
+                .or_default()
+                .push(row.tag.tag.as_str());
+        }
+        let notes: Vec<Option<String>> = rows
+            .iter()
+            .map(|row| {
+                let mut notes = Vec::new();
+                let same = &siblings[&(row.repo, row.tag.digest.as_str())];
+                let others: Vec<&str> =
+                    same.iter().copied().filter(|t| *t != row.tag.tag).collect();
+                if !others.is_empty() {
+                    notes.push(format!("same digest as {}", others.join(", ")));
+                }
+                let elsewhere: Vec<String> = index
+                    .tags(&row.tag.digest)
+                    .iter()
+                    .filter(|(repo, _)| *repo != row.tag.repository)
+                    .map(|(repo, tag)| format!("{}:{}", repo, tag))
+                    .collect();
+                if !elsewhere.is_empty() {
+                    notes.push(format!("also in {}", elsewhere.join(", ")));
+                }
+                Some(notes.join("; ")).filter(|n| !n.is_empty())
+            })
+            .collect();
+        for (row, note) in rows.iter_mut().zip(notes) {
+            row.note = note;
+        }
+
+        let mut review = Self {
+            repos,
+            rows,
+            filter: String::new(),
+            sort: SortKey::Age,
+            items: Vec::new(),
+        };
+        review.refresh();
+        review
+    }
+
+    /// Rebuild the visible list after the filter or sort order changed
+    fn refresh(&mut self) {
+        let filter = self.filter.to_lowercase();
+        let mut items = Vec::new();
+        for (repo, name) in self.repos.iter().enumerate() {
+            let mut tags: Vec<usize> = (0..self.rows.len())
+                .filter(|&i| self.rows[i].repo == repo)
+                .filter(|&i| {
+                    let full = format!("{}:{}", name, self.rows[i].tag.tag).to_lowercase();
+                    filter.is_empty() || full.contains(&filter)
+                })
+                .collect();
+            if tags.is_empty() {
+                continue;
+            }
+            tags.sort_by(|&a, &b| {
+                let (a, b) = (&self.rows[a].tag, &self.rows[b].tag);
+                match self.sort {
+                    SortKey::Age => b.created.cmp(&a.created),
+                    SortKey::Size => b.size.cmp(&a.size),
+                    SortKey::Name => a.tag.cmp(&b.tag),
+                }
+            });
+            items.push(Item::Repo(repo));
+            items.extend(tags.into_iter().map(Item::Tag));
+        }
+        self.items = items;
+    }
+
+    /// Set the action of a tag and every tag of the same digest in its repository
+    fn set_delete(&mut self, row: usize, delete: bool) {
+        let (repo, digest) = (self.rows[row].repo, self.rows[row].tag.digest.clone());
+        for row in &mut self.rows {
+            if row.repo == repo && row.tag.digest == digest {
+                row.delete = delete;
+            }
+        }
+    }
+
+    /// Toggle a tag, or all visible tags of a repository: if any of them is
+    /// kept, all are marked for deletion, otherwise all are kept
+    fn toggle(&mut self, item: Item) {
+        match item {
+            Item::Tag(row) => self.set_delete(row, !self.rows[row].delete),
+            Item::Repo(repo) => {
+                let tags: Vec<usize> = self
+                    .items
+                    .iter()
+                    .filter_map(|item| match item {
+                        Item::Tag(row) if self.rows[*row].repo == repo => Some(*row),
+                        _ => None,
+                    })
+                    .collect();
+                let delete = tags.iter().any(|&row| !self.rows[row].delete);
+                for row in tags {
+                    self.set_delete(row, delete);
+                }
+            }
+        }
+    }
+
+    /// Tags to delete, distinct digests and their size
+    fn totals(&self) -> (usize, usize, u64) {
+        let mut digests: HashMap<&str, u64> = HashMap::new();
+        let mut tags = 0;
+        for row in self.rows.iter().filter(|r| r.delete) {
+            tags += 1;
+            digests.insert(&row.tag.digest, row.tag.size.unwrap_or(0));
+        }
+        (tags, digests.len(), digests.values().sum())
+    }
+
+    fn repo_counts(&self, repo: usize) -> (usize, usize) {
+        let rows = self.rows.iter().filter(|r| r.repo == repo);
+        let delete = rows.clone().filter(|r| r.delete).count();
+        (delete, rows.count() - delete)
+    }
+
+    /// The edited plans, in the original repository order
+    fn into_plans(self) -> Vec<CleanupPlan> {
+        let mut plans: Vec<CleanupPlan> = self
+            .repos
+            .into_iter()
+            .map(|repository| CleanupPlan {
+                repository,
+                to_delete: Vec::new(),
+                to_keep: Vec::new(),
+            })
+            .collect();
+        for row in self.rows {
+            let plan = &mut plans[row.repo];
+            match row.delete {
+                true => plan.to_delete.push(row.tag),
+                false => plan.to_keep.push(row.tag),
+            }
+        }
+        plans
+    }
+}
+
+/// What the keyboard is currently driving
+#[derive(Debug, PartialEq, Eq)]
+enum Mode {
+    Browse,
+    Filter,
+    Confirm,
+}
+
+/// Let the user review and edit the plans in a full-screen terminal UI.
+/// Returns the edited plans once confirmed, or `None` if the review was cancelled.
+pub fn review(
+    plans: Vec<CleanupPlan>,
+    index: &DigestIndex,
+    dry_run: bool,
+) -> Result<Option<Vec<CleanupPlan>>> {
+    if !std::io::stdin().is_terminal() || !std::io::stdout().is_terminal() {
+        anyhow::bail!("--interactive needs a terminal");
+    }
+    let review = Review::new(plans, index);
+    let mut terminal = ratatui::init();
+    let result = run(&mut terminal, review, dry_run);
+    ratatui::restore();
+    result
+}
+
+fn run(
+    terminal: &mut DefaultTerminal,
+    mut review: Review,
+    dry_run: bool,
+) -> Result<Option<Vec<CleanupPlan>>> {
+    let mut state = TableState::default().with_selected(Some(0));
+    let mut mode = Mode::Browse;
+
+    loop {
+        terminal
+            .draw(|frame| draw(frame, &review, &mut state, &mode, dry_run))
+            .context("Failed to draw the terminal UI")?;
+
+        let Event::Key(key) = event::read().context("Failed to read terminal input")? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+
+        match mode {
+            Mode::Filter => match key.code {
+                KeyCode::Enter => mode = Mode::Browse,
+                KeyCode::Esc => {
+                    review.filter.clear();
+                    review.refresh();
+                    mode = Mode::Browse;
+                }
+                KeyCode::Backspace => {
+                    review.filter.pop();
+                    review.refresh();
+                }
+                KeyCode::Char(c) => {
+                    review.filter.push(c);
+                    review.refresh();
+                }
+                _ => {}
+            },
+            Mode::Confirm => match key.code {
+                KeyCode::Char('y') | KeyCode::Char('Y') => return Ok(Some(review.into_plans())),
+                KeyCode::Char('n') | KeyCode::Esc => mode = Mode::Browse,
+                _ => {}
+            },
+            Mode::Browse => match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(None),
+                KeyCode::Enter => mode = Mode::Confirm,
+                KeyCode::Char('/') => mode = Mode::Filter,
+                _ => browse_key(key, &mut review, &mut state),
+            },
+        }
+
+        // Keep the cursor on the list after it shrank
+        let last = review.items.len().saturating_sub(1);
+        state.select(Some(state.selected().unwrap_or(0).min(last)));
+    }
+}
+
+/// Move, toggle and sort in browse mode
+fn browse_key(key: KeyEvent, review: &mut Review, state: &mut TableState) {
+    let selected = state.selected().unwrap_or(0);
+    let page = 20;
+    match key.code {
+        KeyCode::Down | KeyCode::Char('j') => state.select(Some(selected + 1)),
+        KeyCode::Up | KeyCode::Char('k') => state.select(Some(selected.saturating_sub(1))),
+        KeyCode::PageDown => state.select(Some(selected + page)),
+        KeyCode::PageUp => state.select(Some(selected.saturating_sub(page))),
+        KeyCode::Home | KeyCode::Char('g') => state.select(Some(0)),
+        KeyCode::End | KeyCode::Char('G') => state.select(Some(usize::MAX)),
+        KeyCode::Char(' ') => {
+            if let Some(item) = review.items.get(selected).copied() {
+                review.toggle(item);
+            }
+        }
+        KeyCode::Char('s') => {
+            review.sort = review.sort.next();
+            review.refresh();
+        }
+        _ => {}
+    }
+}
+
+fn draw(frame: &mut Frame, review: &Review, state: &mut TableState, mode: &Mode, dry_run: bool) {
+    let [header, body, footer] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Min(1),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    let (tags, digests, bytes) = review.totals();
+    let kept = review.rows.len() - tags;
+    let mut title = vec![
+        Span::styled(" regtidy clean ", Style::new().add_modifier(Modifier::BOLD)),
+        Span::raw("— "),
+        Span::styled(format!("delete {} tags", tags), Style::new().fg(Color::Red)),
+        Span::raw(format!(" ({} digests, {}), ", digests, format_bytes(bytes))),
+        Span::styled(format!("keep {}", kept), Style::new().fg(Color::Green)),
+        Span::raw(format!(" · sorted by {}", review.sort.label())),
+    ];
+    if dry_run {
+        title.push(Span::styled(" · DRY RUN", Style::new().fg(Color::Yellow)));
+    }
+    frame.render_widget(Line::from(title), header);
+
+    let rows = review.items.iter().map(|item| match *item {
+        Item::Repo(repo) => {
+            let (delete, keep) = review.repo_counts(repo);
+            Row::new(vec![
+                Cell::from(""),
+                Cell::from(review.repos[repo].clone()),
+                Cell::from(""),
+                Cell::from(""),
+                Cell::from(""),
+                Cell::from(format!("{} delete / {} keep", delete, keep)),
+            ])
+            .style(Style::new().add_modifier(Modifier::BOLD))
+        }
+        Item::Tag(row) => {
+            let row = &review.rows[row];
+            let action = match row.delete {
+                true => Span::styled("DELETE", Style::new().fg(Color::Red)),
+                false => Span::styled("  keep", Style::new().fg(Color::Green)),
+            };
+            Row::new(vec![
+                Cell::from(action),
+                Cell::from(format!("  {}", row.tag.tag)),
+                Cell::from(short_digest(&row.tag.digest).to_string()),
+                Cell::from(age(&row.tag)),
+                Cell::from(row.tag.size.map(format_bytes).unwrap_or_default()),
+                Cell::from(row.note.clone().unwrap_or_default())
+                    .style(Style::new().fg(Color::Yellow)),
+            ])
+        }
+    });
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(6),
+            Constraint::Percentage(30),
+            Constraint::Length(19),
+            Constraint::Length(6),
+            Constraint::Length(10),
+            Constraint::Fill(1),
+        ],
+    )
+    .header(
+        Row::new(vec![
+            "",
+            "REPOSITORY / TAG",
+            "DIGEST",
+            "AGE",
+            "SIZE",
+            "NOTES",
+        ])
+        .style(Style::new().fg(Color::Cyan)),
+    )
+    .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+    .block(Block::new().borders(Borders::TOP | Borders::BOTTOM));
+    frame.render_stateful_widget(table, body, state);
+
+    let help = match mode {
+        Mode::Filter => format!("Filter: {}▏ (Enter apply, Esc clear)", review.filter),
+        _ if !review.filter.is_empty() => format!(
+            "filter \"{}\" · ↑↓ move · space toggle · / filter · s sort · Enter confirm · q quit",
+            review.filter
+        ),
+        _ => {
+            "↑↓ move · space toggle tag or repository · / filter · s sort · Enter confirm · q quit"
+                .to_string()
+        }
+    };
+    frame.render_widget(Paragraph::new(help), footer);
+
+    if *mode == Mode::Confirm {
+        let text = match dry_run {
+            true => format!("Report {} tags as deleted (dry run)? [y/n]", tags),
+            false => format!("Delete {} tags ({} digests)? [y/n]", tags, digests),
+        };
+        let area = centered(frame.area(), text.chars().count() as u16 + 4, 3);
+        frame.render_widget(Clear, area);
+        frame.render_widget(
+            Paragraph::new(text).block(Block::bordered().title(" Confirm ")),
+            area,
+        );
+    }
+}
+
+fn centered(area: Rect, width: u16, height: u16) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    }
+}
+
+fn short_digest(digest: &str) -> &str {
+    digest.get(..19).unwrap_or(digest)
+}
+
+/// Compact age of the image, e.g. "3d" or "5mo"
+fn age(tag: &TagInfo) -> String {
+    let Some(created) = tag.created else {
+        return "?".to_string();
+    };
+    let days = (Utc::now() - created).num_days();
+    match days {
+        d if d < 1 => format!("{}h", (Utc::now() - created).num_hours().max(0)),
+        d if d < 60 => format!("{}d", d),
+        d if d < 730 => format!("{}mo", d / 30),
+        d => format!("{}y", d / 365),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn tag(repo: &str, name: &str, digest: &str, days: i64) -> TagInfo {
+        TagInfo {
+            repository: repo.to_string(),
+            tag: name.to_string(),
+            digest: digest.to_string(),
+            created: Some(Utc::now() - Duration::days(days)),
+            size: Some(100),
+            pushed: None,
+            last_pulled: None,
+            blobs: Vec::new(),
+        }
+    }
+
+    fn review() -> Review {
+        let plans = vec![
+            CleanupPlan {
+                repository: "app".to_string(),
+                to_delete: vec![tag("app", "v1", "d1", 30), tag("app", "old", "d1", 30)],
+                to_keep: vec![tag("app", "v2", "d2", 1)],
+            },
+            CleanupPlan {
+                repository: "web".to_string(),
+                to_delete: vec![tag("web", "v1", "d3", 10)],
+                to_keep: vec![tag("web", "v2", "d4", 2)],
+            },
+        ];
+        let mut index = DigestIndex::default();
+        for plan in &plans {
+            index.add(&plan.to_delete);
+            index.add(&plan.to_keep);
+        }
+        index.add(&[tag("app-release", "1.0", "d2", 1)]);
+        Review::new(plans, &index)
+    }
+
+    fn row(review: &Review, repo: &str, name: &str) -> usize {
+        let repo = review.repos.iter().position(|r| r == repo).unwrap();
+        review
+            .rows
+            .iter()
+            .position(|r| r.repo == repo && r.tag.tag == name)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_notes() {
+        let review = review();
+        let v1 = &review.rows[row(&review, "app", "v1")];
+        assert_eq!(v1.note.as_deref(), Some("same digest as old"));
+        let v2 = &review.rows[row(&review, "app", "v2")];
+        assert_eq!(v2.note.as_deref(), Some("also in app-release:1.0"));
+        assert_eq!(review.rows[row(&review, "web", "v2")].note, None);
+    }
+
+    #[test]
+    fn test_toggle_keeps_digest_together() {
+        let mut review = review();
+        let v1 = row(&review, "app", "v1");
+        review.toggle(Item::Tag(v1));
+        assert!(!review.rows[v1].delete);
+        assert!(!review.rows[row(&review, "app", "old")].delete);
+        assert_eq!(review.totals().0, 1);
+
+        // A repository with a kept tag is marked for deletion entirely, then kept
+        review.toggle(Item::Repo(1));
+        assert_eq!(review.repo_counts(1), (2, 0));
+        review.toggle(Item::Repo(1));
+        assert_eq!(review.repo_counts(1), (0, 2));
+    }
+
+    #[test]
+    fn test_filter_and_sort() {
+        let mut review = review();
+        assert_eq!(review.items.len(), 7);
+        assert_eq!(review.items[1], Item::Tag(row(&review, "app", "v2")));
+
+        review.filter = "web:".to_string();
+        review.refresh();
+        assert_eq!(review.items.len(), 3);
+        assert_eq!(review.items[0], Item::Repo(1));
+
+        // Group toggles only touch the visible tags
+        review.filter = "web:v2".to_string();
+        review.refresh();
+        review.toggle(Item::Repo(1));
+        assert_eq!(review.repo_counts(1), (2, 0));
+
+        review.filter.clear();
+        review.sort = SortKey::Name;
+        review.refresh();
+        assert_eq!(review.items[1], Item::Tag(row(&review, "app", "old")));
+    }
+
+    #[test]
+    fn test_into_plans() {
+        let mut review = review();
+        review.toggle(Item::Tag(row(&review, "web", "v2")));
+        let plans = review.into_plans();
+        assert_eq!(plans[0].repository, "app");
+        assert_eq!(plans[0].to_delete.len(), 2);
+        assert_eq!(plans[1].to_delete.len(), 2);
+        assert!(plans[1].to_keep.is_empty());
+    }
+}
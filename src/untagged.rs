@@ -0,0 +1,237 @@
+use std::collections::{HashMap, HashSet};
+
+use regex::Regex;
+
+use crate::models::ManifestRevision;
+
+/// Why an untagged manifest is kept
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Protection {
+    /// Child of a kept image index
+    IndexChild(String),
+    /// Subject of a kept referrer (signature, SBOM, attestation)
+    ReferrerSubject(String),
+    /// Referrer whose subject is kept
+    Referrer(String),
+    /// Target of a cosign-style `sha256-<hex>.sig` tag
+    SignatureTag(String),
+}
+
+/// Result of scanning a repository for untagged manifests
+#[derive(Debug)]
+pub struct UntaggedPlan {
+    pub repository: String,
+    pub tagged: usize,
+    /// Untagged, unreferenced manifests, ordered so indexes are deleted before their children
+    pub to_delete: Vec<ManifestRevision>,
+    pub protected: Vec<(ManifestRevision, Protection)>,
+}
+
+/// Find manifests that no tag reaches, directly or through index and referrer relations
+pub fn plan_untagged(repo: &str, revisions: Vec<ManifestRevision>) -> UntaggedPlan {
+    let by_digest: HashMap<&str, &ManifestRevision> =
+        revisions.iter().map(|r| (r.digest.as_str(), r)).collect();
+
+    let mut reasons: HashMap<String, Protection> = HashMap::new();
+    let mut kept: HashSet<String> = HashSet::new();
+    let mut queue: Vec<String> = Vec::new();
+
+    let sig_tag = Regex::new(r"^(sha256|sha512)-([0-9a-f]+)\.(sig|att|sbom)$").unwrap();
+
+    for rev in &revisions {
+        if rev.tags.is_empty() {
+            continue;
+        }
+        if kept.insert(rev.digest.clone()) {
+            queue.push(rev.digest.clone());
+        }
+        for tag in &rev.tags {
+            if let Some(caps) = sig_tag.captures(tag) {
+                let target = format!("{}:{}", &caps[1], &caps[2]);
+                if kept.insert(target.clone()) {
+                    reasons.insert(target.clone(), Protection::SignatureTag(tag.clone()));
+                    queue.push(target);
+                }
+            }
+        }
+    }
+
+    // Referrers point at their subject, so index the reverse direction too
+    let mut referrers: HashMap<&str, Vec<&str>> = HashMap::new();
+    for rev in &revisions {
+        if let Some(subject) = &rev.subject {
+            referrers
+                .entry(subject.as_str())
+                .or_default()
+                .push(rev.digest.as_str());
+        }
+    }
+
+    while let Some(digest) = queue.pop() {
+        let mut reached: Vec<(String, Protection)> = Vec::new();
+
+        if let Some(rev) = by_digest.get(digest.as_str()) {
+            for child in &rev.children {
+                reached.push((child.clone(), Protection::IndexChild(digest.clone())));
+            }
+            if let Some(subject) = &rev.subject {
+                reached.push((subject.clone(), Protection::ReferrerSubject(digest.clone())));
+            }
+        }
+        for referrer in referrers.get(digest.as_str()).into_iter().flatten() {
+            reached.push((referrer.to_string(), Protection::Referrer(digest.clone())));
+        }
+
+        for (target, reason) in reached {
+            if kept.insert(target.clone()) {
+                reasons.insert(target.clone(), reason);
+                queue.push(target);
+            }
+        }
+    }
+
+    let mut tagged = 0;
+    let mut to_delete = Vec::new();
+    let mut protected = Vec::new();
+
+    for rev in revisions {
+        if !rev.tags.is_empty() {
+            tagged += 1;
+        } else if let Some(reason) = reasons.remove(&rev.digest) {
+            protected.push((rev, reason));
+        } else {
+            to_delete.push(rev);
+        }
+    }
+
+    // Delete indexes and referrers before the manifests they reference
+    to_delete.sort_by_key(|r| (r.children.is_empty(), r.subject.is_none(), r.digest.clone()));
+
+    UntaggedPlan {
+        repository: repo.to_string(),
+        tagged,
+        to_delete,
+        protected,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rev(
+        digest: &str,
+        tags: &[&str],
+        children: &[&str],
+        subject: Option<&str>,
+    ) -> ManifestRevision {
+        ManifestRevision {
+            digest: digest.to_string(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            children: children.iter().map(|c| c.to_string()).collect(),
+            subject: subject.map(|s| s.to_string()),
+            size: None,
+        }
+    }
+
+    fn digests(revs: &[ManifestRevision]) -> Vec<&str> {
+        revs.iter().map(|r| r.digest.as_str()).collect()
+    }
+
+    #[test]
+    fn test_untagged_deleted_tagged_kept() {
+        let plan = plan_untagged(
+            "r",
+            vec![
+                rev("sha256:a", &["latest"], &[], None),
+                rev("sha256:b", &[], &[], None),
+                rev("sha256:c", &[], &[], None),
+            ],
+        );
+
+        assert_eq!(plan.tagged, 1);
+        assert_eq!(digests(&plan.to_delete), vec!["sha256:b", "sha256:c"]);
+        assert!(plan.protected.is_empty());
+    }
+
+    #[test]
+    fn test_index_children_protected() {
+        let plan = plan_untagged(
+            "r",
+            vec![
+                rev(
+                    "sha256:index",
+                    &["latest"],
+                    &["sha256:amd64", "sha256:arm64"],
+                    None,
+                ),
+                rev("sha256:amd64", &[], &[], None),
+                rev("sha256:arm64", &[], &[], None),
+                rev("sha256:old-index", &[], &["sha256:old-amd64"], None),
+                rev("sha256:old-amd64", &[], &[], None),
+            ],
+        );
+
+        assert_eq!(plan.protected.len(), 2);
+        assert!(plan
+            .protected
+            .iter()
+            .all(|(_, p)| *p == Protection::IndexChild("sha256:index".to_string())));
+        // The stale index goes first, then the child it referenced
+        assert_eq!(
+            digests(&plan.to_delete),
+            vec!["sha256:old-index", "sha256:old-amd64"]
+        );
+    }
+
+    #[test]
+    fn test_referrers_protected() {
+        let plan = plan_untagged(
+            "r",
+            vec![
+                rev("sha256:image", &["v1"], &[], None),
+                rev("sha256:sbom", &[], &[], Some("sha256:image")),
+                rev("sha256:sbom-sig", &[], &[], Some("sha256:sbom")),
+                rev("sha256:orphan-sig", &[], &[], Some("sha256:gone")),
+                rev("sha256:subject", &[], &[], None),
+                rev("sha256:tagged-sig", &["sig"], &[], Some("sha256:subject")),
+            ],
+        );
+
+        let protected: HashMap<&str, &Protection> = plan
+            .protected
+            .iter()
+            .map(|(r, p)| (r.digest.as_str(), p))
+            .collect();
+        assert_eq!(
+            protected["sha256:sbom"],
+            &Protection::Referrer("sha256:image".to_string())
+        );
+        assert_eq!(
+            protected["sha256:sbom-sig"],
+            &Protection::Referrer("sha256:sbom".to_string())
+        );
+        assert_eq!(
+            protected["sha256:subject"],
+            &Protection::ReferrerSubject("sha256:tagged-sig".to_string())
+        );
+        assert_eq!(digests(&plan.to_delete), vec!["sha256:orphan-sig"]);
+    }
+
+    #[test]
+    fn test_cosign_signature_tag_protects_target() {
+        let plan = plan_untagged(
+            "r",
+            vec![
+                rev("sha256:abc123", &[], &[], None),
+                rev("sha256:sigmanifest", &["sha256-abc123.sig"], &[], None),
+            ],
+        );
+
+        assert!(plan.to_delete.is_empty());
+        assert_eq!(
+            plan.protected[0].1,
+            Protection::SignatureTag("sha256-abc123.sig".to_string())
+        );
+    }
+}
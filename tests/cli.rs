@@ -0,0 +1,267 @@
+mod common;
+
+use std::path::PathBuf;
+use std::process::Output;
+
+use axum::http::{Method, StatusCode};
+
+use common::FakeRegistry;
+
+/// Run the regtidy binary against `registry` with a fresh home directory,
+/// so no config file, cache or REGTIDY_* variable of the host applies
+async fn regtidy(registry: &FakeRegistry, args: &[&str]) -> Output {
+    let home = home_dir();
+    let output = tokio::process::Command::new(env!("CARGO_BIN_EXE_regtidy"))
+        .env_clear()
+        .env("HOME", &home)
+        .env("XDG_CONFIG_HOME", home.join(".config"))
+        .env("XDG_CACHE_HOME", home.join(".cache"))
+        .env("NO_COLOR", "1")
+        .args(["--no-cache", "--registry", &registry.url])
+        .args(args)
+        .output()
+        .await
+        .unwrap();
+    std::fs::remove_dir_all(&home).ok();
+    output
+}
+
+fn home_dir() -> PathBuf {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let dir = std::env::temp_dir().join(format!(
+        "regtidy-e2e-{}-{}",
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::SeqCst)
+    ));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+/// app: v1..v4 a day apart, v4 also tagged `latest`, v1 also tagged `old`
+async fn app_registry() -> (FakeRegistry, Vec<String>) {
+    let registry = FakeRegistry::start().await;
+    let digests: Vec<String> = (1..=4)
+        .map(|i| {
+            registry.push_image(
+                "app",
+                &format!("v{}", i),
+                &format!("2024-01-0{}T00:00:00Z", i),
+            )
+        })
+        .collect();
+    registry.tag("app", "latest", &digests[3]);
+    registry.tag("app", "old", &digests[0]);
+    (registry, digests)
+}
+
+#[tokio::test]
+async fn test_list() {
+    let registry = FakeRegistry::start().await;
+    registry.push_image("app", "v1", "2024-01-01T00:00:00Z");
+    registry.push_image("app", "v2", "2024-01-02T00:00:00Z");
+    registry.push_image("team/web", "main", "2024-01-03T00:00:00Z");
+    registry.create_repository("empty");
+    registry.set_page_size(1);
+
+    let output = regtidy(&registry, &["list"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    let out = stdout(&output);
+    assert!(out.contains("Repository: app"));
+    assert!(out.contains("Repository: team/web"));
+    assert!(out.contains("3 repositories, 3 tags total."), "{}", out);
+}
+
+#[tokio::test]
+async fn test_dangling() {
+    let registry = FakeRegistry::start().await;
+    registry.push_image("app", "v1", "2024-01-01T00:00:00Z");
+    registry.create_repository("abandoned");
+
+    let output = regtidy(&registry, &["dangling"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    let out = stdout(&output);
+    assert!(out.contains("abandoned"));
+    assert!(!out.contains("app"));
+}
+
+#[tokio::test]
+async fn test_clean_dry_run() {
+    let (registry, _) = app_registry().await;
+
+    let output = regtidy(&registry, &["clean", "--keep", "1", "--dry-run"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("DRY RUN"));
+    assert!(registry.requests(Method::DELETE).is_empty());
+    assert_eq!(registry.tags("app").len(), 6);
+}
+
+#[tokio::test]
+async fn test_clean_shared_digests() {
+    let (registry, digests) = app_registry().await;
+
+    let output = regtidy(&registry, &["clean", "--keep", "3", "--yes"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    // v1 and old share a digest, which is deleted once
+    let deletes = registry.requests(Method::DELETE);
+    let deleted = deletes
+        .iter()
+        .filter(|path| digests.iter().any(|d| path.ends_with(d.as_str())));
+    assert_eq!(deleted.count(), 2);
+    assert!(!registry.has_manifest("app", &digests[0]));
+    assert!(!registry.has_manifest("app", &digests[1]));
+    assert_eq!(registry.tags("app"), vec!["latest", "v3", "v4"]);
+}
+
+#[tokio::test]
+async fn test_clean_keeps_digest_of_kept_tag() {
+    let (registry, digests) = app_registry().await;
+
+    // latest shares v4's digest, so deleting v4 would delete latest as well
+    let output = regtidy(&registry, &["clean", "--pattern", "^v4$", "--yes"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(registry.has_manifest("app", &digests[3]));
+    assert!(stderr(&output).contains("shared with a kept tag"));
+}
+
+#[tokio::test]
+async fn test_clean_image_index() {
+    let registry = FakeRegistry::start().await;
+    let amd64 = registry.push_image("app", "amd64", "2024-01-01T00:00:00Z");
+    let arm64 = registry.push_image("app", "arm64", "2024-01-01T00:00:00Z");
+    let index = registry.push_index("app", "multi", &[&amd64, &arm64]);
+    let list = registry.push_manifest_list("app", "list", &[&amd64, &arm64]);
+
+    let output = regtidy(
+        &registry,
+        &["clean", "--pattern", "^(multi|list)$", "--yes"],
+    )
+    .await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    // The index and the list are deleted by their own digests, never a child's
+    let deleted = registry.requests(Method::DELETE);
+    assert!(deleted.contains(&format!("/v2/app/manifests/{}", index)));
+    assert!(deleted.contains(&format!("/v2/app/manifests/{}", list)));
+    assert!(!deleted
+        .iter()
+        .any(|path| path.ends_with(&amd64) || path.ends_with(&arm64)));
+    assert!(registry.has_manifest("app", &amd64));
+    assert!(registry.has_manifest("app", &arm64));
+    assert_eq!(registry.tags("app"), vec!["amd64", "arm64"]);
+}
+
+#[tokio::test]
+async fn test_clean_partial_failure() {
+    let (registry, digests) = app_registry().await;
+    registry.fail(
+        Method::DELETE,
+        &digests[1],
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "UNKNOWN",
+    );
+
+    let output = regtidy(&registry, &["clean", "--keep", "3", "--yes"]).await;
+    assert_eq!(output.status.code(), Some(9), "{}", stderr(&output));
+    assert!(stderr(&output).contains("1 errors occurred during cleanup"));
+    assert!(!registry.has_manifest("app", &digests[0]));
+    assert_eq!(registry.tags("app"), vec!["latest", "v2", "v3", "v4"]);
+}
+
+#[tokio::test]
+async fn test_clean_limits_per_registry() {
+    let (a, _) = app_registry().await;
+    let (b, _) = app_registry().await;
+
+    // v1, v2 and old go in each registry: 6 tags in total, 3 per registry
+    let args = ["--registry", &b.url, "clean", "--keep", "3", "--yes"];
+    let output = regtidy(&a, &[&args[..], &["--max-tags", "4"]].concat()).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(a.tags("app"), vec!["latest", "v3", "v4"]);
+    assert_eq!(b.tags("app"), vec!["latest", "v3", "v4"]);
+
+    let (a, _) = app_registry().await;
+    let (b, _) = app_registry().await;
+    let args = ["--registry", &b.url, "clean", "--keep", "3", "--yes"];
+    let output = regtidy(&a, &[&args[..], &["--max-tags", "2"]].concat()).await;
+    assert!(!output.status.success());
+    assert_eq!(a.tags("app").len(), 6);
+    assert_eq!(b.tags("app").len(), 6);
+}
+
+#[tokio::test]
+async fn test_clean_notifies_once() {
+    use axum::routing::post;
+    use axum::{Json, Router};
+    use std::sync::{Arc, Mutex};
+
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let hook = received.clone();
+    let router = Router::new().route(
+        "/hook",
+        post(move |Json(body): Json<serde_json::Value>| async move {
+            hook.lock().unwrap().push(body);
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let webhook = format!("http://{}/hook", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        axum::serve(listener, router).await.unwrap();
+    });
+
+    let (a, _) = app_registry().await;
+    let (b, _) = app_registry().await;
+    let args = [
+        "--registry",
+        &b.url,
+        "clean",
+        "--keep",
+        "3",
+        "--dry-run",
+        "--notify-webhook",
+        &webhook,
+    ];
+    let output = regtidy(&a, &args).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let received = received.lock().unwrap();
+    assert_eq!(received.len(), 1);
+    assert_eq!(received[0]["report"]["deleted_tags"], 6);
+    let registry = received[0]["registry"].as_str().unwrap();
+    assert!(registry.contains(&a.url) && registry.contains(&b.url));
+}
+
+#[tokio::test]
+async fn test_clean_deletion_disabled() {
+    let (registry, _) = app_registry().await;
+    registry.disable_deletes();
+
+    let output = regtidy(&registry, &["clean", "--keep", "2", "--yes"]).await;
+    assert_eq!(output.status.code(), Some(5), "{}", stderr(&output));
+    assert_eq!(registry.tags("app").len(), 6);
+}
+
+#[tokio::test]
+async fn test_authentication() {
+    let (registry, _) = app_registry().await;
+    registry.require_basic_auth("ci", "secret");
+
+    let output = regtidy(&registry, &["list"]).await;
+    assert_eq!(output.status.code(), Some(3), "{}", stderr(&output));
+
+    let output = regtidy(
+        &registry,
+        &["--username", "ci", "--password", "secret", "list"],
+    )
+    .await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("1 repositories, 6 tags total."));
+}
@@ -0,0 +1,586 @@
+//! An in-process Registry V2 server for end-to-end tests.
+//!
+//! Repositories, tags, manifests and blobs live in memory. Catalog and tag
+//! lists are paginated with `n`/`last` and `Link` headers, manifests are
+//! negotiated on `Accept` as distribution does, DELETE removes a manifest with
+//! every tag pointing at it, and failures can be injected per method and path.
+
+#![allow(dead_code)]
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::{header, HeaderMap, HeaderValue, Method, StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
+use axum::Router;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+pub const MANIFEST_V2: &str = "application/vnd.docker.distribution.manifest.v2+json";
+pub const MANIFEST_LIST: &str = "application/vnd.docker.distribution.manifest.list.v2+json";
+pub const OCI_MANIFEST: &str = "application/vnd.oci.image.manifest.v1+json";
+pub const OCI_INDEX: &str = "application/vnd.oci.image.index.v1+json";
+const CONFIG_V1: &str = "application/vnd.docker.container.image.v1+json";
+const LAYER: &str = "application/vnd.docker.image.rootfs.diff.tar.gzip";
+
+/// Size of the single layer every pushed image has
+pub const LAYER_SIZE: u64 = 1000;
+
+#[derive(Default)]
+struct Repository {
+    /// Tag → manifest digest
+    tags: BTreeMap<String, String>,
+    /// Digest → (media type, body)
+    manifests: HashMap<String, (String, Vec<u8>)>,
+}
+
+struct Failure {
+    method: Method,
+    path: String,
+    status: StatusCode,
+    code: &'static str,
+}
+
+#[derive(Default)]
+struct Registry {
+    repositories: BTreeMap<String, Repository>,
+    blobs: HashMap<String, Vec<u8>>,
+    /// Page size used when the client does not pass `n`
+    page_size: Option<usize>,
+    deletes_disabled: bool,
+    /// Expected `Authorization` header, if authentication is required
+    authorization: Option<String>,
+    failures: Vec<Failure>,
+    requests: Vec<(Method, String)>,
+}
+
+/// A running fake registry; the server stops with the test's runtime
+#[derive(Clone)]
+pub struct FakeRegistry {
+    pub url: String,
+    state: Arc<Mutex<Registry>>,
+}
+
+impl FakeRegistry {
+    pub async fn start() -> Self {
+        let state = Arc::new(Mutex::new(Registry::default()));
+        let router = Router::new().fallback(handle).with_state(state.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, router).await.unwrap();
+        });
+        Self {
+            url: format!("http://{}", addr),
+            state,
+        }
+    }
+
+    fn with<T>(&self, f: impl FnOnce(&mut Registry) -> T) -> T {
+        f(&mut self.state.lock().unwrap())
+    }
+
+    /// Create a repository without tags
+    pub fn create_repository(&self, repo: &str) {
+        self.with(|r| {
+            r.repositories.entry(repo.to_string()).or_default();
+        });
+    }
+
+    /// Push a single-platform image created at `created` (RFC 3339) and
+    /// tag it; returns the manifest digest
+    pub fn push_image(&self, repo: &str, tag: &str, created: &str) -> String {
+        let layer = format!("layer of {}:{}", repo, tag);
+        self.push_image_with_layer(repo, tag, created, &layer)
+    }
+
+    /// Like `push_image`, with a layer of the given content; images pushed
+    /// with the same content share the layer blob
+    pub fn push_image_with_layer(
+        &self,
+        repo: &str,
+        tag: &str,
+        created: &str,
+        layer: &str,
+    ) -> String {
+        let config = json!({
+            "created": created,
+            "architecture": "amd64",
+            "os": "linux",
+            "config": { "Labels": { "tag": tag } },
+        });
+        let config = serde_json::to_vec(&config).unwrap();
+        let layer = layer.as_bytes().to_vec();
+        let config_digest = self.push_blob(config.clone());
+        let layer_digest = self.push_blob(layer);
+        let manifest = json!({
+            "schemaVersion": 2,
+            "mediaType": MANIFEST_V2,
+            "config": { "mediaType": CONFIG_V1, "size": config.len(), "digest": config_digest },
+            "layers": [{ "mediaType": LAYER, "size": LAYER_SIZE, "digest": layer_digest }],
+        });
+        let digest = self.push_manifest(repo, MANIFEST_V2, &manifest);
+        self.tag(repo, tag, &digest);
+        digest
+    }
+
+    /// Push an OCI image index of manifests already in `repo` and tag it;
+    /// returns the index digest
+    pub fn push_index(&self, repo: &str, tag: &str, children: &[&str]) -> String {
+        self.push_multi_platform(repo, tag, OCI_INDEX, children)
+    }
+
+    /// Push a Docker manifest list of manifests already in `repo` and tag
+    /// it; returns the list digest. The first child is linux/amd64.
+    pub fn push_manifest_list(&self, repo: &str, tag: &str, children: &[&str]) -> String {
+        self.push_multi_platform(repo, tag, MANIFEST_LIST, children)
+    }
+
+    fn push_multi_platform(
+        &self,
+        repo: &str,
+        tag: &str,
+        media_type: &str,
+        children: &[&str],
+    ) -> String {
+        let platforms = ["amd64", "arm64", "ppc64le", "s390x"];
+        let manifests: Vec<_> = children
+            .iter()
+            .zip(platforms)
+            .map(|(digest, architecture)| {
+                let size = self.with(|r| r.repositories[repo].manifests[*digest].1.len());
+                json!({
+                    "mediaType": MANIFEST_V2,
+                    "size": size,
+                    "digest": digest,
+                    "platform": { "architecture": architecture, "os": "linux" },
+                })
+            })
+            .collect();
+        let index = json!({
+            "schemaVersion": 2,
+            "mediaType": media_type,
+            "manifests": manifests,
+        });
+        let digest = self.push_manifest(repo, media_type, &index);
+        self.tag(repo, tag, &digest);
+        digest
+    }
+
+    /// Point `tag` at a manifest; manifests of other repositories are copied
+    /// over, as a cross-repository push would
+    pub fn tag(&self, repo: &str, tag: &str, digest: &str) {
+        self.with(|r| {
+            if !r
+                .repositories
+                .get(repo)
+                .is_some_and(|r| r.manifests.contains_key(digest))
+            {
+                let manifest = r
+                    .repositories
+                    .values()
+                    .find_map(|r| r.manifests.get(digest).cloned())
+                    .expect("unknown manifest");
+                let repository = r.repositories.entry(repo.to_string()).or_default();
+                repository.manifests.insert(digest.to_string(), manifest);
+            }
+            let repository = r.repositories.get_mut(repo).unwrap();
+            repository.tags.insert(tag.to_string(), digest.to_string());
+        });
+    }
+
+    /// Remove `tag`, leaving its manifest untagged
+    pub fn untag(&self, repo: &str, tag: &str) {
+        self.with(|r| r.repositories.get_mut(repo).unwrap().tags.remove(tag));
+    }
+
+    fn push_blob(&self, body: Vec<u8>) -> String {
+        let digest = sha256(&body);
+        self.with(|r| r.blobs.insert(digest.clone(), body));
+        digest
+    }
+
+    fn push_manifest(&self, repo: &str, media_type: &str, manifest: &serde_json::Value) -> String {
+        let body = serde_json::to_vec(manifest).unwrap();
+        let digest = sha256(&body);
+        self.with(|r| {
+            let repository = r.repositories.entry(repo.to_string()).or_default();
+            repository
+                .manifests
+                .insert(digest.clone(), (media_type.to_string(), body));
+        });
+        digest
+    }
+
+    /// Paginate catalog and tag lists by `n` when the client does not ask for a size
+    pub fn set_page_size(&self, n: usize) {
+        self.with(|r| r.page_size = Some(n));
+    }
+
+    /// Answer manifest DELETEs with 405 UNSUPPORTED, as a registry without
+    /// `storage.delete.enabled` does
+    pub fn disable_deletes(&self) {
+        self.with(|r| r.deletes_disabled = true);
+    }
+
+    /// Reject requests without these basic auth credentials
+    pub fn require_basic_auth(&self, username: &str, password: &str) {
+        use base64::Engine;
+        let encoded =
+            base64::engine::general_purpose::STANDARD.encode(format!("{}:{}", username, password));
+        self.with(|r| r.authorization = Some(format!("Basic {}", encoded)));
+    }
+
+    /// Answer `method` requests whose path contains `path` with `status`
+    /// and a registry error of `code`
+    pub fn fail(&self, method: Method, path: &str, status: StatusCode, code: &'static str) {
+        self.with(|r| {
+            r.failures.push(Failure {
+                method,
+                path: path.to_string(),
+                status,
+                code,
+            })
+        });
+    }
+
+    /// Tags currently in `repo`
+    pub fn tags(&self, repo: &str) -> Vec<String> {
+        self.with(|r| {
+            r.repositories
+                .get(repo)
+                .map(|r| r.tags.keys().cloned().collect())
+                .unwrap_or_default()
+        })
+    }
+
+    /// Whether `repo` still has a manifest with this digest
+    pub fn has_manifest(&self, repo: &str, digest: &str) -> bool {
+        self.with(|r| {
+            r.repositories
+                .get(repo)
+                .is_some_and(|r| r.manifests.contains_key(digest))
+        })
+    }
+
+    /// Paths (with query) of the requests made with `method`, in order
+    pub fn requests(&self, method: Method) -> Vec<String> {
+        self.with(|r| {
+            r.requests
+                .iter()
+                .filter(|(m, _)| *m == method)
+                .map(|(_, path)| path.clone())
+                .collect()
+        })
+    }
+}
+
+pub fn sha256(body: &[u8]) -> String {
+    format!("sha256:{:x}", Sha256::digest(body))
+}
+
+fn error(status: StatusCode, code: &str, message: &str) -> Response {
+    let body = json!({ "errors": [{ "code": code, "message": message }] });
+    (status, axum::Json(body)).into_response()
+}
+
+async fn handle(
+    State(state): State<Arc<Mutex<Registry>>>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+) -> Response {
+    let mut registry = state.lock().unwrap();
+    let path = uri.path().to_string();
+    let path_and_query = uri.path_and_query().map_or(path.clone(), |p| p.to_string());
+    registry.requests.push((method.clone(), path_and_query));
+
+    if let Some(expected) = &registry.authorization {
+        let given = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok());
+        if given != Some(expected.as_str()) {
+            let mut resp = error(
+                StatusCode::UNAUTHORIZED,
+                "UNAUTHORIZED",
+                "authentication required",
+            );
+            resp.headers_mut().insert(
+                header::WWW_AUTHENTICATE,
+                HeaderValue::from_static("Basic realm=\"fake-registry\""),
+            );
+            return resp;
+        }
+    }
+
+    if let Some(failure) = registry
+        .failures
+        .iter()
+        .find(|f| f.method == method && path.contains(&f.path))
+    {
+        return error(failure.status, failure.code, "injected failure");
+    }
+
+    let query: HashMap<String, String> = uri
+        .query()
+        .map(|q| {
+            url_pairs(q)
+                .into_iter()
+                .collect::<HashMap<String, String>>()
+        })
+        .unwrap_or_default();
+
+    if path == "/v2/" {
+        let mut resp = axum::Json(json!({})).into_response();
+        resp.headers_mut().insert(
+            "Docker-Distribution-API-Version",
+            HeaderValue::from_static("registry/2.0"),
+        );
+        return resp;
+    }
+    if path == "/v2/_catalog" {
+        let names: Vec<String> = registry.repositories.keys().cloned().collect();
+        let page_size = registry.page_size;
+        return paginated(
+            &path,
+            names,
+            &query,
+            page_size,
+            |page| json!({ "repositories": page }),
+        );
+    }
+
+    let Some(rest) = path.strip_prefix("/v2/") else {
+        return error(StatusCode::NOT_FOUND, "NOT_FOUND", "not found");
+    };
+
+    if let Some(repo) = rest.strip_suffix("/tags/list") {
+        let Some(repository) = registry.repositories.get(repo) else {
+            return error(
+                StatusCode::NOT_FOUND,
+                "NAME_UNKNOWN",
+                "repository name not known",
+            );
+        };
+        let tags: Vec<String> = repository.tags.keys().cloned().collect();
+        let page_size = registry.page_size;
+        return paginated(&path, tags, &query, page_size, |page| {
+            // The distribution registry reports a repository without tags as null
+            let tags = if page.is_empty() {
+                json!(null)
+            } else {
+                json!(page)
+            };
+            json!({ "name": repo, "tags": tags })
+        });
+    }
+
+    if let Some((repo, digest)) = rest.rsplit_once("/blobs/") {
+        if !registry.repositories.contains_key(repo) {
+            return error(
+                StatusCode::NOT_FOUND,
+                "NAME_UNKNOWN",
+                "repository name not known",
+            );
+        }
+        return match registry.blobs.get(digest) {
+            Some(body) => Response::builder()
+                .header(header::CONTENT_TYPE, "application/octet-stream")
+                .header("Docker-Content-Digest", digest)
+                .body(Body::from(body.clone()))
+                .unwrap(),
+            None => error(
+                StatusCode::NOT_FOUND,
+                "BLOB_UNKNOWN",
+                "blob unknown to registry",
+            ),
+        };
+    }
+
+    if let Some((repo, reference)) = rest.rsplit_once("/manifests/") {
+        let deletes_disabled = registry.deletes_disabled;
+        let Some(repository) = registry.repositories.get_mut(repo) else {
+            return error(
+                StatusCode::NOT_FOUND,
+                "NAME_UNKNOWN",
+                "repository name not known",
+            );
+        };
+        return manifest(repository, &method, &headers, reference, deletes_disabled);
+    }
+
+    error(StatusCode::NOT_FOUND, "NOT_FOUND", "not found")
+}
+
+fn manifest(
+    repository: &mut Repository,
+    method: &Method,
+    headers: &HeaderMap,
+    reference: &str,
+    deletes_disabled: bool,
+) -> Response {
+    let is_digest = reference.contains(':');
+    let mut digest = if is_digest {
+        reference.to_string()
+    } else {
+        match repository.tags.get(reference) {
+            Some(digest) => digest.clone(),
+            None => {
+                return error(
+                    StatusCode::NOT_FOUND,
+                    "MANIFEST_UNKNOWN",
+                    "manifest unknown",
+                )
+            }
+        }
+    };
+
+    if matches!(*method, Method::GET | Method::HEAD) {
+        match negotiate(repository, headers, &digest, is_digest) {
+            Ok(served) => digest = served,
+            Err(message) => return error(StatusCode::NOT_FOUND, "MANIFEST_UNKNOWN", message),
+        }
+    }
+
+    match *method {
+        Method::GET | Method::HEAD => match repository.manifests.get(&digest) {
+            Some((media_type, body)) => {
+                let body = if *method == Method::HEAD {
+                    Body::empty()
+                } else {
+                    Body::from(body.clone())
+                };
+                Response::builder()
+                    .header(header::CONTENT_TYPE, media_type.as_str())
+                    .header("Docker-Content-Digest", digest.as_str())
+                    .body(body)
+                    .unwrap()
+            }
+            None => error(
+                StatusCode::NOT_FOUND,
+                "MANIFEST_UNKNOWN",
+                "manifest unknown",
+            ),
+        },
+        Method::DELETE => {
+            if deletes_disabled {
+                return error(
+                    StatusCode::METHOD_NOT_ALLOWED,
+                    "UNSUPPORTED",
+                    "The operation is unsupported.",
+                );
+            }
+            if !is_digest {
+                return error(
+                    StatusCode::BAD_REQUEST,
+                    "DIGEST_INVALID",
+                    "provided digest did not match",
+                );
+            }
+            if repository.manifests.remove(&digest).is_none() {
+                return error(
+                    StatusCode::NOT_FOUND,
+                    "MANIFEST_UNKNOWN",
+                    "manifest unknown",
+                );
+            }
+            repository.tags.retain(|_, d| *d != digest);
+            StatusCode::ACCEPTED.into_response()
+        }
+        _ => error(StatusCode::METHOD_NOT_ALLOWED, "UNSUPPORTED", "unsupported"),
+    }
+}
+
+/// The digest of the manifest to serve for `Accept`, as distribution picks it:
+/// OCI manifests and indexes only if accepted, a manifest list fetched by tag
+/// falls back to its linux/amd64 child, and a Docker schema 2 manifest fetched
+/// by tag needs schema 2 to be accepted (there is no schema 1 to convert to).
+fn negotiate(
+    repository: &Repository,
+    headers: &HeaderMap,
+    digest: &str,
+    by_digest: bool,
+) -> Result<String, &'static str> {
+    let accepted: Vec<&str> = headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(|t| t.split(';').next().unwrap_or_default().trim())
+        .collect();
+    let accepts = |media_type: &str| accepted.contains(&media_type);
+    let Some((media_type, body)) = repository.manifests.get(digest) else {
+        return Ok(digest.to_string());
+    };
+
+    match media_type.as_str() {
+        OCI_MANIFEST if !accepts(OCI_MANIFEST) => {
+            Err("OCI manifest found, but accept header does not support OCI manifests")
+        }
+        OCI_INDEX if !accepts(OCI_INDEX) => {
+            Err("OCI index found, but accept header does not support OCI indexes")
+        }
+        MANIFEST_LIST if !by_digest && !accepts(MANIFEST_LIST) => {
+            let list: serde_json::Value = serde_json::from_slice(body).unwrap();
+            let child = list["manifests"]
+                .as_array()
+                .and_then(|children| {
+                    children.iter().find(|c| {
+                        c["platform"]["architecture"] == "amd64" && c["platform"]["os"] == "linux"
+                    })
+                })
+                .and_then(|c| c["digest"].as_str())
+                .ok_or("no matching manifest for linux/amd64 in the manifest list")?;
+            negotiate(repository, headers, child, false)
+        }
+        MANIFEST_V2 if !by_digest && !accepts(MANIFEST_V2) => {
+            Err("schema 2 manifest found, but schema 1 conversion is not supported")
+        }
+        _ => Ok(digest.to_string()),
+    }
+}
+
+/// One page of `items` after `last`, with a `Link` to the next page if there is one
+fn paginated(
+    path: &str,
+    items: Vec<String>,
+    query: &HashMap<String, String>,
+    default_size: Option<usize>,
+    body: impl FnOnce(&[String]) -> serde_json::Value,
+) -> Response {
+    let start = match query.get("last") {
+        Some(last) => items.iter().position(|i| i > last).unwrap_or(items.len()),
+        None => 0,
+    };
+    let size = query
+        .get("n")
+        .and_then(|n| n.parse().ok())
+        .or(default_size)
+        .unwrap_or(usize::MAX);
+    let end = start.saturating_add(size).min(items.len());
+    let page = &items[start..end];
+
+    let mut resp = axum::Json(body(page)).into_response();
+    if end < items.len() {
+        let link = format!(
+            "<{}?n={}&last={}>; rel=\"next\"",
+            path,
+            size,
+            page[page.len() - 1]
+        );
+        resp.headers_mut()
+            .insert(header::LINK, HeaderValue::from_str(&link).unwrap());
+    }
+    resp
+}
+
+/// Decode `a=1&b=2` query pairs
+fn url_pairs(query: &str) -> Vec<(String, String)> {
+    reqwest::Url::parse(&format!("http://localhost/?{}", query))
+        .unwrap()
+        .query_pairs()
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect()
+}
@@ -0,0 +1,340 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use axum::http::{Method, StatusCode};
+
+use common::{FakeRegistry, LAYER_SIZE, MANIFEST_V2};
+use regtidy::backend::{Auth, Tls};
+use regtidy::error::ErrorKind;
+use regtidy::limits::Limits;
+use regtidy::models::CleanupPlan;
+use regtidy::report::Silent;
+use regtidy::{
+    CleanHooks, CleanOptions, Event, Executor, RegistryClient, Reporter, Strategy, Unattended,
+};
+
+fn client(registry: &FakeRegistry) -> RegistryClient {
+    RegistryClient::new(&registry.url, Arc::new(Silent))
+}
+
+#[tokio::test]
+async fn test_pagination() {
+    let registry = FakeRegistry::start().await;
+    for repo in ["a", "b", "c", "team/d", "team/e"] {
+        registry.create_repository(repo);
+    }
+    for tag in ["v1", "v2", "v3"] {
+        registry.push_image("a", tag, "2024-01-01T00:00:00Z");
+    }
+    registry.set_page_size(2);
+
+    let client = client(&registry);
+    assert_eq!(
+        client.list_repositories().await.unwrap(),
+        vec!["a", "b", "c", "team/d", "team/e"]
+    );
+    assert_eq!(
+        registry.requests(Method::GET),
+        vec![
+            "/v2/_catalog",
+            "/v2/_catalog?n=2&last=b",
+            "/v2/_catalog?n=2&last=team/d",
+        ]
+    );
+    assert_eq!(client.list_tags("a").await.unwrap(), vec!["v1", "v2", "v3"]);
+    assert!(client.list_tags("b").await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_page_size() {
+    let registry = FakeRegistry::start().await;
+    for repo in ["a", "b", "c"] {
+        registry.create_repository(repo);
+    }
+
+    let client = client(&registry).with_page_size(2);
+    assert_eq!(
+        client.list_repositories().await.unwrap(),
+        vec!["a", "b", "c"]
+    );
+    assert_eq!(
+        registry.requests(Method::GET),
+        vec!["/v2/_catalog?n=2", "/v2/_catalog?n=2&last=b"]
+    );
+}
+
+#[tokio::test]
+async fn test_resolve_tags() {
+    let registry = FakeRegistry::start().await;
+    let image = registry.push_image("app", "v1", "2024-03-01T12:00:00Z");
+    registry.tag("app", "latest", &image);
+    let index = registry.push_index("app", "multi", &[&image]);
+    let list = registry.push_manifest_list("app", "list", &[&image]);
+
+    let client = client(&registry);
+    assert_eq!(client.get_digest("app", "latest").await.unwrap(), image);
+    // Multi-platform tags resolve to the index or list itself, not a child
+    assert_eq!(client.get_digest("app", "multi").await.unwrap(), index);
+    assert_eq!(client.get_digest("app", "list").await.unwrap(), list);
+
+    let mut tags = client.resolve_all_tags("app").await.unwrap();
+    tags.sort_by(|a, b| a.tag.cmp(&b.tag));
+    let names: Vec<_> = tags.iter().map(|t| t.tag.as_str()).collect();
+    assert_eq!(names, vec!["latest", "list", "multi", "v1"]);
+    assert_eq!(tags[1].digest, list);
+
+    let v1 = &tags[3];
+    assert_eq!(v1.digest, image);
+    assert_eq!(
+        v1.created.unwrap().to_rfc3339(),
+        "2024-03-01T12:00:00+00:00"
+    );
+    assert!(v1.size.unwrap() > LAYER_SIZE);
+    assert_eq!(tags[0].digest, image);
+
+    // An index is dated by its first platform image and sized by all of them
+    let multi = &tags[2];
+    assert_eq!(multi.digest, index);
+    assert_eq!(multi.created, v1.created);
+    assert_eq!(multi.size, v1.size);
+    assert_eq!(tags[1].created, v1.created);
+}
+
+#[tokio::test]
+async fn test_keep_recent_multi_platform() {
+    let registry = FakeRegistry::start().await;
+    registry.push_image("app", "old", "2024-01-01T00:00:00Z");
+    let amd64 = registry.push_image("app", "amd64", "2024-06-01T00:00:00Z");
+    let arm64 = registry.push_image("app", "arm64", "2024-06-01T00:00:00Z");
+    registry.push_index("app", "new", &[&amd64, &arm64]);
+    registry.untag("app", "amd64");
+    registry.untag("app", "arm64");
+
+    let client = client(&registry);
+    let tags = client.resolve_all_tags("app").await.unwrap();
+    let new = tags.iter().find(|t| t.tag == "new").unwrap();
+    assert!(new.size.unwrap() > 2 * LAYER_SIZE);
+
+    // The newest image is multi-platform and must be the one kept
+    let plan = Strategy::KeepRecent(1).apply("app", tags, &Silent);
+    assert_eq!(plan.to_keep[0].tag, "new");
+    assert_eq!(plan.to_delete[0].tag, "old");
+}
+
+#[tokio::test]
+async fn test_accept_negotiation() {
+    let registry = FakeRegistry::start().await;
+    let amd64 = registry.push_image("app", "amd64", "2024-03-01T12:00:00Z");
+    let arm64 = registry.push_image("app", "arm64", "2024-03-01T12:00:00Z");
+    registry.push_index("app", "oci", &[&amd64, &arm64]);
+    registry.push_manifest_list("app", "docker", &[&amd64, &arm64]);
+
+    // A client that only accepts schema 2 gets no index, and the amd64 child
+    // in place of a manifest list
+    let head = |tag: &str| {
+        reqwest::Client::new()
+            .head(format!("{}/v2/app/manifests/{}", registry.url, tag))
+            .header(reqwest::header::ACCEPT, MANIFEST_V2)
+            .send()
+    };
+    assert_eq!(head("oci").await.unwrap().status(), StatusCode::NOT_FOUND);
+    let resp = head("docker").await.unwrap();
+    assert_eq!(resp.headers()["Docker-Content-Digest"], amd64.as_str());
+}
+
+#[tokio::test]
+async fn test_delete_semantics() {
+    let registry = FakeRegistry::start().await;
+    let image = registry.push_image("app", "v1", "2024-01-01T00:00:00Z");
+    registry.tag("app", "stable", &image);
+    let other = registry.push_image("app", "v2", "2024-01-02T00:00:00Z");
+
+    let client = client(&registry);
+    client.delete_manifest("app", &image).await.unwrap();
+    assert_eq!(registry.tags("app"), vec!["v2"]);
+
+    let err = client.delete_manifest("app", &image).await.unwrap_err();
+    assert_eq!(ErrorKind::of(&err), ErrorKind::NotFound);
+
+    registry.disable_deletes();
+    let err = client.delete_manifest("app", &other).await.unwrap_err();
+    assert_eq!(ErrorKind::of(&err), ErrorKind::DeletionDisabled);
+    assert!(registry.has_manifest("app", &other));
+}
+
+#[tokio::test]
+async fn test_basic_auth() {
+    let registry = FakeRegistry::start().await;
+    registry.push_image("app", "v1", "2024-01-01T00:00:00Z");
+    registry.require_basic_auth("ci", "secret");
+
+    let err = client(&registry).list_repositories().await.unwrap_err();
+    assert_eq!(ErrorKind::of(&err), ErrorKind::Auth);
+
+    let auth = Auth::from_args(Some("ci"), Some("secret"), None).unwrap();
+    let client = client(&registry).with_auth(&auth, &Tls::default()).unwrap();
+    assert_eq!(client.list_repositories().await.unwrap(), vec!["app"]);
+}
+
+#[tokio::test]
+async fn test_execute_partial_failure() {
+    let registry = FakeRegistry::start().await;
+    let old = registry.push_image("app", "v1", "2024-01-01T00:00:00Z");
+    let broken = registry.push_image("app", "v2", "2024-01-02T00:00:00Z");
+    registry.push_image("app", "v3", "2024-01-03T00:00:00Z");
+    registry.fail(
+        Method::DELETE,
+        &broken,
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "UNKNOWN",
+    );
+
+    let client = client(&registry);
+    let executor = Executor::new(&client, &Silent);
+    let resolved = executor.resolve(&["app".to_string()]).await;
+    assert!(resolved.failures.is_empty());
+    let plans = resolved
+        .repositories
+        .into_iter()
+        .map(|(repo, tags)| Strategy::KeepRecent(1).apply(&repo, tags, &Silent))
+        .collect();
+
+    let report = executor
+        .execute(plans, &resolved.index, |_, _, _, _| Ok(()))
+        .await
+        .unwrap();
+    assert_eq!(report.deleted_digests, 1);
+    assert_eq!(report.errors, 1);
+    assert_eq!(report.failures[0].digest.as_deref(), Some(broken.as_str()));
+    assert_eq!(report.failures[0].kind, ErrorKind::RegistryStatus);
+    assert!(!registry.has_manifest("app", &old));
+    assert_eq!(registry.tags("app"), vec!["v2", "v3"]);
+}
+
+/// Declines every run it is asked to confirm
+struct Decline;
+
+#[async_trait::async_trait]
+impl CleanHooks for Decline {
+    async fn confirm(&mut self, _plans: &[CleanupPlan]) -> anyhow::Result<bool> {
+        Ok(false)
+    }
+}
+
+#[tokio::test]
+async fn test_clean() {
+    let registry = FakeRegistry::start().await;
+    for (tag, created) in [
+        ("v1", "2024-01-01T00:00:00Z"),
+        ("v2", "2024-02-01T00:00:00Z"),
+        ("v3", "2024-03-01T00:00:00Z"),
+    ] {
+        registry.push_image("app", tag, created);
+    }
+
+    let client = client(&registry);
+    let executor = Executor::new(&client, &Silent);
+    let repos = ["app".to_string()];
+    let strategy = Strategy::KeepRecent(1);
+
+    let limited = CleanOptions {
+        limits: Limits {
+            max_tags: Some(1),
+            ..Limits::default()
+        },
+        ..CleanOptions::default()
+    };
+    let err = executor
+        .clean(&repos, &strategy, &limited, &mut Unattended)
+        .await
+        .unwrap_err();
+    assert_eq!(ErrorKind::of(&err), ErrorKind::LimitExceeded);
+
+    let options = CleanOptions::default();
+    let err = executor
+        .clean(&repos, &strategy, &options, &mut Decline)
+        .await
+        .unwrap_err();
+    assert_eq!(ErrorKind::of(&err), ErrorKind::NotConfirmed);
+    assert!(registry.requests(Method::DELETE).is_empty());
+
+    let report = executor
+        .clean(&repos, &strategy, &options, &mut Unattended)
+        .await
+        .unwrap();
+    assert_eq!(report.deleted_tags, 2);
+    assert_eq!(registry.tags("app"), vec!["v3"]);
+}
+
+/// Keeps the delete and keep counts of every published plan
+#[derive(Default)]
+struct Plans(Mutex<Vec<(String, usize, usize)>>);
+
+impl Reporter for Plans {
+    fn report(&self, event: Event) {
+        if let Event::PlanReady {
+            repository,
+            delete,
+            keep,
+        } = event
+        {
+            self.0.lock().unwrap().push((repository, delete, keep));
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_plan_ready_after_protection() {
+    let registry = FakeRegistry::start().await;
+    let old = registry.push_image("app", "v1", "2024-01-01T00:00:00Z");
+    registry.push_image("app", "v2", "2024-02-01T00:00:00Z");
+    registry.tag("other", "stable", &old);
+
+    let client = client(&registry);
+    let plans = Plans::default();
+    let options = CleanOptions {
+        protect_across_repos: true,
+        ..CleanOptions::default()
+    };
+    let report = Executor::new(&client, &plans)
+        .dry_run(true)
+        .clean(
+            &["app".to_string()],
+            &Strategy::KeepRecent(1),
+            &options,
+            &mut Unattended,
+        )
+        .await
+        .unwrap();
+
+    // v1 is still tagged in `other`, so the published plan keeps it
+    assert_eq!(report.deleted_tags, 0);
+    assert_eq!(*plans.0.lock().unwrap(), [("app".to_string(), 0, 2)]);
+}
+
+#[tokio::test]
+async fn test_deleted_bytes_shared_layer() {
+    let registry = FakeRegistry::start().await;
+    registry.push_image_with_layer("app", "v1", "2024-01-01T00:00:00Z", "base");
+    registry.push_image("app", "v2", "2024-02-01T00:00:00Z");
+    registry.push_image_with_layer("other", "stable", "2024-01-01T00:00:00Z", "base");
+
+    let client = client(&registry);
+    let report = Executor::new(&client, &Silent)
+        .dry_run(true)
+        .clean(
+            &["app".to_string()],
+            &Strategy::KeepRecent(1),
+            &CleanOptions::default(),
+            &mut Unattended,
+        )
+        .await
+        .unwrap();
+
+    // v1 goes, but its layer is still used by a repository outside the run:
+    // only its config blob is reclaimed
+    assert_eq!(report.deleted_tags, 1);
+    assert!(report.deleted_bytes > 0);
+    assert!(report.deleted_bytes < LAYER_SIZE);
+}